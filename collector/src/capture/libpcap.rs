@@ -0,0 +1,191 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use palantir_core::log::{DnsResponseEvent, Event};
+
+use super::{query_event, NetCapture};
+use crate::{emit, fmt_rfc3339_local};
+
+/// In-process capture with libpcap: no tshark binary needed, only CAP_NET_RAW.
+/// Decodes UDP/53 packets itself and emits the same events as the tshark backend.
+pub struct Pcap {
+    pub attribute: bool,
+}
+
+impl NetCapture for Pcap {
+    fn name(&self) -> &'static str {
+        "pcap"
+    }
+
+    fn run(&mut self) -> anyhow::Result<()> {
+        let mut cap = pcap::Capture::from_device("any")?
+            .immediate_mode(true)
+            .snaplen(65535)
+            .timeout(1000)
+            .open()?;
+        cap.filter("udp port 53", true)?;
+        let link = cap.get_datalink().0;
+
+        loop {
+            let packet = match cap.next_packet() {
+                Ok(p) => p,
+                Err(pcap::Error::TimeoutExpired) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let epoch = packet.header.ts.tv_sec as f64 + packet.header.ts.tv_usec as f64 / 1_000_000.0;
+            let Some((src, dst, src_port, payload)) = udp_payload(link, packet.data) else { continue; };
+            let Some(msg) = parse_dns(payload) else { continue; };
+
+            let evt = if msg.is_response {
+                Event::DnsResponse(DnsResponseEvent {
+                    ts: fmt_rfc3339_local(epoch),
+                    client_ip: dst.to_string(),
+                    dns_qname: msg.qname,
+                    rcode: msg.rcode,
+                    resolved: msg.resolved,
+                })
+            } else {
+                query_event(fmt_rfc3339_local(epoch), src.to_string(), msg.qname, Some(src_port), self.attribute)
+            };
+            emit(&evt)?;
+        }
+    }
+}
+
+// link-layer header types (tcpdump.org/linktypes.html)
+const LINKTYPE_ETHERNET: i32 = 1;
+const LINKTYPE_RAW: i32 = 101;
+const LINKTYPE_LINUX_SLL: i32 = 113;
+const LINKTYPE_LINUX_SLL2: i32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+const IPPROTO_UDP: u8 = 17;
+
+fn be16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*b.get(at)?, *b.get(at + 1)?]))
+}
+
+/// (source, destination, source port, UDP payload) of an IPv4/IPv6 UDP frame; None for anything else
+fn udp_payload(link: i32, frame: &[u8]) -> Option<(IpAddr, IpAddr, u16, &[u8])> {
+    let (ethertype, ip) = match link {
+        LINKTYPE_ETHERNET => {
+            let mut off = 12;
+            let mut t = be16(frame, off)?;
+            if t == ETHERTYPE_VLAN {
+                off += 4;
+                t = be16(frame, off)?;
+            }
+            (Some(t), frame.get(off + 2..)?)
+        }
+        LINKTYPE_LINUX_SLL => (Some(be16(frame, 14)?), frame.get(16..)?),
+        LINKTYPE_LINUX_SLL2 => (Some(be16(frame, 0)?), frame.get(20..)?),
+        LINKTYPE_RAW => (None, frame),
+        _ => return None,
+    };
+
+    let version = ip.first()? >> 4;
+    let (src, dst, udp) = match (ethertype, version) {
+        (Some(ETHERTYPE_IPV4) | None, 4) => {
+            let ihl = ((ip[0] & 0x0f) as usize) * 4;
+            if *ip.get(9)? != IPPROTO_UDP || ihl < 20 {
+                return None;
+            }
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (IpAddr::V4(Ipv4Addr::from(src)), IpAddr::V4(Ipv4Addr::from(dst)), ip.get(ihl..)?)
+        }
+        // extension headers are not followed; DNS over IPv6 practically never has any
+        (Some(ETHERTYPE_IPV6) | None, 6) => {
+            if *ip.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(src)), IpAddr::V6(Ipv6Addr::from(dst)), ip.get(40..)?)
+        }
+        _ => return None,
+    };
+    Some((src, dst, be16(udp, 0)?, udp.get(8..)?))
+}
+
+/// The fields of a DNS message the events carry
+struct DnsMessage {
+    is_response: bool,
+    rcode: u8,
+    qname: String,
+    // A and AAAA answers
+    resolved: Vec<String>,
+}
+
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+
+fn parse_dns(msg: &[u8]) -> Option<DnsMessage> {
+    let flags = be16(msg, 2)?;
+    let qdcount = be16(msg, 4)?;
+    let ancount = be16(msg, 6)?;
+    if qdcount == 0 {
+        return None;
+    }
+
+    let (qname, mut off) = read_name(msg, 12)?;
+    // qtype, qclass of the first question; further questions (never seen in practice) are skipped
+    off += 4;
+    for _ in 1..qdcount {
+        off = read_name(msg, off)?.1 + 4;
+    }
+
+    let mut resolved = Vec::new();
+    for _ in 0..ancount {
+        let Some((_, after)) = read_name(msg, off) else { break; };
+        let (Some(rtype), Some(rdlen)) = (be16(msg, after), be16(msg, after + 8)) else { break; };
+        let rdata_at = after + 10;
+        let Some(rdata) = msg.get(rdata_at..rdata_at + rdlen as usize) else { break; };
+        match (rtype, rdata.len()) {
+            (DNS_TYPE_A, 4) => resolved.push(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string()),
+            (DNS_TYPE_AAAA, 16) => {
+                let b: [u8; 16] = rdata.try_into().ok()?;
+                resolved.push(Ipv6Addr::from(b).to_string());
+            }
+            _ => {}
+        }
+        off = rdata_at + rdlen as usize;
+    }
+
+    Some(DnsMessage {
+        is_response: flags & 0x8000 != 0,
+        rcode: (flags & 0x000f) as u8,
+        qname,
+        resolved,
+    })
+}
+
+/// Dotted name at `at` (following compression pointers) and the offset right after it,
+/// without the trailing dot, as tshark prints dns.qry.name
+fn read_name(msg: &[u8], at: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = at;
+    let mut end = None;
+    // bounds pointer loops in malformed packets
+    for _ in 0..128 {
+        let len = *msg.get(pos)? as usize;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            l if l & 0xc0 == 0xc0 => {
+                let target = ((l & 0x3f) << 8) | *msg.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            l => {
+                let label = msg.get(pos + 1..pos + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+    None
+}
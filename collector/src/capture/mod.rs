@@ -0,0 +1,45 @@
+//! DNS capture backends. Each one emits the same `net` / `dns_response` events.
+
+#[cfg(feature = "pcap")]
+mod libpcap;
+mod owner;
+mod tshark;
+
+use palantir_core::log::{Event, NetEvent};
+
+/// A source of DNS traffic, selected with PAL_CAPTURE
+pub trait NetCapture: Send {
+    /// Backend name, also the prefix of its restart meta event (`tshark_restart`)
+    fn name(&self) -> &'static str;
+
+    /// Capture and emit events until the capture stops; Err says why
+    fn run(&mut self) -> anyhow::Result<()>;
+}
+
+/// PAL_CAPTURE: `tshark` (default) or `pcap` (collector built with `--features pcap`).
+/// PAL_ATTRIBUTE_DNS=true tags queries with the process that sent them.
+pub fn from_env() -> anyhow::Result<Box<dyn NetCapture>> {
+    let attribute = std::env::var("PAL_ATTRIBUTE_DNS").is_ok_and(|v| v.trim() == "true");
+    let raw = std::env::var("PAL_CAPTURE").unwrap_or_default();
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "" | "tshark" => Ok(Box::new(tshark::Tshark { attribute })),
+        #[cfg(feature = "pcap")]
+        "pcap" => Ok(Box::new(libpcap::Pcap { attribute })),
+        #[cfg(not(feature = "pcap"))]
+        "pcap" => anyhow::bail!("PAL_CAPTURE=pcap, but this collector was built without the pcap feature"),
+        other => anyhow::bail!("unknown PAL_CAPTURE '{other}' (tshark or pcap)"),
+    }
+}
+
+/// Event for a query sent from local UDP port `src_port`; when `attribute` is on and the
+/// socket is still open, tagged with the process that owns it
+fn query_event(ts: String, src_ip: String, dns_qname: String, src_port: Option<u16>, attribute: bool) -> Event {
+    let owner = src_port.filter(|_| attribute).and_then(owner::udp_port_owner);
+    Event::Net(NetEvent {
+        ts,
+        src_ip,
+        dns_qname,
+        pid: owner.as_ref().map(|(pid, _)| *pid),
+        comm: owner.map(|(_, comm)| comm),
+    })
+}
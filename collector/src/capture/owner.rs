@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+use std::fs;
+
+/// Best-effort owner (pid, name) of the local UDP socket bound to `port`: the socket inode from
+/// /proc/net/udp{,6}, then the process holding it open. None when the socket is already closed
+/// (the resolver finished before we got to it) or belongs to another machine or namespace.
+pub fn udp_port_owner(port: u16) -> Option<(i32, String)> {
+    let inodes = udp_inodes(port);
+    if inodes.is_empty() {
+        return None;
+    }
+    let pid = pid_holding(&inodes)?;
+    let name = crate::exe_basename(pid).or_else(|| {
+        fs::read_to_string(format!("/proc/{pid}/comm")).ok().map(|c| c.trim().to_lowercase())
+    })?;
+    Some((pid, name))
+}
+
+/// Inodes of the UDP sockets whose local port is `port`
+fn udp_inodes(port: u16) -> HashSet<String> {
+    let mut inodes = HashSet::new();
+    for table in ["/proc/net/udp", "/proc/net/udp6"] {
+        let Ok(text) = fs::read_to_string(table) else { continue; };
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+        for line in text.lines().skip(1) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let (Some(local), Some(inode)) = (cols.get(1), cols.get(9)) else { continue; };
+            let local_port = local.rsplit(':').next().and_then(|p| u16::from_str_radix(p, 16).ok());
+            if local_port == Some(port) && *inode != "0" {
+                inodes.insert(inode.to_string());
+            }
+        }
+    }
+    inodes
+}
+
+/// First process with an fd pointing at one of the socket inodes
+fn pid_holding(inodes: &HashSet<String>) -> Option<i32> {
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<i32>().ok()) else { continue; };
+        let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else { continue; };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else { continue; };
+            let target = target.to_string_lossy();
+            if let Some(inode) = target.strip_prefix("socket:[").and_then(|t| t.strip_suffix(']')) {
+                if inodes.contains(inode) {
+                    return Some(pid);
+                }
+            }
+        }
+    }
+    None
+}
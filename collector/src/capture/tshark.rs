@@ -0,0 +1,114 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use palantir_core::log::{DnsResponseEvent, Event};
+
+use super::{query_event, NetCapture};
+use crate::{emit, fmt_rfc3339_local};
+
+/// The original backend: `tshark` as a child process printing one tab separated line per packet
+pub struct Tshark {
+    pub attribute: bool,
+}
+
+impl NetCapture for Tshark {
+    fn name(&self) -> &'static str {
+        "tshark"
+    }
+
+    fn run(&mut self) -> anyhow::Result<()> {
+        read_tshark(self.attribute)
+    }
+}
+
+fn spawn_tshark() -> std::io::Result<std::process::Child> {
+    // Using frame.time_epoch so we control formatting
+    Command::new("tshark")
+        .arg("-i").arg("any")
+        .arg("-l")
+        .arg("-q")
+        .arg("-f").arg("udp port 53")
+        // queries and responses; the response flag below tells them apart
+        .arg("-T").arg("fields")
+        .arg("-e").arg("frame.time_epoch")
+        .arg("-e").arg("ip.src")
+        .arg("-e").arg("dns.qry.name")
+        .arg("-e").arg("dns.flags.response")
+        .arg("-e").arg("ip.dst")
+        .arg("-e").arg("dns.flags.rcode")
+        .arg("-e").arg("dns.a")
+        .arg("-e").arg("dns.aaaa")
+        .arg("-e").arg("udp.srcport")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+}
+
+/// Emit events until tshark exits; Err says why (spawn failure, exit status)
+fn read_tshark(attribute: bool) -> anyhow::Result<()> {
+    let mut child = spawn_tshark()?;
+    let out = child.stdout.take().unwrap();
+    let result = read_tshark_lines(out, attribute);
+    // reap it, and stop it if we bailed out while it was still running
+    let _ = child.kill();
+    let status = child.wait()?;
+    result?;
+    anyhow::bail!("tshark exited with {status}")
+}
+
+fn read_tshark_lines(out: std::process::ChildStdout, attribute: bool) -> anyhow::Result<()> {
+    let mut br = BufReader::new(out);
+    let mut line = String::new();
+
+    while br.read_line(&mut line)? != 0 {
+        // only strip the line ending; empty trailing fields are still tab separated
+        let raw = line
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+        
+        line.clear();
+
+        if raw.trim().is_empty() { 
+            continue; 
+        }
+        
+        let parts: Vec<&str> = raw
+            .split('\t')
+            .collect();
+
+        if parts.len() < 3 { 
+            continue; 
+        }
+
+        let epoch: f64 = parts[0].parse().unwrap_or(0.0);
+        let field = |i: usize| parts.get(i).map(|s| s.trim()).unwrap_or("");
+        // older tshark prints booleans as 1/0, newer ones as True/False
+        let is_response = matches!(field(3), "1" | "True" | "true");
+        let evt = if is_response {
+            let resolved = [field(6), field(7)]
+                .iter()
+                .flat_map(|f| f.split(','))
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .map(str::to_string)
+                .collect();
+            Event::DnsResponse(DnsResponseEvent {
+                ts: fmt_rfc3339_local(epoch),
+                client_ip: field(4).to_string(),
+                dns_qname: field(2).to_string(),
+                rcode: field(5).parse().unwrap_or(0),
+                resolved,
+            })
+        } else {
+            query_event(
+                fmt_rfc3339_local(epoch),
+                field(1).to_string(),
+                field(2).to_string(),
+                field(8).parse().ok(),
+                attribute,
+            )
+        };
+        emit(&evt)?;
+    }
+    Ok(())
+}
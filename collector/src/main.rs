@@ -1,39 +1,38 @@
-use std::collections::{HashMap, HashSet};
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
 use std::{fs, thread};
 use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Local, TimeZone};
 use regex::Regex;
-use serde::Serialize;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
 
-#[derive(Serialize, Debug, Clone)]
-#[serde(tag = "kind")]
-enum Event {
-    net(NetEvent),
-    proc(ProcEvent),
-}
+mod capture;
+mod sink;
 
-#[derive(Serialize, Debug, Clone)]
-struct ProcEvent {
-    ts: String,
-    user: String,
-    pid: i32,
-    comm: String,   // canonicalized name like "firefox"
-    action: String, // "start" or "stop"
-}
+use palantir_core::log::{Event, MetaEvent, ProcEvent, LOG_FORMAT_VERSION, META_FORMAT_VERSION, META_HEARTBEAT, META_SHUTDOWN};
+use sink::{RotatingFile, Sink};
 
-// keep NetEvent as before
+/// Longest command line kept on a start event
+const ARGS_MAX_CHARS: usize = 512;
 
-#[derive(Serialize, Debug, Clone)]
-struct NetEvent {
-    ts: String,        // RFC3339 in local time
-    src_ip: String,    // ip.src
-    dns_qname: String, // dns.qry.name
-}
+/// Most names kept in a start event's ancestry, the process itself included
+const ANCESTRY_MAX_DEPTH: usize = 5;
 
+/// Most restarts of one watcher within RESTART_WINDOW; further ones wait for the window to clear
+const MAX_RESTARTS: usize = 10;
+const RESTART_WINDOW: Duration = Duration::from_secs(600);
 
+/// A watcher that ran at least this long is considered healthy again and restarts without delay
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+/// ps failures in a row before the process watcher gives up and is restarted
+const PS_MAX_FAILURES: u32 = 5;
+
+/// A running process counts as stopped once it is missing from this many snapshots in a row
+const STOP_GRACE_SNAPSHOTS: u32 = 2;
 
 lazy_static::lazy_static! {
     static ref RE_PS_LINE: Regex = Regex::new(
@@ -42,6 +41,65 @@ lazy_static::lazy_static! {
 }
 
 
+// the palantir.log batch record (or stdout with --stdout); set once in main
+static SINK: OnceLock<Mutex<Sink>> = OnceLock::new();
+
+// set when PAL_LIVE_URL is configured; every emitted line is also offered to the live streamer
+static LIVE_TX: OnceLock<mpsc::Sender<String>> = OnceLock::new();
+
+/// Write one event line to the log sink and, if enabled, the live stream.
+fn emit(evt: &Event) -> anyhow::Result<()> {
+    let line = serde_json::to_string(evt)?;
+    if let Some(sink) = SINK.get() {
+        sink.lock().unwrap().write_line(&line)?;
+    }
+    if let Some(tx) = LIVE_TX.get() {
+        let _ = tx.send(line);
+    }
+    Ok(())
+}
+
+/// Stream event lines to the server over a WebSocket, reconnecting with backoff.
+/// While disconnected, lines are dropped from the live stream only: the log file still
+/// receives everything, so the batch upload remains complete.
+fn run_live_streamer(url: String, token: String, rx: mpsc::Receiver<String>) {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match live_request(&url, &token).and_then(|req| Ok(tungstenite::connect(req)?)) {
+            Ok((mut socket, _)) => {
+                eprintln!("live: connected");
+                backoff = Duration::from_secs(1);
+                // skip whatever piled up while we were offline
+                while rx.try_recv().is_ok() {}
+                loop {
+                    let Ok(line) = rx.recv() else { return; };
+                    if let Err(e) = socket.send(tungstenite::Message::Text(line)) {
+                        eprintln!("live: send failed, falling back to batch only: {e}");
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("live: connect failed ({e}), retrying in {}s", backoff.as_secs());
+            }
+        }
+        thread::sleep(backoff);
+        while rx.try_recv().is_ok() {}
+        backoff = (backoff * 2).min(Duration::from_secs(60));
+    }
+}
+
+/// Upgrade request carrying the seat token; the server takes assignment and seat from it
+fn live_request(url: &str, token: &str) -> anyhow::Result<tungstenite::handshake::client::Request> {
+    use tungstenite::client::IntoClientRequest;
+    let mut req = url.into_client_request()?;
+    req.headers_mut().insert(
+        tungstenite::http::header::AUTHORIZATION,
+        format!("Bearer {token}").parse()?,
+    );
+    Ok(req)
+}
+
 fn fmt_rfc3339_local(epoch: f64) -> String {
     // epoch may have nanos after the decimal
     let secs = epoch.trunc() as i64;
@@ -53,57 +111,72 @@ fn now_local_rfc3339() -> String {
     Local::now().to_rfc3339()
 }
 
-fn spawn_tshark() -> std::io::Result<std::process::ChildStdout> {
-    // Using frame.time_epoch so we control formatting
-    let mut child = Command::new("tshark")
-        .arg("-i").arg("any")
-        .arg("-l")
-        .arg("-q")
-        .arg("-f").arg("udp port 53")
-        .arg("-Y").arg("dns.flags.response==0")
-        .arg("-T").arg("fields")
-        .arg("-e").arg("frame.time_epoch")
-        .arg("-e").arg("ip.src")
-        .arg("-e").arg("dns.qry.name")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .spawn()?;
-    Ok(child.stdout.take().unwrap())
-}
-
-fn read_tshark() -> anyhow::Result<()> {
-    let out = spawn_tshark()?;
-    let mut br = BufReader::new(out);
-    let mut line = String::new();
-
-    while br.read_line(&mut line)? != 0 {
-        let raw = line
-            .trim()
-            .to_string();
-        
-        line.clear();
-
-        if raw.is_empty() { 
-            continue; 
-        }
-        
-        let parts: Vec<&str> = raw
-            .split('\t')
-            .collect();
+fn format_version_event() -> Event {
+    Event::Meta(MetaEvent {
+        ts: now_local_rfc3339(),
+        event: META_FORMAT_VERSION.to_string(),
+        reason: None,
+        format_version: Some(LOG_FORMAT_VERSION),
+    })
+}
+
+/// Header line of a freshly rotated log file
+fn format_version_line() -> String {
+    serde_json::to_string(&format_version_event()).unwrap_or_default()
+}
 
-        if parts.len() < 3 { 
-            continue; 
+/// Emit a `heartbeat` meta event every `interval`, so the analyzer can tell an idle student
+/// (heartbeats, no other events) from a collector that wasn't running (no heartbeats either)
+fn heartbeat(interval: Duration) {
+    loop {
+        let _ = emit(&Event::Meta(MetaEvent {
+            ts: now_local_rfc3339(),
+            event: META_HEARTBEAT.to_string(),
+            reason: None,
+            format_version: None,
+        }));
+        thread::sleep(interval);
+    }
+}
+
+/// Run `watch` forever: whenever it returns (error or not) log why, write a `meta` event
+/// named `restart_event` and start it again, backing off 1s..60s while it keeps failing fast
+/// and never more than MAX_RESTARTS times per RESTART_WINDOW.
+fn supervise(name: &str, restart_event: &str, mut watch: impl FnMut() -> anyhow::Result<()>) {
+    let mut backoff = Duration::from_secs(1);
+    let mut restarts: VecDeque<Instant> = VecDeque::new();
+    loop {
+        let started = Instant::now();
+        let reason = match watch() {
+            Ok(()) => "exited".to_string(),
+            Err(e) => format!("{e:#}"),
+        };
+        eprintln!("{name}: stopped ({reason}), restarting in {}s", backoff.as_secs());
+
+        if started.elapsed() >= HEALTHY_RUN {
+            backoff = Duration::from_secs(1);
         }
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(Duration::from_secs(60));
 
-        let epoch: f64 = parts[0].parse().unwrap_or(0.0);
-        let evt = Event::net(NetEvent {
-            ts: fmt_rfc3339_local(epoch),
-            src_ip: parts[1].to_string(),
-            dns_qname: parts[2].to_string(),
-        });
-        println!("{}", serde_json::to_string(&evt)?);
+        while restarts.front().is_some_and(|t| t.elapsed() > RESTART_WINDOW) {
+            restarts.pop_front();
+        }
+        if restarts.len() >= MAX_RESTARTS {
+            let wait = RESTART_WINDOW.saturating_sub(restarts[0].elapsed());
+            eprintln!("{name}: {MAX_RESTARTS} restarts in {}s, waiting {}s", RESTART_WINDOW.as_secs(), wait.as_secs());
+            thread::sleep(wait);
+            restarts.pop_front();
+        }
+        restarts.push_back(Instant::now());
+
+        let _ = emit(&Event::Meta(MetaEvent {
+            ts: now_local_rfc3339(),
+            event: restart_event.to_string(),
+            reason: Some(reason),
+            format_version: None,
+        }));
     }
-    Ok(())
 }
 
 #[derive(Clone, Debug)]
@@ -128,16 +201,14 @@ fn ps_snapshot(user: &str, ignore_prefixes: &[&str]) -> anyhow::Result<std::coll
     let stdout = String::from_utf8_lossy(&output.stdout);
     let mut map = std::collections::HashMap::new();
 
-    'lines: for line in stdout.lines() {
+    for line in stdout.lines() {
         if let Some(c) = RE_PS_LINE.captures(line) {
             let pid: i32 = c["pid"].parse().unwrap_or(-1);
             let ppid: i32 = c["ppid"].parse().unwrap_or(-1);
             let comm = c.name("comm").map(|m| m.as_str()).unwrap_or("").to_string();
             let args = c.name("args").map(|m| m.as_str()).unwrap_or("").to_string();
 
-            for p in ignore_prefixes {
-                if comm.starts_with(p) || args.starts_with(p) { continue 'lines; }
-            }
+            if is_ignored(&comm, &args, ignore_prefixes) { continue; }
             if pid > 0 {
                 map.insert(pid, PsRow { pid, ppid, comm, args });
             }
@@ -146,6 +217,109 @@ fn ps_snapshot(user: &str, ignore_prefixes: &[&str]) -> anyhow::Result<std::coll
     Ok(map)
 }
 
+fn is_ignored(comm: &str, args: &str, ignore_prefixes: &[&str]) -> bool {
+    ignore_prefixes.iter().any(|p| comm.starts_with(p) || args.starts_with(p))
+}
+
+/// Same rows as ps_snapshot, read straight from /proc: no process spawned per tick, so it is
+/// cheap enough to poll fast. Processes owned (effective uid) by `uid`.
+fn proc_snapshot(uid: u32, ignore_prefixes: &[&str]) -> anyhow::Result<HashMap<i32, PsRow>> {
+    let mut map = HashMap::new();
+    for entry in fs::read_dir("/proc")?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<i32>().ok()) else { continue; };
+        // it may exit while we read it; then it simply isn't in this snapshot
+        let Ok(status) = fs::read_to_string(format!("/proc/{pid}/status")) else { continue; };
+        let mut euid = None;
+        let mut ppid = -1;
+        for line in status.lines() {
+            if let Some(ids) = line.strip_prefix("Uid:") {
+                euid = ids.split_whitespace().nth(1).and_then(|s| s.parse::<u32>().ok());
+            } else if let Some(p) = line.strip_prefix("PPid:") {
+                ppid = p.trim().parse().unwrap_or(-1);
+            }
+        }
+        if euid != Some(uid) { continue; }
+
+        let comm = fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default().trim_end().to_string();
+        let cmdline = fs::read(format!("/proc/{pid}/cmdline")).unwrap_or_default();
+        // like ps: kernel threads and zombies have no command line and show as [comm]
+        let args = if cmdline.is_empty() {
+            format!("[{comm}]")
+        } else {
+            String::from_utf8_lossy(&cmdline).trim_end_matches('\0').replace('\0', " ")
+        };
+
+        if is_ignored(&comm, &args, ignore_prefixes) { continue; }
+        map.insert(pid, PsRow { pid, ppid, comm, args });
+    }
+    Ok(map)
+}
+
+/// Numeric uid of a user name (or of a number given as is), from /etc/passwd
+fn user_uid(user: &str) -> anyhow::Result<u32> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    let passwd = fs::read_to_string("/etc/passwd")?;
+    passwd
+        .lines()
+        .map(|l| l.split(':').collect::<Vec<_>>())
+        .find(|f| f.first() == Some(&user))
+        .and_then(|f| f.get(2)?.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("no user '{user}' in /etc/passwd"))
+}
+
+/// Where process snapshots come from (PAL_PS_SOURCE)
+#[derive(Clone, Copy, Debug)]
+enum ProcSource {
+    Ps,
+    Proc { uid: u32 },
+}
+
+/// How often the process watcher polls (PAL_PS_*)
+#[derive(Clone, Copy, Debug)]
+struct PollConfig {
+    // PAL_PS_INTERVAL_MILLIS: every tick in fixed mode, the slowest tick in adaptive mode
+    interval: Duration,
+    // PAL_PS_ADAPTIVE: after any start or stop, poll every `fast` for `fast_window`,
+    // then double the interval on each quiet tick back up to `interval`
+    adaptive: bool,
+    fast: Duration,
+    fast_window: Duration,
+    source: ProcSource,
+}
+
+impl PollConfig {
+    fn from_env(user: &str) -> anyhow::Result<Self> {
+        let millis = |k: &str, d: u64| Duration::from_millis(std::env::var(k).ok().and_then(|s| s.parse().ok()).unwrap_or(d));
+        let source = match std::env::var("PAL_PS_SOURCE").unwrap_or_default().trim() {
+            "" | "ps" => ProcSource::Ps,
+            "proc" => ProcSource::Proc { uid: user_uid(user)? },
+            other => anyhow::bail!("unknown PAL_PS_SOURCE '{other}' (ps or proc)"),
+        };
+        Ok(PollConfig {
+            interval: millis("PAL_PS_INTERVAL_MILLIS", 500),
+            adaptive: std::env::var("PAL_PS_ADAPTIVE").is_ok_and(|v| v.trim() == "true"),
+            fast: millis("PAL_PS_FAST_MILLIS", 100),
+            fast_window: millis("PAL_PS_FAST_WINDOW_MILLIS", 3000),
+            source,
+        })
+    }
+}
+
+fn snapshot(source: ProcSource, user: &str, ignore_prefixes: &[&str]) -> anyhow::Result<HashMap<i32, PsRow>> {
+    match source {
+        ProcSource::Ps => ps_snapshot(user, ignore_prefixes),
+        ProcSource::Proc { uid } => proc_snapshot(uid, ignore_prefixes),
+    }
+}
+
+fn exe_path(pid: i32) -> Option<String> {
+    let path = fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+    let p = path.to_string_lossy().to_string();
+    if p.is_empty() { None } else { Some(p) }
+}
+
 fn exe_basename(pid: i32) -> Option<String> {
     let path = fs::read_link(format!("/proc/{pid}/exe")).ok()?;
     let base = path.file_name()?.to_string_lossy().to_string();
@@ -208,79 +382,227 @@ fn canonical_name(pid: i32, curr: &std::collections::HashMap<i32, PsRow>) -> Str
 }
 
 
-fn watch_processes(user: String, interval: Duration) -> anyhow::Result<()> {
-    let ignore_prefixes = [
-        "gnome-", "gsd-", "ibus-", "gvfs", "at-spi", "dbus", "xdg-", "systemd",
-        "speech", "snapd", "wireplumber", "pipewire",
-    ];
+/// Names of `pid` and its parents, e.g. `["bash", "code", "curl"]`, outermost first.
+/// Stops at ANCESTRY_MAX_DEPTH or at the first parent outside the snapshot (another user's process).
+fn ancestry(pid: i32, curr: &std::collections::HashMap<i32, PsRow>) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut p = pid;
+    while names.len() < ANCESTRY_MAX_DEPTH {
+        let Some(row) = curr.get(&p) else { break; };
+        let name = if row.comm.is_empty() {
+            exe_basename(p).unwrap_or_else(|| "unknown".to_string())
+        } else {
+            row.comm.to_lowercase()
+        };
+        names.push(name);
+        if row.ppid == p { break; }
+        p = row.ppid;
+    }
+    names.reverse();
+    names
+}
 
-    // debounce and active state
-    use std::collections::{HashMap, HashSet};
-    let mut seen_once: HashSet<i32> = HashSet::new();
 
-    // store canonical names for stops
-    let mut active: HashMap<i32, String> = HashMap::new();
+/// Process watcher state; outlives watcher restarts so running processes aren't reported twice
+#[derive(Default)]
+struct ProcWatch {
+    // canonical names of running processes, for their stop events
+    active: HashMap<i32, String>,
+    // active pids absent from the last snapshots: (snapshots in a row, when first missed)
+    missing: HashMap<i32, (u32, String)>,
+}
 
-    loop {
-        let curr = ps_snapshot(&user, &ignore_prefixes)?;
+impl ProcWatch {
+    /// Start and stop events for the next snapshot `curr`, taken at `now`
+    fn apply(&mut self, user: &str, curr: &HashMap<i32, PsRow>, now: &str) -> Vec<Event> {
+        let ProcWatch { active, missing } = self;
+        let mut events = Vec::new();
 
-        // starts
+        // starts, on first sighting: a curl that lives for a single tick is exactly what we want
         for pid in curr.keys() {
-            if active.contains_key(pid) { continue; }
-            if !seen_once.contains(pid) {
-                seen_once.insert(*pid);
+            if active.contains_key(pid) {
+                missing.remove(pid);
                 continue;
             }
-            let name = canonical_name(*pid, &curr);
+            let name = canonical_name(*pid, curr);
             active.insert(*pid, name.clone());
-            let evt = Event::proc(ProcEvent {
-                ts: chrono::Local::now().to_rfc3339(),
-                user: user.clone(),
+            events.push(Event::Proc(ProcEvent {
+                ts: now.to_string(),
+                user: user.to_string(),
                 pid: *pid,
                 comm: name,
                 action: "start".to_string(),
-            });
-            println!("{}", serde_json::to_string(&evt)?);
-
+                exe: exe_path(*pid),
+                args: curr
+                    .get(pid)
+                    .map(|r| r.args.chars().take(ARGS_MAX_CHARS).collect::<String>())
+                    .filter(|a| !a.trim().is_empty()),
+                ppid: curr.get(pid).map(|r| r.ppid),
+                ancestry: Some(ancestry(*pid, curr)),
+            }));
         }
 
         // stops
-        let curr_pids: std::collections::HashSet<i32> = curr.keys().cloned().collect();
-        let gone: Vec<i32> = active.keys().filter(|pid| !curr_pids.contains(pid)).cloned().collect();
+        let gone: Vec<i32> = active.keys().filter(|pid| !curr.contains_key(pid)).cloned().collect();
         for pid in gone {
+            let (misses, since) = missing.entry(pid).or_insert_with(|| (0, now.to_string()));
+            *misses += 1;
+            if *misses < STOP_GRACE_SNAPSHOTS { continue; }
+            // stamped when it first went missing, not when the grace ran out
+            let since = since.clone();
+            missing.remove(&pid);
             if let Some(name) = active.remove(&pid) {
-                let evt = Event::proc(ProcEvent {
-                    ts: chrono::Local::now().to_rfc3339(),
-                    user: user.clone(),
-                    pid,
-                    comm: name,
-                    action: "stop".to_string(),
-                });
-                println!("{}", serde_json::to_string(&evt)?);
+                events.push(stop_event(user, pid, name, since));
             }
-            seen_once.remove(&pid);
         }
+        events
+    }
+}
+
+fn stop_event(user: &str, pid: i32, comm: String, ts: String) -> Event {
+    Event::Proc(ProcEvent {
+        ts,
+        user: user.to_string(),
+        pid,
+        comm,
+        action: "stop".to_string(),
+        exe: None,
+        args: None,
+        ppid: None,
+        ancestry: None,
+    })
+}
+
+/// Poll the process list until it fails PS_MAX_FAILURES times in a row. `state` is only locked while a
+/// snapshot is being applied, so shutdown() can take it over between ticks.
+fn watch_processes(user: &str, poll: PollConfig, state: &Mutex<ProcWatch>) -> anyhow::Result<()> {
+    let ignore_prefixes = [
+        "gnome-", "gsd-", "ibus-", "gvfs", "at-spi", "dbus", "xdg-", "systemd",
+        "speech", "snapd", "wireplumber", "pipewire",
+    ];
+    let mut failures = 0;
+    let mut interval = poll.interval;
+    let mut last_change = Instant::now();
+
+    loop {
+        let curr = match snapshot(poll.source, user, &ignore_prefixes) {
+            Ok(curr) => {
+                failures = 0;
+                curr
+            }
+            Err(e) => {
+                failures += 1;
+                if failures >= PS_MAX_FAILURES {
+                    return Err(e.context(format!("ps failed {failures} times in a row")));
+                }
+                eprintln!("ps snapshot failed ({failures}/{PS_MAX_FAILURES}): {e}");
+                thread::sleep(interval);
+                continue;
+            }
+        };
 
+        let mut guard = state.lock().unwrap();
+        let events = guard.apply(user, &curr, &now_local_rfc3339());
+        let changed = !events.is_empty();
+        for evt in &events {
+            emit(evt)?;
+        }
+        drop(guard);
+
+        if poll.adaptive {
+            if changed {
+                interval = poll.fast;
+                last_change = Instant::now();
+            } else if last_change.elapsed() >= poll.fast_window {
+                interval = (interval * 2).min(poll.interval);
+            }
+        }
         thread::sleep(interval);
     }
 }
 
+/// SIGTERM/SIGINT (exam over, machine shutting down): close every process still running with
+/// a stop event, so the analyzer doesn't have to guess where its interval ends, write a final
+/// `shutdown` meta event and exit
+fn shutdown(user: &str, state: &Mutex<ProcWatch>, signal: i32) -> ! {
+    // held until exit: the watcher can't emit anything after the stops
+    let mut guard = state.lock().unwrap_or_else(|e| e.into_inner());
+    let mut active: Vec<(i32, String)> = guard.active.drain().collect();
+    active.sort();
+    for (pid, name) in active {
+        // already gone, still in its grace period
+        let ts = guard.missing.remove(&pid).map(|(_, since)| since).unwrap_or_else(now_local_rfc3339);
+        let _ = emit(&stop_event(user, pid, name, ts));
+    }
+    let _ = emit(&Event::Meta(MetaEvent {
+        ts: now_local_rfc3339(),
+        event: META_SHUTDOWN.to_string(),
+        reason: Some(format!("signal {signal}")),
+        format_version: None,
+    }));
+    eprintln!("shutdown on signal {signal}");
+    std::process::exit(0);
+}
+
 fn main() -> anyhow::Result<()> {
     let user = std::env::var("MONITOR_USER").unwrap_or_else(|_| "exam".to_string());
-    let poll_millis: u64 = std::env::var("PAL_PS_INTERVAL_MILLIS").ok().and_then(|s| s.parse().ok()).unwrap_or(500);
-    println!("MONITOR: {user}");
+    let poll = PollConfig::from_env(&user)?;
+    eprintln!("MONITOR: {user}");
+
+    let sink = if std::env::args().skip(1).any(|a| a == "--stdout") {
+        Sink::Stdout
+    } else {
+        let path = std::env::var("PAL_LOG_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(palantir_core::log::DEFAULT_LOG_PATH));
+        let max_bytes: u64 = std::env::var("PAL_LOG_MAX_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(sink::DEFAULT_MAX_BYTES);
+        let keep: usize = std::env::var("PAL_LOG_KEEP").ok().and_then(|s| s.parse().ok()).unwrap_or(sink::DEFAULT_KEEP);
+        let file = RotatingFile::open(path.clone(), max_bytes, keep, format_version_line)
+            .map_err(|e| anyhow::anyhow!("open log {}: {e}", path.display()))?;
+        eprintln!("LOG: {} (rotating at {max_bytes} bytes, keeping {keep})", path.display());
+        Sink::File(file)
+    };
+    let _ = SINK.set(Mutex::new(sink));
+    emit(&format_version_event())?;
+
+    // optional live tailing to ws://server:8080/api/v1/live with this seat's token (PAL_LIVE_TOKEN)
+    if let Ok(url) = std::env::var("PAL_LIVE_URL") {
+        if !url.trim().is_empty() {
+            let token = std::env::var("PAL_LIVE_TOKEN").unwrap_or_default().trim().to_string();
+            if token.is_empty() {
+                eprintln!("live: PAL_LIVE_URL is set but PAL_LIVE_TOKEN is not, live tailing disabled");
+            } else {
+                let (tx, rx) = mpsc::channel();
+                let _ = LIVE_TX.set(tx);
+                thread::spawn(move || run_live_streamer(url, token, rx));
+            }
+        }
+    }
 
 
+    let heartbeat_secs: u64 = std::env::var("PAL_HEARTBEAT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30);
+    if heartbeat_secs > 0 {
+        thread::spawn(move || heartbeat(Duration::from_secs(heartbeat_secs)));
+    }
+
+    let mut net = capture::from_env()?;
+    eprintln!("CAPTURE: {}", net.name());
     let t_net = thread::spawn(move || {
-        if let Err(e) = read_tshark() {
-            eprintln!("tshark reader error: {e:?}");
-        }
+        let name = net.name();
+        supervise(&format!("{name} capture"), &format!("{name}_restart"), || net.run());
     });
 
     let user2 = user.clone();
+    let state = Arc::new(Mutex::new(ProcWatch::default()));
+    let state2 = state.clone();
     let t_proc = thread::spawn(move || {
-        if let Err(e) = watch_processes(user2, Duration::from_millis(poll_millis)) {
-            eprintln!("process watcher error: {e:?}");
+        supervise("process watcher", "ps_restart", || {
+            watch_processes(&user2, poll, &state2)
+        });
+    });
+
+    let mut signals = Signals::new([SIGTERM, SIGINT])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            shutdown(&user, &state, signal);
         }
     });
 
@@ -288,3 +610,68 @@ fn main() -> anyhow::Result<()> {
     t_proc.join().ok();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // far above any real pid_max, so nothing is found under /proc for them
+    const SHELL: i32 = 90_000_001;
+    const CURL: i32 = 90_000_002;
+
+    fn snapshot_of(rows: &[(i32, i32, &str)]) -> HashMap<i32, PsRow> {
+        rows.iter()
+            .map(|&(pid, ppid, comm)| (pid, PsRow { pid, ppid, comm: comm.to_string(), args: String::new() }))
+            .collect()
+    }
+
+    fn summary(events: &[Event]) -> Vec<(String, String, String)> {
+        events
+            .iter()
+            .map(|e| match e {
+                Event::Proc(p) => (p.action.clone(), p.comm.clone(), p.ts.clone()),
+                _ => panic!("unexpected event"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_tick_process_is_captured() {
+        let mut watch = ProcWatch::default();
+        let shell = snapshot_of(&[(SHELL, 1, "bash")]);
+        let with_curl = snapshot_of(&[(SHELL, 1, "bash"), (CURL, SHELL, "curl")]);
+
+        let tick = |watch: &mut ProcWatch, curr: &HashMap<i32, PsRow>, ts: &str| summary(&watch.apply("exam", curr, ts));
+        assert_eq!(tick(&mut watch, &shell, "t0"), [("start".into(), "bash".into(), "t0".into())]);
+        // curl shows up in exactly one snapshot
+        assert_eq!(tick(&mut watch, &with_curl, "t1"), [("start".into(), "curl".into(), "t1".into())]);
+        // gone: one grace snapshot, then the stop, stamped when it was first missed
+        assert_eq!(tick(&mut watch, &shell, "t2"), []);
+        assert_eq!(tick(&mut watch, &shell, "t3"), [("stop".into(), "curl".into(), "t2".into())]);
+        assert_eq!(tick(&mut watch, &shell, "t4"), []);
+    }
+
+    #[test]
+    fn process_missing_for_one_snapshot_is_not_restarted() {
+        let mut watch = ProcWatch::default();
+        let shell = snapshot_of(&[(SHELL, 1, "bash")]);
+        let events = watch.apply("exam", &shell, "t0");
+        assert_eq!(events.len(), 1);
+        // ps missed it once (e.g. a truncated listing): no stop and no second start
+        assert!(watch.apply("exam", &HashMap::new(), "t1").is_empty());
+        assert!(watch.apply("exam", &shell, "t2").is_empty());
+        assert!(watch.missing.is_empty());
+    }
+
+    #[test]
+    fn start_event_carries_the_ancestry() {
+        let mut watch = ProcWatch::default();
+        let events = watch.apply("exam", &snapshot_of(&[(SHELL, 1, "bash"), (CURL, SHELL, "curl")]), "t0");
+        let curl = events.iter().find_map(|e| match e {
+            Event::Proc(p) if p.pid == CURL => Some(p),
+            _ => None,
+        }).unwrap();
+        assert_eq!(curl.ppid, Some(SHELL));
+        assert_eq!(curl.ancestry.as_deref(), Some(&["bash".to_string(), "curl".to_string()][..]));
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Rotate once the log would grow past this many bytes (PAL_LOG_MAX_BYTES)
+pub const DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Rotated files kept next to the log: palantir.log.1 (newest) .. palantir.log.N (PAL_LOG_KEEP)
+pub const DEFAULT_KEEP: usize = 5;
+
+/// Destination of the event lines
+pub enum Sink {
+    // --stdout, for debugging
+    Stdout,
+    File(RotatingFile),
+}
+
+impl Sink {
+    /// Write one line and flush it, so a crash never loses more than the event being written
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        match self {
+            Sink::Stdout => {
+                let mut out = std::io::stdout().lock();
+                writeln!(out, "{line}")?;
+                out.flush()
+            }
+            Sink::File(f) => f.write_line(line),
+        }
+    }
+}
+
+/// Append-only newline-delimited log that is renamed to `<path>.1` (shifting the older
+/// rotations up, dropping the one past `keep`) when it reaches `max_bytes`.
+/// Each fresh file starts with the `header` line, so every rotation can be read on its own.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    header: fn() -> String,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Appends to an existing log, so a restarted collector continues where it left off
+    pub fn open(path: PathBuf, max_bytes: u64, keep: usize, header: fn() -> String) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(RotatingFile { path, max_bytes, keep, header, file, written })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.rotate()?;
+            let header = (self.header)();
+            self.append(&header)?;
+        }
+        self.append(line)
+    }
+
+    fn append(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        // one write per line: readers never see half a line followed by another event
+        let mut buf = Vec::with_capacity(line.len() + 1);
+        buf.extend_from_slice(line.as_bytes());
+        buf.push(b'\n');
+        self.file.write_all(&buf)?;
+        self.file.flush()?;
+        self.written += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+            self.written = 0;
+            return Ok(());
+        }
+        let _ = fs::remove_file(rotated(&self.path, self.keep));
+        for i in (1..self.keep).rev() {
+            let from = rotated(&self.path, i);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, i + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, i: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{i}"));
+    PathBuf::from(name)
+}
@@ -0,0 +1,224 @@
+//! Domain categories the analysis counts lookups by, and the AI classifier shared by the
+//! findings and the network timeline so both count the same lookups.
+
+use std::sync::OnceLock;
+
+use publicsuffix::{List, Psl};
+
+/// Web search engines, matched by base domain
+pub const SEARCH_BASES: &[&str] = &[
+    "google.com", 
+    "bing.com", 
+    "duckduckgo.com"
+];
+
+/// Q&A sites
+pub const QNA_BASES: &[&str] = &[
+    "stackoverflow.com", 
+    "stackexchange.com"
+];
+
+/// Code hosting
+pub const CODE_HOST_BASES: &[&str] = &[
+    "github.com", 
+    "gitlab.com", 
+    "bitbucket.org"
+];
+
+/// Package registries
+pub const PKG_BASES: &[&str] = &[
+    "pypi.org",
+    "pythonhosted.org",
+    "npmjs.com",
+    "registry.npmjs.org",
+    "crates.io",
+];
+
+/// File sharing and paste sites
+pub const CLOUD_BASES: &[&str] = &[
+    "drive.google.com",
+    "dropbox.com",
+    "mega.nz",
+    "wetransfer.com",
+    "pastebin.com",
+    "hastebin.com",
+    "ghostbin.com",
+];
+
+/// DNS-over-HTTPS / DNS-over-TLS resolvers. A lookup of one of these means later
+/// name resolution likely bypassed the captured plain DNS. Matched as host suffix.
+pub const DOH_HOSTS: &[&str] = &[
+    "dns.google",
+    "cloudflare-dns.com",
+    "dns.quad9.net",
+    "doh.opendns.com",
+    "dns.nextdns.io",
+    "doh.mullvad.net",
+    "dns.adguard.com",
+    "dns.adguard-dns.com",
+];
+
+/// AI provider domains, matched as the host itself or a parent domain (see `ai_provider`).
+/// Keep tight to reduce false positives.
+pub const AI_PROVIDER_BASES: &[&str] = &[
+    // OpenAI
+    "openai.com",
+    "chatgpt.com",
+
+    // Anthropic
+    "anthropic.com",
+    "claude.ai",
+
+    // Google
+    "ai.google",
+    "deepmind.com",
+    "gemini.google.com",
+
+    // Microsoft
+    "cognitive.microsoft.com",
+    "githubcopilot.com",
+    "openai.azure.com",
+    "azureopenai.com",
+
+    // Meta
+    "ai.meta.com",
+    "llama.meta.com",
+
+    // Others
+    "mistral.ai",
+    "cohere.ai",
+    "stability.ai",
+    "ai21.com",
+    "perplexity.ai",
+    "huggingface.co",
+    "replicate.com",
+    "runpod.io",
+    "openrouter.ai",
+    "poe.com",
+    "x.ai",
+    "you.com",
+    "character.ai",
+    "elevenlabs.io",
+    "jasper.ai",
+    "writesonic.com",
+    "copy.ai",
+    "rytr.me",
+    "forefront.ai",
+    "midjourney.com",
+    "replit.com",
+];
+
+/// Public Suffix List read by `base_domain_guess`, unless PAL_PSL_PATH points elsewhere
+/// (the path distributions install it to with the `publicsuffix` package)
+pub const DEFAULT_PSL_PATH: &str = "/usr/share/publicsuffix/public_suffix_list.dat";
+
+// loaded on first use; None when the list couldn't be read
+static PSL: OnceLock<Option<List>> = OnceLock::new();
+
+fn psl() -> Option<&'static List> {
+    PSL.get_or_init(|| {
+        let path = std::env::var("PAL_PSL_PATH").unwrap_or_else(|_| DEFAULT_PSL_PATH.to_string());
+        let list = std::fs::read_to_string(&path).ok()?.parse::<List>().ok()?;
+        (!list.is_empty()).then_some(list)
+    })
+    .as_ref()
+}
+
+/// Registrable domain of `host`: "bbc.co.uk" for "news.bbc.co.uk", "x.github.io" for
+/// "cdn.x.github.io". Uses the Public Suffix List when it is installed; without it, falls
+/// back to the last two labels (which gets multi-label suffixes like co.uk wrong).
+/// A host that is itself a public suffix is returned as is.
+pub fn base_domain_guess(host: &str) -> String {
+    registrable_domain(psl(), host)
+}
+
+fn registrable_domain(list: Option<&List>, host: &str) -> String {
+    let lower = host.trim_end_matches('.').to_ascii_lowercase();
+    if let Some(list) = list {
+        return match list.domain(lower.as_bytes()) {
+            Some(d) => String::from_utf8_lossy(d.as_bytes()).into_owned(),
+            None => lower,
+        };
+    }
+
+    let mut parts: Vec<&str> = lower.split('.').filter(|s| !s.is_empty()).collect();
+    if parts.len() >= 2 {
+        let last = parts.pop().unwrap();
+        let prev = parts.pop().unwrap();
+        format!("{prev}.{last}")
+    } else {
+        lower
+    }
+}
+
+/// The AI_PROVIDER_BASES entry `host` falls under, e.g. "openai.com" for "api.openai.com"
+/// and "gemini.google.com" for "gemini.google.com" (whose base domain is just google.com)
+pub fn ai_provider(host: &str) -> Option<&'static str> {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    AI_PROVIDER_BASES
+        .iter()
+        .copied()
+        .find(|p| host == *p || host.strip_suffix(p).is_some_and(|rest| rest.ends_with('.')))
+}
+
+/// Host belongs to an AI provider. The one test behind `ai_hits_total`, the AI counts of the
+/// network timeline and the AI severity of the cards, so they all agree on the same log.
+pub fn is_ai_domain(host: &str) -> bool {
+    ai_provider(host).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the relevant rules of the real list, so the tests don't depend on it being installed
+    const PSL_SNIPPET: &str = "\
+// ===BEGIN ICANN DOMAINS===
+com
+io
+uk
+co.uk
+gov.uk
+// ===END ICANN DOMAINS===
+// ===BEGIN PRIVATE DOMAINS===
+github.io
+// ===END PRIVATE DOMAINS===
+";
+
+    fn base(host: &str) -> String {
+        let list: List = PSL_SNIPPET.parse().unwrap();
+        registrable_domain(Some(&list), host)
+    }
+
+    #[test]
+    fn multi_label_suffixes() {
+        assert_eq!(base("foo.bar.co.uk"), "bar.co.uk");
+        assert_eq!(base("news.bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(base("student.gov.uk"), "student.gov.uk");
+        assert_eq!(base("www.student.gov.uk"), "student.gov.uk");
+    }
+
+    #[test]
+    fn private_suffixes() {
+        assert_eq!(base("x.github.io"), "x.github.io");
+        assert_eq!(base("cdn.x.github.io"), "x.github.io");
+        // different users' pages are different sites
+        assert_ne!(base("alice.github.io"), base("bob.github.io"));
+    }
+
+    #[test]
+    fn plain_and_edge_cases() {
+        assert_eq!(base("api.openai.com"), "openai.com");
+        assert_eq!(base("API.OpenAI.com."), "openai.com");
+        // a public suffix on its own stays as it is
+        assert_eq!(base("co.uk"), "co.uk");
+        assert_eq!(base("github.io"), "github.io");
+    }
+
+    #[test]
+    fn falls_back_to_last_two_labels_without_a_list() {
+        assert_eq!(registrable_domain(None, "api.openai.com"), "openai.com");
+        assert_eq!(registrable_domain(None, "foo.bar.co.uk"), "co.uk");
+        assert_eq!(registrable_domain(None, "localhost"), "localhost");
+    }
+}
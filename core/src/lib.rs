@@ -0,0 +1,5 @@
+//! Shared by the collector, the server and the desktop client: the palantir.log line format
+//! and the domain classification the analysis and the timelines agree on.
+
+pub mod domains;
+pub mod log;
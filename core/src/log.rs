@@ -0,0 +1,121 @@
+//! Lines of the collector's palantir.log, one JSON object per line tagged by `kind`.
+//!
+//! Every field except `kind` is optional on the wire: older collectors left some out, and a
+//! line missing a field is still counted with the default. The collector writes a
+//! `{"kind":"meta","event":"format_version",...}` line when it starts and at the top of each
+//! rotated file; logs from before that have no version line and are version 1 as well.
+
+use serde::{Deserialize, Serialize};
+
+/// Where the collector writes its log and the desktop client looks for it, unless PAL_LOG_PATH says otherwise
+pub const DEFAULT_LOG_PATH: &str = "/var/tmp/palantir.log";
+
+/// Version of the line format. Bump it when a field changes meaning or is removed;
+/// new optional fields and new kinds don't need a bump, readers skip what they don't know.
+pub const LOG_FORMAT_VERSION: u32 = 1;
+
+/// meta `event` values
+pub const META_FORMAT_VERSION: &str = "format_version";
+pub const META_HEARTBEAT: &str = "heartbeat";
+pub const META_SHUTDOWN: &str = "shutdown";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    Net(NetEvent),
+    Proc(ProcEvent),
+    DnsResponse(DnsResponseEvent),
+    Meta(MetaEvent),
+    // a kind this reader doesn't know (newer collector)
+    #[serde(other)]
+    Unknown,
+}
+
+/// A DNS query seen on the machine
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NetEvent {
+    pub ts: String,        // RFC3339 in local time
+    pub src_ip: String,    // ip.src
+    pub dns_qname: String, // dns.qry.name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>, // process that sent the query, with PAL_ATTRIBUTE_DNS and when it could be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comm: Option<String>, // its executable name
+}
+
+/// A process start or stop
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProcEvent {
+    pub ts: String,
+    pub user: String,
+    pub pid: i32,
+    pub comm: String,   // canonicalized name like "firefox"
+    pub action: String, // "start" or "stop"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>, // resolved /proc/<pid>/exe path, only on start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<String>, // command line, only on start (truncated by the collector)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ppid: Option<i32>, // parent pid, only on start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ancestry: Option<Vec<String>>, // outermost ancestor first, ending with this process, only on start
+}
+
+impl Default for ProcEvent {
+    fn default() -> Self {
+        ProcEvent {
+            ts: String::new(),
+            user: String::new(),
+            pid: -1,
+            comm: "unknown".to_string(),
+            action: String::new(),
+            exe: None,
+            args: None,
+            ppid: None,
+            ancestry: None,
+        }
+    }
+}
+
+/// Answer to a DNS query; tells which lookups resolved and to what
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DnsResponseEvent {
+    pub ts: String,            // RFC3339 in local time
+    pub client_ip: String,     // ip.dst, the machine that asked (src_ip of the matching net event)
+    pub dns_qname: String,     // dns.qry.name
+    pub rcode: u8,             // dns.flags.rcode, 0 = ok, 3 = NXDOMAIN
+    pub resolved: Vec<String>, // dns.a and dns.aaaa answers, empty when nothing resolved
+}
+
+/// Something about the collector itself rather than the machine, e.g. a restarted capture;
+/// marks where the log may have a gap
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MetaEvent {
+    pub ts: String,
+    pub event: String, // "format_version", "heartbeat", "tshark_restart" / "pcap_restart", "ps_restart", "shutdown"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format_version: Option<u32>, // only on "format_version"
+}
+
+impl Event {
+    pub fn parse(line: &str) -> Result<Event, serde_json::Error> {
+        serde_json::from_str(line)
+    }
+
+    pub fn ts(&self) -> Option<&str> {
+        let ts = match self {
+            Event::Net(e) => &e.ts,
+            Event::Proc(e) => &e.ts,
+            Event::DnsResponse(e) => &e.ts,
+            Event::Meta(e) => &e.ts,
+            Event::Unknown => return None,
+        };
+        Some(ts.as_str()).filter(|s| !s.is_empty())
+    }
+}
@@ -1,6 +1,6 @@
 use iced::keyboard::key;
 use iced::widget::text_input::Id;
-use iced::widget::{self, button, column, container, row, scrollable, text, text_input, ProgressBar};
+use iced::widget::{self, button, checkbox, column, container, row, scrollable, text, text_input, ProgressBar};
 use iced::{keyboard, Application, Color, Command, Element, Length, Settings, Size, Subscription, Theme};
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
@@ -17,6 +17,10 @@ use iced::Border;
 use iced::border::Radius;
 use iced::Background;
 use iced::event::{self, Event};
+use iced::futures::{stream, TryStreamExt};
+use tokio_util::io::ReaderStream;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use palantir_core::log::DEFAULT_LOG_PATH;
 
 
 
@@ -27,19 +31,40 @@ struct PalantirApp {
     assignment_instance_id: String, // actual instance id needed for moodle api
     assignment_title: Option<String>,
     files: Vec<PathBuf>,
+    files_count: usize, // regular files, folders expanded
+    files_total: u64,
+    files_blocked: bool, // over PAL_MAX_FILES or PAL_MAX_TOTAL_BYTES, Continue stays disabled
+    file_warnings: Vec<String>, // single files over PAL_MAX_FILE_BYTES, not blocking
+    student_note: String, // optional explanation for the grader
     // login
     username: String,
     password: String,
     moodle_token: Option<String>,
+    remember: bool, // keep the token in the OS keyring for the next start
+    full_name: Option<String>, // moodle profile name, fetched after login
+    name_source: NameSource,
     // endpoints
     moodle_base: String,
     moodle_service: String,
     server_base: String,
+    log_path: PathBuf, // collector log; rotated siblings (palantir.log.1, ...) are shipped too
+    submit_mode: SubmitMode,
+    moodle_fns: MoodleFunctions,
+    retry: RetryPolicy,
+    limits: FileLimits,
+    dry_run: bool, // Confirm skips Moodle and the server and shows the would-be payload
     // ui
     status: String,
     progress_main: f32,
     progress_logs: f32,
     receipt: Option<String>,
+    moodle_warnings: Vec<MoodleWarning>,
+    moodle_status: Option<MoodleSubmitStatus>,
+    // per-task failures, kept apart so one outcome doesn't overwrite the other on the Done step
+    moodle_error: Option<String>,
+    logs_error: Option<String>,
+    review: Option<Manifest>, // built when leaving PickFiles, sent as-is on Confirm
+    dry_run_payload: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -47,6 +72,7 @@ enum Step {
     Login,
     EnterId,
     PickFiles,
+    Review,
     Submit,
     Progress,
     Done,
@@ -55,25 +81,281 @@ enum Step {
 impl Default for Step {
     fn default() -> Self { Step::Login }
 }
+
+/// Which uploads a submission performs: Moodle hand-in, log upload to the server, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SubmitMode {
+    #[default]
+    Both,
+    MoodleOnly,
+    ServerOnly,
+}
+
+impl SubmitMode {
+    fn from_env() -> Self {
+        match std::env::var("SUBMIT_MODE").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "moodle" | "moodle_only" => SubmitMode::MoodleOnly,
+            "server" | "server_only" | "logs" => SubmitMode::ServerOnly,
+            _ => SubmitMode::Both,
+        }
+    }
+
+    fn moodle(self) -> bool { self != SubmitMode::ServerOnly }
+    fn server(self) -> bool { self != SubmitMode::MoodleOnly }
+}
+
+/// Moodle web service function names and the file area key used for hand-in.
+/// Sites with a custom service or renamed plugin areas override them from the env.
+#[derive(Debug, Clone)]
+struct MoodleFunctions {
+    save_submission: String,
+    submit_for_grading: String,
+    files_plugindata_key: String,
+}
+
+impl Default for MoodleFunctions {
+    fn default() -> Self {
+        MoodleFunctions {
+            save_submission: "mod_assign_save_submission".to_string(),
+            submit_for_grading: "mod_assign_submit_for_grading".to_string(),
+            files_plugindata_key: "files_filemanager".to_string(),
+        }
+    }
+}
+
+impl MoodleFunctions {
+    fn from_env() -> Self {
+        let d = MoodleFunctions::default();
+        let var = |name: &str, default: String| {
+            std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).unwrap_or(default)
+        };
+        MoodleFunctions {
+            save_submission: var("MOODLE_SAVE_FUNCTION", d.save_submission),
+            submit_for_grading: var("MOODLE_SUBMIT_FUNCTION", d.submit_for_grading),
+            files_plugindata_key: var("MOODLE_FILES_PLUGINDATA_KEY", d.files_plugindata_key),
+        }
+    }
+
+    /// Configured functions the site's web service does not offer
+    fn missing(&self, available: &[String]) -> Vec<String> {
+        [&self.save_submission, &self.submit_for_grading]
+            .into_iter()
+            .filter(|f| !available.iter().any(|a| a == *f))
+            .cloned()
+            .collect()
+    }
+}
+
+/// How often a Moodle request is retried after a network error, with the delay doubling each time.
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        // 500ms, 1s, 2s
+        RetryPolicy { max_retries: 3, base_delay_ms: 500 }
+    }
+}
+
+impl RetryPolicy {
+    fn from_env() -> Self {
+        let d = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: std::env::var("PAL_UPLOAD_MAX_RETRIES").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(d.max_retries),
+            base_delay_ms: std::env::var("PAL_UPLOAD_BACKOFF_MS").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(d.base_delay_ms),
+        }
+    }
+
+    fn delay(&self, retry: u32) -> std::time::Duration {
+        std::time::Duration::from_millis(self.base_delay_ms.saturating_mul(1u64 << retry.min(16)))
+    }
+}
+
+/// Send a request built by `make`, retrying connection errors, timeouts and gateway errors
+/// (502/503/504) with exponential backoff. Other HTTP statuses, 4xx included, are returned
+/// as-is for the caller to judge. `on_retry(n, max)` is called before the n-th retry.
+/// Requests that must not run twice (`replay_safe = false`) are only retried when the
+/// connection never came up, since after a timeout or gateway error Moodle may have acted on them.
+async fn retry_request<F, Fut>(
+    policy: RetryPolicy,
+    replay_safe: bool,
+    on_retry: &(dyn Fn(u32, u32) + Send + Sync),
+    make: F,
+) -> Result<reqwest::Response, reqwest::Error>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<reqwest::Response, reqwest::Error>>,
+{
+    let mut retry = 0;
+    loop {
+        let res = make().await;
+        let transient = match &res {
+            Err(e) if e.is_connect() => true,
+            _ if !replay_safe => false,
+            Ok(r) => matches!(r.status().as_u16(), 502..=504),
+            Err(e) => e.is_timeout() || e.is_request(),
+        };
+        if !transient || retry >= policy.max_retries {
+            return res;
+        }
+        tokio::time::sleep(policy.delay(retry)).await;
+        retry += 1;
+        on_retry(retry, policy.max_retries);
+    }
+}
+
+/// Longest Retry-After of a 429 that is waited out; a server asking for more gets an error instead
+const MAX_RETRY_AFTER_SECS: u64 = 120;
+
+/// Send a log-server request built by `make`, waiting out 429 Too Many Requests (for the
+/// server's Retry-After, else the policy's backoff) up to `policy.max_retries` times.
+/// Everything else, errors included, is returned for the caller to judge.
+async fn send_rate_limited<F, Fut>(policy: RetryPolicy, make: F) -> Result<reqwest::Response, reqwest::Error>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<reqwest::Response, reqwest::Error>>,
+{
+    let mut retry = 0;
+    loop {
+        let res = make().await?;
+        if res.status() != reqwest::StatusCode::TOO_MANY_REQUESTS || retry >= policy.max_retries {
+            return Ok(res);
+        }
+        let wait = match res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.trim().parse::<u64>().ok())
+        {
+            Some(secs) if secs > MAX_RETRY_AFTER_SECS => return Ok(res),
+            Some(secs) => std::time::Duration::from_secs(secs),
+            None => policy.delay(retry),
+        };
+        println!("log server is rate limiting, retrying in {}s", wait.as_secs());
+        tokio::time::sleep(wait).await;
+        retry += 1;
+    }
+}
+
+/// Caps on the picked files, so a stray huge folder is caught before it is sent to Moodle.
+/// The file count and total size block submitting; the per-file cap only warns.
+#[derive(Debug, Clone, Copy)]
+struct FileLimits {
+    max_files: usize,
+    max_total_bytes: u64,
+    max_file_bytes: u64,
+}
+
+impl Default for FileLimits {
+    fn default() -> Self {
+        FileLimits {
+            max_files: 200,
+            max_total_bytes: 200 * 1024 * 1024,
+            max_file_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+impl FileLimits {
+    fn from_env() -> Self {
+        let d = FileLimits::default();
+        FileLimits {
+            max_files: std::env::var("PAL_MAX_FILES").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(d.max_files),
+            max_total_bytes: std::env::var("PAL_MAX_TOTAL_BYTES").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(d.max_total_bytes),
+            max_file_bytes: std::env::var("PAL_MAX_FILE_BYTES").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(d.max_file_bytes),
+        }
+    }
+
+    /// Why the selection cannot be submitted, if it is over the count or total size limit
+    fn blocking(&self, count: usize, total: u64) -> Option<String> {
+        if count > self.max_files {
+            return Some(format!(
+                "Too many files: {} selected, at most {} can be submitted. Remove folders you did not mean to add.",
+                count, self.max_files
+            ));
+        }
+        if total > self.max_total_bytes {
+            return Some(format!(
+                "Selection is too large: {} selected, at most {} can be submitted. Remove large folders (e.g. build output) and try again.",
+                pretty_size(total), pretty_size(self.max_total_bytes)
+            ));
+        }
+        None
+    }
+
+    /// One warning per file over the per-file cap
+    fn warnings(&self, files: &[(PathBuf, u64)]) -> Vec<String> {
+        files
+            .iter()
+            .filter(|(_, len)| *len > self.max_file_bytes)
+            .map(|(p, len)| format!("{} is {}, it may be slow to upload", p.to_string_lossy(), pretty_size(*len)))
+            .collect()
+    }
+}
+
+/// What the client uses from core_webservice_get_site_info
+#[derive(Debug, Clone)]
+pub struct SiteInfo {
+    pub full_name: Option<String>,
+    pub functions: Vec<String>,
+}
+
+/// Which identity is sent as the primary student name: the Moodle profile name or the typed login.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum NameSource {
+    #[default]
+    FullName,
+    Username,
+}
+
+impl NameSource {
+    fn from_env() -> Self {
+        match std::env::var("STUDENT_NAME_SOURCE").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "username" | "login" => NameSource::Username,
+            _ => NameSource::FullName,
+        }
+    }
+
+    /// Primary name; falls back to the login when the profile name is unknown
+    fn pick(self, username: &str, full_name: Option<&str>) -> String {
+        match (self, full_name) {
+            (NameSource::FullName, Some(n)) if !n.trim().is_empty() => n.trim().to_string(),
+            _ => username.to_string(),
+        }
+    }
+}
 #[derive(Debug, Clone)]
 enum Msg {
     // login
     UsernameChanged(String),
     PasswordChanged(String),
     LoginPressed,
+    RememberChanged(bool),
     LoginFinished(Result<String, String>), // token on success
+    SiteInfo(Result<SiteInfo, String>),    // moodle full name and offered functions
+    SavedLoginChecked(Result<SiteInfo, SavedLoginError>), // site info fetched with the keyring token at startup
 
     // id check
     AssignmentIdChanged(String),
     CheckId,
     IdVerified(Result<AssignmentIdentifiers, String>), // (assignment_instance_id, name)
+    TestSetup,
+    SetupTested(Result<String, String>), // summary of the dry run
 
     // files and submission
     PickFiles,
     FilesChosen(Vec<PathBuf>),
+    NoteChanged(String),
+    ReviewPressed,
+    BackToFiles,
     SubmitPressed,
-    FinishedMain(Result<String, String>),
+    FinishedMain(Result<MoodleSubmitResult, String>),
+    RetryingMain(u32, u32), // (retry, max) after a network error during the Moodle upload
     FinishedLogs(Result<String, String>),
+    ReuploadLogs, // replace the artifact behind the current receipt, or retry a failed upload
     TickMain(f32),
     TickLogs(f32),
 
@@ -82,13 +364,103 @@ enum Msg {
 }
 
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Manifest {
     assignment_id: String,
     username: String,
+    full_name: Option<String>,
+    // name shown to professors (full name or username, see NameSource)
+    student_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    student_note: Option<String>,
     created_at: String,
     file_hashes: Vec<(String, String)>,
     client_version: String,
+    // device identity signals, used server side for the device key
+    os: String,
+    arch: String,
+    machine_id_hash: Option<String>,
+    // dry run from "Test my setup"; the server keeps it out of the stats
+    #[serde(default)]
+    is_test: bool,
+}
+
+/// Longest student note accepted by the client (the server enforces the same limit)
+const STUDENT_NOTE_MAX_CHARS: usize = 2000;
+
+/// Moodle save_submission warning codes that do not prevent submitting for grading.
+/// Anything not listed here is treated as fatal.
+const NON_FATAL_SAVE_WARNINGS: &[&str] = &[
+    "filealreadysubmitted",
+    "submissionalreadysubmitted",
+    "filesalreadyexist",
+    "duplicatefile",
+];
+
+#[derive(Debug, Clone)]
+pub struct MoodleWarning {
+    pub code: String,
+    pub message: String,
+}
+
+impl MoodleWarning {
+    fn is_fatal(&self) -> bool {
+        !NON_FATAL_SAVE_WARNINGS.contains(&self.code.as_str())
+    }
+}
+
+impl std::fmt::Display for MoodleWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+/// Moodle error/warning codes meaning the student already handed in and can no longer change it.
+const LOCKED_CODES: &[&str] = &[
+    "submissionslocked",
+    "submissionlocked",
+    "alreadysubmitted",
+    "submissionalreadysubmitted",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoodleSubmitStatus {
+    Submitted,
+    /// an earlier submission already counts and further changes are locked
+    AlreadySubmittedLocked,
+}
+
+/// Outcome of a Moodle submission that went through, possibly with non-fatal warnings.
+#[derive(Debug, Clone)]
+pub struct MoodleSubmitResult {
+    pub status: MoodleSubmitStatus,
+    pub summary: String,
+    pub warnings: Vec<MoodleWarning>,
+}
+
+impl MoodleSubmitResult {
+    fn locked() -> Self {
+        MoodleSubmitResult {
+            status: MoodleSubmitStatus::AlreadySubmittedLocked,
+            summary: "Your assignment is already submitted and locked; no action needed.".to_string(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// True when a Moodle response (exception object or warnings array) reports the locked state
+fn is_locked_response(text: &str) -> bool {
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(text) else { return false; };
+    let code_is_locked = |x: Option<&serde_json::Value>| {
+        x.and_then(|c| c.as_str())
+            .map(|c| LOCKED_CODES.contains(&c.to_ascii_lowercase().as_str()))
+            .unwrap_or(false)
+    };
+    match v {
+        serde_json::Value::Object(obj) => code_is_locked(obj.get("errorcode")),
+        serde_json::Value::Array(arr) => arr.iter().any(|w| code_is_locked(w.get("warningcode"))),
+        _ => false,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -103,6 +475,8 @@ async fn main() -> iced::Result {
     let _ = dotenv::dotenv();
     let mut settings = Settings::default();
     settings.window.size = Size::new(900.0, 640.0);
+    // lab monitors can be small; the layouts fill the window up to their max widths
+    settings.window.min_size = Some(Size::new(420.0, 420.0));
     PalantirApp::run(settings)
 }
 
@@ -115,17 +489,33 @@ impl Application for PalantirApp {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Msg>) {
-    (
-        PalantirApp {
+        let mut app = PalantirApp {
             moodle_base: std::env::var("MOODLE_BASE_URL").unwrap_or_else(|_| "http://localhost".to_string()),
             moodle_service: std::env::var("MOODLE_SERVICE").unwrap_or_else(|_| "moodle_mobile_app".to_string()),
             server_base: std::env::var("SERVER_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:8080".to_string()),
+            log_path: std::env::var("PAL_LOG_PATH").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(DEFAULT_LOG_PATH)),
+            submit_mode: SubmitMode::from_env(),
+            moodle_fns: MoodleFunctions::from_env(),
+            retry: RetryPolicy::from_env(),
+            limits: FileLimits::from_env(),
+            dry_run: std::env::args().any(|a| a == "--dry-run")
+                || std::env::var("PAL_DRY_RUN").is_ok_and(|v| matches!(v.trim(), "1" | "true" | "yes")),
+            name_source: NameSource::from_env(),
             step: Step::Login,
             ..Default::default()
-        },
-        Command::none(),
-    )
-}
+        };
+
+        // a remembered login skips the form once the token is confirmed to still work
+        let Some((username, tok)) = load_saved_login(&app.moodle_base) else {
+            return (app, Command::none());
+        };
+        app.username = username;
+        app.moodle_token = Some(tok.clone());
+        app.remember = true;
+        app.status = "restoring saved login...".into();
+        let base = app.moodle_base.clone();
+        (app, Command::perform(async move { check_saved_login(&base, &tok).await }, Msg::SavedLoginChecked))
+    }
 
     fn title(&self) -> String {
         "Palantir".into()
@@ -186,6 +576,34 @@ impl Application for PalantirApp {
                 
                 Command::none()
             }
+            Msg::TestSetup => {
+                let Some(tok) = self.moodle_token.clone() else {
+                    self.status = "please login first".into();
+                    return Command::none();
+                };
+                let cmid = self.assignment_id.trim().to_string();
+                if cmid.is_empty() || !cmid.chars().all(|c| c.is_ascii_digit()) {
+                    self.status = "invalid assignment id (cmid)".into();
+                    return Command::none();
+                }
+                self.status = "testing setup...".into();
+                let base = self.moodle_base.clone();
+                let server_base = self.server_base.clone();
+                let log_path = self.log_path.clone();
+                let submit_mode = self.submit_mode;
+                let mut manifest = build_manifest(&cmid, &self.username, self.full_name.as_deref(), self.name_source, &[]);
+                manifest.is_test = true;
+                return Command::perform(async move {
+                    test_setup(&base, &tok, &cmid, &server_base, &log_path, submit_mode, &manifest).await
+                }, Msg::SetupTested);
+            }
+            Msg::SetupTested(res) => {
+                self.status = match res {
+                    Ok(summary) => summary,
+                    Err(e) => format!("setup test failed: {}", e),
+                };
+                Command::none()
+            }
             Msg::PickFiles => {
                 if let Some(paths) = FileDialog::new().set_directory(".").pick_files() {
                     return Command::perform(async move { paths }, Msg::FilesChosen);
@@ -193,7 +611,49 @@ impl Application for PalantirApp {
                 Command::none()
             }
             Msg::FilesChosen(list) => {
+                let sizes = file_sizes(&list);
                 self.files = list;
+                self.files_count = sizes.len();
+                self.files_total = sizes.iter().map(|(_, len)| len).sum();
+                self.file_warnings = self.limits.warnings(&sizes);
+                match self.limits.blocking(self.files_count, self.files_total) {
+                    Some(msg) => {
+                        self.files_blocked = true;
+                        self.status = msg;
+                    }
+                    None => {
+                        self.files_blocked = false;
+                        self.status.clear();
+                    }
+                }
+                Command::none()
+            }
+            Msg::NoteChanged(s) => {
+                self.student_note = s.chars().take(STUDENT_NOTE_MAX_CHARS).collect();
+                Command::none()
+            }
+            Msg::ReviewPressed => {
+                if self.files.is_empty() {
+                    self.status = "no files selected".into();
+                    return Command::none();
+                }
+                if let Some(msg) = self.limits.blocking(self.files_count, self.files_total) {
+                    self.status = msg;
+                    return Command::none();
+                }
+                let mut manifest = build_manifest(&self.assignment_id, &self.username, self.full_name.as_deref(), self.name_source, &self.files);
+                let note = self.student_note.trim();
+                manifest.student_note = (!note.is_empty()).then(|| note.to_string());
+                self.review = Some(manifest);
+                self.status.clear();
+                self.step = Step::Review;
+                Command::none()
+            }
+            Msg::BackToFiles => {
+                self.review = None;
+                self.dry_run_payload = None;
+                self.status.clear();
+                self.step = Step::PickFiles;
                 Command::none()
             }
             Msg::SubmitPressed => {
@@ -201,45 +661,64 @@ impl Application for PalantirApp {
                     self.status = "please login first".into();
                     return Command::none();
                 };
+                // the manifest the student reviewed is the one that is sent
+                let Some(manifest) = self.review.clone() else {
+                    return self.update(Msg::ReviewPressed);
+                };
 
-                if self.files.is_empty() {
-                    self.status = "no files selected".into();
+                if self.dry_run {
+                    self.dry_run_payload = Some(self.would_send(&manifest));
+                    self.status = "dry run: nothing was sent to Moodle or the server".into();
+                    self.step = Step::Done;
                     return Command::none();
                 }
 
-                // move to progress screen
+                // move to progress screen; a disabled task counts as already finished
                 self.step = Step::Progress;
-                self.progress_main = 0.0;
-                self.progress_logs = 0.0;
+                self.progress_main = if self.submit_mode.moodle() { 0.0 } else { 1.0 };
+                self.progress_logs = if self.submit_mode.server() { 0.0 } else { 1.0 };
+                self.moodle_warnings.clear();
+                self.moodle_status = None;
+                self.moodle_error = None;
+                self.logs_error = None;
 
                 // capture values for async tasks
                 let base = self.moodle_base.clone();
-                let sid  = self.assignment_id.clone(); 
                 let aid  = self.assignment_instance_id.clone(); 
                 let files = self.files.clone();
                 let token = tok.clone();
+                let log_token = tok.clone();
+                let fns = self.moodle_fns.clone();
+                let retry = self.retry;
 
                 let server_base = self.server_base.clone();
-                let manifest = build_manifest(&sid, &self.username, &self.files);
+                let log_path = self.log_path.clone();
 
-                // task 1: upload to Moodle and submit
+                // task 1: upload to Moodle and submit; retries are reported on the side channel
+                // and the stream ends with the result once the sender is dropped
+                let (retry_tx, retry_rx) = iced::futures::channel::mpsc::unbounded::<Msg>();
                 let main_task = async move {
-                    let res = moodle_upload_and_submit(&base, &token, &aid, &files).await?;
-                    Ok::<String, String>(res)
+                    let on_retry = move |n: u32, max: u32| { let _ = retry_tx.unbounded_send(Msg::RetryingMain(n, max)); };
+                    Msg::FinishedMain(moodle_upload_and_submit(&base, &token, &aid, &files, &fns, retry, &on_retry).await)
                 };
+                let main_task = stream::select(retry_rx, stream::once(main_task));
 
                 // task 2: zip logs and send to server
                 let logs_task = async move {
-                    let zip_path = zip_snapshot("/var/tmp/", &manifest)?;
-                    let receipt = upload_logs(&server_base, &manifest, &zip_path).await?;
+                    let zip_path = zip_snapshot(&log_path, &manifest)?;
+                    let receipt = upload_logs(&server_base, &manifest, &zip_path, &log_token, None).await?;
                     println!("logs uploaded, receipt {}", receipt);
                     Ok::<String, String>(receipt)
                 };
 
-                Command::batch(vec![
-                    Command::perform(main_task, Msg::FinishedMain),
-                    Command::perform(logs_task, Msg::FinishedLogs),
-                ])
+                let mut tasks = Vec::new();
+                if self.submit_mode.moodle() {
+                    tasks.push(Command::run(main_task, |m| m));
+                }
+                if self.submit_mode.server() {
+                    tasks.push(Command::perform(logs_task, Msg::FinishedLogs));
+                }
+                Command::batch(tasks)
             }
             Msg::UsernameChanged(s) => {
                 self.username = s;
@@ -252,11 +731,14 @@ impl Application for PalantirApp {
             Msg::FinishedMain(res) => {
                 match res {
                     Ok(r) => {
-                        self.status = format!("{}", r);
+                        self.status = r.summary;
+                        self.moodle_warnings = r.warnings;
+                        self.moodle_status = Some(r.status);
                         self.progress_main = 1.0;
                     }
                     Err(e) => {
                         self.status = format!("{}", e);
+                        self.moodle_error = Some(e);
                         self.progress_main = 1.0;
                     }
                 }
@@ -268,12 +750,14 @@ impl Application for PalantirApp {
             Msg::FinishedLogs(res) => {
                 match res {
                     Ok(r) => {
+                        self.logs_error = None;
                         self.status = format!("logs uploaded receipt {}", r);
                         self.progress_logs = 1.0;
                         self.receipt = Some(r);
                     }
                     Err(e) => {
                         self.status = format!("log upload error {}", e);
+                        self.logs_error = Some(e);
                         self.progress_logs = 1.0;
                     }
                 }
@@ -282,6 +766,29 @@ impl Application for PalantirApp {
                 }
                 Command::none()
             }
+            Msg::ReuploadLogs => {
+                // no receipt yet means the first upload failed and this is a plain retry
+                let receipt = self.receipt.clone();
+                let token = self.moodle_token.clone().unwrap_or_default();
+                self.status = "re-uploading logs...".into();
+                self.progress_logs = 0.0;
+                let server_base = self.server_base.clone();
+                let log_path = self.log_path.clone();
+                let mut manifest = build_manifest(&self.assignment_id, &self.username, self.full_name.as_deref(), self.name_source, &self.files);
+                let note = self.student_note.trim();
+                manifest.student_note = (!note.is_empty()).then(|| note.to_string());
+                Command::perform(async move {
+                    let zip_path = zip_snapshot(&log_path, &manifest)?;
+                    upload_logs(&server_base, &manifest, &zip_path, &token, receipt.as_deref()).await
+                }, Msg::FinishedLogs)
+            }
+            Msg::RetryingMain(n, max) => {
+                // may arrive after the result on the merged stream
+                if self.progress_main < 1.0 {
+                    self.status = format!("retrying upload ({}/{})...", n, max);
+                }
+                Command::none()
+            }
             Msg::TickMain(p) => {
                 self.progress_main = p;
                 Command::none()
@@ -296,6 +803,10 @@ impl Application for PalantirApp {
             Msg::PasswordChanged(s) => { 
                 self.password = s; Command::none() 
             }
+            Msg::RememberChanged(b) => {
+                self.remember = b;
+                Command::none()
+            }
             Msg::LoginPressed => {
                 self.status = "signing in...".into();
                 let base = self.moodle_base.clone();
@@ -307,17 +818,69 @@ impl Application for PalantirApp {
             Msg::LoginFinished(res) => {
                 match res {
                     Ok(tok) => {
-                        self.moodle_token = Some(tok);
+                        if self.remember {
+                            if let Err(e) = save_login(&self.moodle_base, &self.username, &tok) {
+                                println!("keyring error: {}", e);
+                            }
+                        } else {
+                            clear_saved_login(&self.moodle_base, &self.username);
+                        }
+                        self.moodle_token = Some(tok.clone());
+                        self.full_name = None;
                         self.status.clear();
                         self.step = Step::EnterId;
+                        let base = self.moodle_base.clone();
+                        return Command::perform(async move { moodle_get_site_info(&base, &tok).await }, Msg::SiteInfo);
                     }
                     Err(e) => {
+                        clear_saved_login(&self.moodle_base, &self.username);
                         self.status = format!("login error: {}", e);
                         self.step = Step::Login;
                     }
                 }
                 Command::none()
             }
+            Msg::SavedLoginChecked(res) => {
+                match res {
+                    Ok(info) => {
+                        self.status.clear();
+                        self.step = Step::EnterId;
+                        return self.update(Msg::SiteInfo(Ok(info)));
+                    }
+                    Err(SavedLoginError::Rejected(e)) => {
+                        // expired or revoked; the student signs in again
+                        println!("saved login rejected: {}", e);
+                        clear_saved_login(&self.moodle_base, &self.username);
+                        self.moodle_token = None;
+                        self.status = "Your saved login has expired, please sign in again.".into();
+                    }
+                    Err(SavedLoginError::Unchecked(e)) => {
+                        // Moodle unreachable or confused; the token may well be fine, keep it
+                        println!("saved login not checked: {}", e);
+                        self.step = Step::EnterId;
+                        self.status = "Could not reach Moodle to check your saved login; submitting will retry it.".into();
+                    }
+                }
+                Command::none()
+            }
+            Msg::SiteInfo(res) => {
+                match res {
+                    Ok(info) => {
+                        self.full_name = info.full_name;
+                        // an empty list means the site did not report its functions, nothing to check
+                        let missing = self.moodle_fns.missing(&info.functions);
+                        if !info.functions.is_empty() && !missing.is_empty() {
+                            self.status = format!(
+                                "warning: this Moodle service does not offer {}; submitting will fail, ask your teacher to check the client configuration",
+                                missing.join(", ")
+                            );
+                        }
+                    }
+                    // not fatal, the username is used instead
+                    Err(e) => println!("site info error: {}", e),
+                }
+                Command::none()
+            }
             Msg::AssignmentIdChanged(s) => { 
                 self.assignment_id = s; Command::none() 
             }
@@ -381,6 +944,10 @@ impl Application for PalantirApp {
                         button("Check")
                             .on_press_maybe(id_ok.then_some(Msg::CheckId))
                             .style(theme::Button::Custom(Box::new(PrimaryBtn)))
+                            .padding(8),
+                        // dry run against Moodle and the server, nothing is handed in
+                        button("Test my setup")
+                            .on_press_maybe(id_ok.then_some(Msg::TestSetup))
                             .padding(8)
                     ]
                     .spacing(12),
@@ -388,9 +955,11 @@ impl Application for PalantirApp {
                         text(format!("Assignment: {}", name))
                             .style(theme::Text::Color(Color::from_rgb8(71, 85, 105)))
                     } else { text("") },
+                    if !self.status.is_empty() { text(&self.status) } else { text("") },
                 ]
                 .spacing(16)
-                .width(Length::Fixed(640.0));
+                .width(Length::Fill)
+                .max_width(640.0);
 
                 container(form)
                     .padding(24)
@@ -440,18 +1009,93 @@ impl Application for PalantirApp {
                     title(&display_title),
                     subtitle("Pick all files and folders you want to submit"),
                     text(format!(
-                        "  {} items  •  {}",
+                        "  {} items  •  {} files  •  {} of at most {}",
                         self.files.len(),
-                        pretty_size(total_size(&self.files))
+                        self.files_count,
+                        pretty_size(self.files_total),
+                        pretty_size(self.limits.max_total_bytes)
                     ))
                     .style(theme::Text::Color(Color::from_rgb8(71, 85, 105))),
                     actions,
+                    // takes whatever height the card has left, so it grows with the window
                     scrollable(list_content)
-                        .height(Length::Fixed(240.0))
+                        .height(Length::Fill)
+                        .width(Length::Fill),
+                    text_input("Optional note for the grader (e.g. \"my VPN auto-connected\")", &self.student_note)
+                        .on_input(Msg::NoteChanged)
+                        .padding(10)
+                        .size(15)
                         .width(Length::Fill),
                     row![
                         button("Continue")
-                            .on_press_maybe((!self.files.is_empty()).then_some(Msg::SubmitPressed))
+                            .on_press_maybe((!self.files.is_empty() && !self.files_blocked).then_some(Msg::ReviewPressed))
+                            .style(theme::Button::Custom(Box::new(PrimaryBtn)))
+                            .padding(8),
+                    ]
+                    .spacing(12),
+                    self.file_warnings_notice(),
+                    if !self.status.is_empty() { text(&self.status) } else { text("") },
+                ]
+                .spacing(16)
+                .width(Length::Fill)
+                .max_width(720.0)
+                .height(Length::Fill);
+
+                container(body)
+                    .padding(24)
+                    .height(Length::Fill)
+                    .max_height(760.0)
+                    .style(theme::Container::Custom(Box::new(Card)))
+                    .into()
+
+
+            }
+
+            Step::Review => {
+                let Some(manifest) = &self.review else {
+                    return text("nothing to review").into();
+                };
+                let muted = Color::from_rgb8(71, 85, 105);
+                let files: Vec<Element<Msg>> = manifest
+                    .file_hashes
+                    .iter()
+                    .map(|(name, hash)| {
+                        column![
+                            text(name).size(15),
+                            text(format!("sha256 {}", hash)).size(12).style(theme::Text::Color(muted)),
+                        ]
+                        .spacing(2)
+                        .into()
+                    })
+                    .collect();
+
+                let mut targets = Vec::new();
+                if self.submit_mode.moodle() {
+                    targets.push(format!("Moodle: {}", self.moodle_base));
+                }
+                if self.submit_mode.server() {
+                    targets.push(format!("Logs: {}", self.server_base));
+                }
+
+                let body = column![
+                    title(&format!("Submit to: {}", self.assignment_title.clone().unwrap_or("Unknown".into()))),
+                    subtitle("Check what will be sent before submitting"),
+                    text(format!("Student: {}", manifest.student_name)).size(15),
+                    text(format!("Assignment id: {}", manifest.assignment_id)).size(15),
+                    text(targets.join("  •  ")).size(15).style(theme::Text::Color(muted)),
+                    if let Some(note) = &manifest.student_note {
+                        text(format!("Note: {}", note)).size(15)
+                    } else { text("") },
+                    text(format!("{} files", manifest.file_hashes.len())).size(15),
+                    scrollable(column(files).spacing(8))
+                        .height(Length::Fill)
+                        .width(Length::Fill),
+                    row![
+                        button("Back")
+                            .on_press(Msg::BackToFiles)
+                            .padding(8),
+                        button(if self.dry_run { "Confirm (dry run)" } else { "Confirm & Submit" })
+                            .on_press(Msg::SubmitPressed)
                             .style(theme::Button::Custom(Box::new(PrimaryBtn)))
                             .padding(8),
                     ]
@@ -459,14 +1103,44 @@ impl Application for PalantirApp {
                     if !self.status.is_empty() { text(&self.status) } else { text("") },
                 ]
                 .spacing(16)
-                .width(Length::Fixed(720.0));
+                .width(Length::Fill)
+                .max_width(720.0)
+                .height(Length::Fill);
 
                 container(body)
                     .padding(24)
+                    .height(Length::Fill)
+                    .max_height(760.0)
                     .style(theme::Container::Custom(Box::new(Card)))
                     .into()
+            }
 
+            Step::Done if self.dry_run_payload.is_some() => {
+                let payload = self.dry_run_payload.clone().unwrap_or_default();
+                let body = column![
+                    subtitle("Dry run complete"),
+                    text(&self.status),
+                    scrollable(text(payload).size(13))
+                        .height(Length::Fill)
+                        .width(Length::Fill),
+                    row![
+                        button("Back")
+                            .on_press(Msg::BackToFiles)
+                            .padding(8),
+                    ]
+                    .spacing(12),
+                ]
+                .spacing(16)
+                .width(Length::Fill)
+                .max_width(720.0)
+                .height(Length::Fill);
 
+                container(body)
+                    .padding(24)
+                    .height(Length::Fill)
+                    .max_height(760.0)
+                    .style(theme::Container::Custom(Box::new(Card)))
+                    .into()
             }
 
             Step::Submit => {
@@ -483,7 +1157,8 @@ impl Application for PalantirApp {
                     if !self.status.is_empty() { text(&self.status) } else { text("") },
                 ]
                 .spacing(16)
-                .width(Length::Fixed(600.0));
+                .width(Length::Fill)
+                .max_width(600.0);
 
                 container(body)
                     .padding(24)
@@ -493,16 +1168,30 @@ impl Application for PalantirApp {
             }
 
             Step::Progress => {
-                let body = column![
-                    subtitle("Uploading to Moodle and sending logs"),
-                    text("Moodle").size(14),
-                    ProgressBar::new(0.0..=1.0, self.progress_main),
-                    text("Logs").size(14),
-                    ProgressBar::new(0.0..=1.0, self.progress_logs),
-                    if !self.status.is_empty() { text(&self.status)} else { text("").into() },
-                ]
-                .spacing(16)
-                .width(Length::Fixed(600.0));
+                let heading = match self.submit_mode {
+                    SubmitMode::Both => "Uploading to Moodle and sending logs",
+                    SubmitMode::MoodleOnly => "Uploading to Moodle",
+                    SubmitMode::ServerOnly => "Sending logs",
+                };
+                let mut body = column![subtitle(heading)]
+                    .spacing(16)
+                    .width(Length::Fill)
+                .max_width(600.0);
+
+                // only show bars for tasks that actually run
+                if self.submit_mode.moodle() {
+                    body = body
+                        .push(text("Moodle").size(14))
+                        .push(ProgressBar::new(0.0..=1.0, self.progress_main));
+                }
+                if self.submit_mode.server() {
+                    body = body
+                        .push(text("Logs").size(14))
+                        .push(ProgressBar::new(0.0..=1.0, self.progress_logs));
+                }
+                let body = body
+                    .push(if !self.status.is_empty() { text(&self.status) } else { text("") })
+                    .push(self.warnings_notice());
 
                 container(body)
                     .padding(24)
@@ -511,17 +1200,36 @@ impl Application for PalantirApp {
             }
 
             Step::Done => {
+                let heading = match (self.moodle_error.is_some(), self.logs_error.is_some()) {
+                    (false, false) => "Submission complete",
+                    (true, false) => "Moodle submission failed",
+                    (false, true) => "Log upload failed",
+                    (true, true) => "Submission failed",
+                };
                 let body = column![
-                    subtitle("Submission complete"),
+                    subtitle(heading),
+                    self.outcome_notice(),
                     if let Some(r) = &self.receipt {
                         text(format!("Receipt {}", r)).size(16)
                     } else {
                         text("No receipt available").size(16)
                     },
                     text(&self.status),
+                    self.warnings_notice(),
+                    // e.g. the collector log was incomplete at submit time, or the upload failed
+                    if (self.receipt.is_some() || self.logs_error.is_some()) && self.submit_mode.server() {
+                        Element::from(
+                            button(if self.receipt.is_some() { "Re-upload logs" } else { "Retry log upload" })
+                                .on_press_maybe((self.progress_logs >= 1.0).then_some(Msg::ReuploadLogs))
+                                .padding(8),
+                        )
+                    } else {
+                        Element::from(text(""))
+                    },
                 ]
                 .spacing(16)
-                .width(Length::Fixed(600.0));
+                .width(Length::Fill)
+                .max_width(600.0);
 
                 container(body)
                     .padding(24)
@@ -544,6 +1252,9 @@ impl Application for PalantirApp {
                         .padding(10)
                         .size(16)
                         .width(Length::Fill),
+                    checkbox("Remember me on this computer", self.remember)
+                        .on_toggle(Msg::RememberChanged)
+                        .size(16),
                     row![
                         button("Login")
                             .on_press_maybe((!self.username.is_empty() && !self.password.is_empty()).then_some(Msg::LoginPressed))
@@ -554,7 +1265,8 @@ impl Application for PalantirApp {
                     if !self.status.is_empty() { text(&self.status) } else { text("") },
                 ]
                 .spacing(16)
-                .width(Length::Fixed(480.0));
+                .width(Length::Fill)
+                .max_width(480.0);
 
                 container(form)
                     .padding(24)
@@ -574,6 +1286,8 @@ impl Application for PalantirApp {
         )
         .width(Length::Fill)
         .height(Length::Fill)
+        // keeps the card off the window edges when the window is narrower than it
+        .padding(16)
         .center_x()
         .center_y()
         .style(theme::Container::Custom(Box::new(PageBg)))
@@ -584,9 +1298,101 @@ impl Application for PalantirApp {
 
 }
 
+impl PalantirApp {
+    // non-fatal Moodle warnings render as a yellow notice, not a failure
+    fn warnings_notice(&self) -> Element<'_, Msg> {
+        let mut items: Vec<Element<Msg>> = Vec::new();
+        if self.moodle_status == Some(MoodleSubmitStatus::AlreadySubmittedLocked) {
+            items.push(
+                text("Your assignment is already submitted and locked; no action needed. Your logs are still sent.")
+                    .size(14)
+                    .style(theme::Text::Color(Color::from_rgb8(21, 128, 61)))
+                    .into(),
+            );
+        }
+        for w in &self.moodle_warnings {
+            items.push(
+                text(format!("⚠ {}", w))
+                    .size(14)
+                    .style(theme::Text::Color(Color::from_rgb8(161, 98, 7)))
+                    .into(),
+            );
+        }
+        column(items).spacing(4).into()
+    }
+
+    // oversized single files are allowed through, with a yellow notice like Moodle warnings
+    fn file_warnings_notice(&self) -> Element<'_, Msg> {
+        let items: Vec<Element<Msg>> = self
+            .file_warnings
+            .iter()
+            .map(|w| {
+                text(format!("⚠ {}", w))
+                    .size(14)
+                    .style(theme::Text::Color(Color::from_rgb8(161, 98, 7)))
+                    .into()
+            })
+            .collect();
+        column(items).spacing(4).into()
+    }
+
+    /// What a real submission would send: the Moodle hand-in and the manifest that goes with the logs
+    fn would_send(&self, manifest: &Manifest) -> String {
+        let mut payload = serde_json::Map::new();
+        if self.submit_mode.moodle() {
+            payload.insert("moodle".into(), serde_json::json!({
+                "base_url": self.moodle_base,
+                "assignment": self.assignment_title,
+                "assignment_instance_id": self.assignment_instance_id,
+                "save_function": self.moodle_fns.save_submission,
+                "submit_function": self.moodle_fns.submit_for_grading,
+                "files": self.files,
+            }));
+        }
+        if self.submit_mode.server() {
+            payload.insert("server".into(), serde_json::json!({
+                "base_url": self.server_base,
+                "manifest": manifest,
+            }));
+        }
+        serde_json::to_string_pretty(&payload).unwrap_or_else(|e| format!("serialize payload: {}", e))
+    }
+
+    /// What the student still has to do when only part of the submission went through
+    fn outcome_notice(&self) -> Element<'_, Msg> {
+        let danger = Color::from_rgb8(185, 28, 28);
+        let mut items: Vec<Element<Msg>> = Vec::new();
+        if self.moodle_error.is_some() {
+            let evidence = if self.receipt.is_some() {
+                " Monitoring evidence was saved (see the receipt below)."
+            } else {
+                ""
+            };
+            items.push(
+                text(format!(
+                    "Your work was NOT submitted to Moodle. Submit it manually in Moodle before the deadline.{}",
+                    evidence
+                ))
+                .size(14)
+                .style(theme::Text::Color(danger))
+                .into(),
+            );
+        }
+        if self.logs_error.is_some() {
+            items.push(
+                text("Your monitoring logs were NOT uploaded. Keep this window open and retry, or tell the exam supervisor.")
+                    .size(14)
+                    .style(theme::Text::Color(danger))
+                    .into(),
+            );
+        }
+        column(items).spacing(4).into()
+    }
+}
+
 // helpers
 
-fn build_manifest(assignment_id: &str, username: &str, files: &[PathBuf]) -> Manifest {
+fn build_manifest(assignment_id: &str, username: &str, full_name: Option<&str>, name_source: NameSource, files: &[PathBuf]) -> Manifest {
     let mut file_hashes = Vec::new();
     for p in files {
         if p.is_file() {
@@ -607,10 +1413,29 @@ fn build_manifest(assignment_id: &str, username: &str, files: &[PathBuf]) -> Man
     Manifest {
         assignment_id: assignment_id.to_string(),
         username: username.to_string(),
+        full_name: full_name.map(|s| s.to_string()),
+        student_name: name_source.pick(username, full_name),
+        student_note: None,
         created_at,
         file_hashes,
         client_version: "palantir-desktop-0.1.0".to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        machine_id_hash: machine_id_hash(),
+        is_test: false,
+    }
+}
+
+// sha256 of /etc/machine-id so the raw id never leaves the machine
+fn machine_id_hash() -> Option<String> {
+    let raw = std::fs::read_to_string("/etc/machine-id")
+        .or_else(|_| std::fs::read_to_string("/var/lib/dbus/machine-id"))
+        .ok()?;
+    let id = raw.trim();
+    if id.is_empty() {
+        return None;
     }
+    Some(hex::encode(Sha256::digest(id.as_bytes())))
 }
 
 fn hash_file(path: &Path) -> String {
@@ -625,7 +1450,45 @@ fn hash_file(path: &Path) -> String {
     hex::encode(hasher.finalize())
 }
 
-fn zip_snapshot(snapshot_dir: &str, manifest: &Manifest) -> Result<PathBuf, String> {
+/// Written into the zip instead of failing when no collector log exists, so the professor
+/// sees that the student submitted but monitoring data was absent
+const MISSING_LOG_ENTRY: &str = "snapshot/MISSING_LOG.txt";
+
+fn zip_snapshot(log_path: &Path, manifest: &Manifest) -> Result<PathBuf, String> {
+    let logs = rotated_logs(log_path);
+    if logs.is_empty() {
+        println!("no collector log at {}, submitting without monitoring data", log_path.display());
+    }
+    write_snapshot_zip(manifest, &logs, log_path)
+}
+
+/// `log_path` and its rotations (`palantir.log.1`, `palantir.log.2`, ...), oldest first
+fn rotated_logs(log_path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (log_path.parent(), log_path.file_name()) else {
+        return Vec::new();
+    };
+    let name = name.to_string_lossy();
+    let prefix = format!("{}.", name);
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut logs: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|e| {
+            let n = e.file_name().to_string_lossy().to_string();
+            n == name || n.strip_prefix(&prefix).is_some_and(|i| !i.is_empty() && i.chars().all(|c| c.is_ascii_digit()))
+        })
+        .filter(|e| e.path().is_file())
+        .map(|e| (e.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH), e.path()))
+        .collect();
+    // rotations are never written again, so modification time orders them by their events
+    logs.sort();
+    logs.into_iter().map(|(_, p)| p).collect()
+}
+
+/// manifest.json + snapshot/palantir.log with the given log files concatenated in order.
+/// Without any log a real submission also gets snapshot/MISSING_LOG.txt naming `log_path`.
+fn write_snapshot_zip(manifest: &Manifest, logs: &[PathBuf], log_path: &Path) -> Result<PathBuf, String> {
     let out_name = format!(
         "palantir-snapshot-{}-{}.zip",
         manifest.assignment_id, 
@@ -649,26 +1512,33 @@ fn zip_snapshot(snapshot_dir: &str, manifest: &Manifest) -> Result<PathBuf, Stri
     zip.write_all(&manifest_json)
         .map_err(|e| format!("write manifest.json: {}", e))?;
 
-    // add /var/tmp/palantir.log exactly
-    let log_path = Path::new(snapshot_dir).join("palantir.log");
-    
-    if log_path.exists() {
-    
-        zip.start_file("snapshot/palantir.log", opts)
-            .map_err(|e| format!("start file {}: {}", log_path.display(), e))?;
-    
-        let mut f = File::open(&log_path)
-            .map_err(|e| format!("open {}: {}", log_path.display(), e))?;
-    
+    zip.start_file("snapshot/palantir.log", opts)
+        .map_err(|e| format!("start file palantir.log: {}", e))?;
+
+    for path in logs {
+        let mut f = File::open(path)
+            .map_err(|e| format!("open {}: {}", path.display(), e))?;
         let mut buf = Vec::new();
-    
         f.read_to_end(&mut buf)
-            .map_err(|e| format!("read {}: {}", log_path.display(), e))?;
-    
+            .map_err(|e| format!("read {}: {}", path.display(), e))?;
+        // a rotation cut mid-line must not glue two events together
+        if !buf.is_empty() && !buf.ends_with(b"\n") {
+            buf.push(b'\n');
+        }
         zip.write_all(&buf)
-            .map_err(|e| format!("write palantir.log into zip: {}", e))?;
-    } else {
-        return Err(format!("missing {}", log_path.display()));
+            .map_err(|e| format!("write {} into zip: {}", path.display(), e))?;
+    }
+
+    if logs.is_empty() && !manifest.is_test {
+        zip.start_file(MISSING_LOG_ENTRY, opts)
+            .map_err(|e| format!("start {}: {}", MISSING_LOG_ENTRY, e))?;
+        let note = format!(
+            "No collector log was found at {} when this submission was made ({}).\nThe collector was probably not running; this submission has no monitoring data.\n",
+            log_path.display(),
+            manifest.created_at
+        );
+        zip.write_all(note.as_bytes())
+            .map_err(|e| format!("write {}: {}", MISSING_LOG_ENTRY, e))?;
     }
 
     zip.finish()
@@ -677,40 +1547,227 @@ fn zip_snapshot(snapshot_dir: &str, manifest: &Manifest) -> Result<PathBuf, Stri
 }
 
 
-async fn upload_logs(server_base: &str, manifest: &Manifest, zip_path: &Path) -> Result<String, String> {
-    let url = format!(
-        "{}/api/v1/logs?submission_id={}&student_name={}&moodle_assignment_id={}&client_version={}",
-        server_base,
+/// `replaces`: receipt of an earlier upload of this student/assignment whose artifact is replaced
+/// `moodle_token` lets the server confirm the student name against the Moodle account
+async fn upload_logs(
+    server_base: &str,
+    manifest: &Manifest,
+    zip_path: &Path,
+    moodle_token: &str,
+    replaces: Option<&str>,
+) -> Result<String, String> {
+    let query = format!(
+        "submission_id={}&student_name={}&student_username={}&moodle_assignment_id={}&client_version={}",
         urlencoding::encode(&manifest.assignment_id),
+        urlencoding::encode(&manifest.student_name),
         urlencoding::encode(&manifest.username),
         urlencoding::encode(&manifest.assignment_id),
         urlencoding::encode(&manifest.client_version),
     );
 
-    println!("uploading logs to {}", url);
+    let query = if manifest.is_test { format!("{}&is_test=true", query) } else { query };
+    let query = match replaces {
+        Some(r) => format!("{}&replaces={}", query, urlencoding::encode(r)),
+        None => query,
+    };
 
-    let file_part = reqwest::multipart::Part::stream(tokio::fs::read(zip_path).await.map_err(|e| e.to_string())?)
-        .file_name(
-            zip_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
-        );
+    let client = reqwest::Client::new();
+    let note = manifest.student_note.as_deref();
+    if let Some(receipt) = upload_logs_chunked(&client, server_base, &query, zip_path, moodle_token, note).await? {
+        return Ok(receipt);
+    }
 
-    let form = reqwest::multipart::Form::new().part("log_zip", file_part);
+    // older server without chunked uploads: send the whole zip in one request
+    let url = format!("{}/api/v1/logs?{}", server_base, query);
+    println!("uploading logs to {}", url);
 
-    let client = reqwest::Client::new();
-    let res = client.post(url).multipart(form).send().await.map_err(|e| e.to_string())?;
+    let bytes = tokio::fs::read(zip_path).await.map_err(|e| e.to_string())?;
+    // lets the server detect corruption in transit
+    let sha256 = hex::encode(Sha256::digest(&bytes));
+    let file_name = zip_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let res = send_rate_limited(RetryPolicy::from_env(), || {
+        let file_part = reqwest::multipart::Part::bytes(bytes.clone()).file_name(file_name.clone());
+        let mut form = reqwest::multipart::Form::new().part("log_zip", file_part);
+        if let Some(note) = note {
+            form = form.text("student_note", note.to_string());
+        }
+        client
+            .post(&url)
+            .header("X-Moodle-Token", moodle_token)
+            .header("X-Content-SHA256", &sha256)
+            .multipart(form)
+            .send()
+    })
+    .await
+    .map_err(|e| e.to_string())?;
     if !res.status().is_success() {
-        println!("response: {:#?}", res);
-        return Err(format!("server error {}", res.status()));
+        return Err(server_error(res).await);
     }
     let v: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
     let receipt = v.get("receipt_id").and_then(|x| x.as_str()).unwrap_or("").to_string();
     Ok(receipt)
 }
 
+/// Size of each piece of a chunked log upload
+const LOG_CHUNK_BYTES: u64 = 1024 * 1024;
+
+/// Resumable log upload: init a session, send the zip in chunks, then complete with its sha256.
+/// After a network error the upload continues from the offset the server has, up to
+/// PAL_UPLOAD_MAX_RETRIES times in a row. `Ok(None)` when the server has no chunked uploads.
+async fn upload_logs_chunked(
+    client: &reqwest::Client,
+    server_base: &str,
+    query: &str,
+    zip_path: &Path,
+    moodle_token: &str,
+    note: Option<&str>,
+) -> Result<Option<String>, String> {
+    let url = format!("{}/api/v1/logs/init?{}", server_base, query);
+    println!("starting chunked log upload at {}", url);
+    let retry = RetryPolicy::from_env();
+    let res = send_rate_limited(retry, || client.post(&url).header("X-Moodle-Token", moodle_token).send())
+        .await
+        .map_err(|e| e.to_string())?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(server_error(res).await);
+    }
+    let v: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let session = v
+        .get("session_id")
+        .and_then(|x| x.as_str())
+        .ok_or_else(|| "server returned no upload session".to_string())?
+        .to_string();
+    let session_url = format!("{}/api/v1/logs/{}", server_base, session);
+    let offset_of = |v: &serde_json::Value| v.get("offset").and_then(|x| x.as_u64());
+
+    let sha256 = hash_file(zip_path);
+    let len = tokio::fs::metadata(zip_path).await.map_err(|e| e.to_string())?.len();
+    let mut file = tokio::fs::File::open(zip_path).await.map_err(|e| e.to_string())?;
+    let mut offset = 0u64;
+    let mut failures = 0u32;
+
+    while offset < len {
+        let mut chunk = Vec::new();
+        file.seek(std::io::SeekFrom::Start(offset)).await.map_err(|e| e.to_string())?;
+        (&mut file).take(LOG_CHUNK_BYTES).read_to_end(&mut chunk).await.map_err(|e| e.to_string())?;
+        let sent = chunk.len() as u64;
+
+        let chunk_url = format!("{}/chunk?offset={}", session_url, offset);
+        let res = send_rate_limited(retry, || client.put(&chunk_url).body(chunk.clone()).send()).await;
+        match res {
+            Ok(r) if r.status().is_success() => {
+                let v: serde_json::Value = r.json().await.unwrap_or_default();
+                offset = offset_of(&v).unwrap_or(offset + sent);
+                failures = 0;
+            }
+            // the server has a different end (e.g. an earlier chunk did arrive); continue from there
+            Ok(r) if r.status() == reqwest::StatusCode::CONFLICT => {
+                let v: serde_json::Value = r.json().await.unwrap_or_default();
+                offset = offset_of(&v).ok_or_else(|| "upload session lost its offset".to_string())?;
+            }
+            Ok(r) if r.status().is_client_error() || r.status() == reqwest::StatusCode::INSUFFICIENT_STORAGE => {
+                return Err(server_error(r).await);
+            }
+            other => {
+                failures += 1;
+                if failures > retry.max_retries {
+                    return Err(match other {
+                        Ok(r) => server_error(r).await,
+                        Err(e) => e.to_string(),
+                    });
+                }
+                println!("log chunk at {} failed, resuming (attempt {}/{})", offset, failures, retry.max_retries);
+                tokio::time::sleep(retry.delay(failures - 1)).await;
+                if let Ok(r) = send_rate_limited(retry, || client.get(&session_url).send()).await
+                    && let Ok(v) = r.json::<serde_json::Value>().await
+                {
+                    offset = offset_of(&v).unwrap_or(offset);
+                }
+            }
+        }
+    }
+
+    let complete_url = format!("{}/complete", session_url);
+    let body = serde_json::json!({ "sha256": sha256, "student_note": note });
+    let res = send_rate_limited(retry, || client.post(&complete_url).json(&body).send())
+        .await
+        .map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(server_error(res).await);
+    }
+    let v: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let receipt = v.get("receipt_id").and_then(|x| x.as_str()).unwrap_or("").to_string();
+    Ok(Some(receipt))
+}
+
+/// Message for a failed server response; statuses the server explains (out of disk, identity
+/// check, corrupted upload, too large, rate limited) pass its text through so the student knows what to do
+async fn server_error(res: reqwest::Response) -> String {
+    let status = res.status();
+    let explained = [
+        reqwest::StatusCode::INSUFFICIENT_STORAGE,
+        reqwest::StatusCode::UNAUTHORIZED,
+        reqwest::StatusCode::FORBIDDEN,
+        reqwest::StatusCode::BAD_GATEWAY,
+        reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+        reqwest::StatusCode::PAYLOAD_TOO_LARGE,
+        reqwest::StatusCode::TOO_MANY_REQUESTS,
+    ];
+    if explained.contains(&status) {
+        let msg = res.text().await.unwrap_or_default();
+        if !msg.is_empty() {
+            return msg;
+        }
+    } else {
+        println!("response: {:#?}", res);
+    }
+    format!("server error {}", status)
+}
+
+/// "Test my setup": checks the Moodle token and assignment, then uploads an empty test log
+/// to the server and verifies the receipt. Nothing is submitted to Moodle.
+async fn test_setup(
+    moodle_base: &str,
+    token: &str,
+    cmid: &str,
+    server_base: &str,
+    log_path: &Path,
+    submit_mode: SubmitMode,
+    manifest: &Manifest,
+) -> Result<String, String> {
+    let mut report = Vec::new();
+    if submit_mode.moodle() {
+        let ids = moodle_get_assignment_identifiers(moodle_base, token, cmid)
+            .await
+            .map_err(|e| format!("moodle: {}", e))?;
+        report.push(format!("Moodle login ok, assignment \"{}\" found", ids.name));
+    }
+    if submit_mode.server() {
+        let zip_path = write_snapshot_zip(manifest, &[], log_path)?;
+        let receipt = upload_logs(server_base, manifest, &zip_path, token, None).await;
+        let _ = std::fs::remove_file(&zip_path);
+        let receipt = receipt.map_err(|e| format!("server: {}", e))?;
+        if receipt.is_empty() {
+            return Err("server: upload accepted but no receipt returned".into());
+        }
+        report.push(format!("server ok (test receipt {})", receipt));
+
+        // the real submission needs the collector's log
+        if rotated_logs(log_path).is_empty() {
+            report.push(format!("warning: {} not found, is the collector running?", log_path.display()));
+        }
+    }
+    Ok(report.join("; "))
+}
+
 struct Card;
 
 impl container::StyleSheet for Card {
@@ -805,22 +1862,83 @@ fn pretty_size(bytes: u64) -> String {
     else { format!("{} B", bytes) }
 }
 
-fn total_size(paths: &[PathBuf]) -> u64 {
-    let mut total = 0u64;
+/// Every regular file in the selection with its size, folders expanded
+fn file_sizes(paths: &[PathBuf]) -> Vec<(PathBuf, u64)> {
+    let mut out = Vec::new();
     for p in paths {
         if p.is_file() {
-            if let Ok(md) = std::fs::metadata(p) { total += md.len(); }
+            if let Ok(md) = std::fs::metadata(p) { out.push((p.clone(), md.len())); }
         } else if p.is_dir() {
             for e in WalkDir::new(p) {
                 if let Ok(e) = e {
                     if e.path().is_file() {
-                        if let Ok(md) = e.metadata() { total += md.len(); }
+                        if let Ok(md) = e.metadata() { out.push((e.path().to_path_buf(), md.len())); }
                     }
                 }
             }
         }
     }
-    total
+    out
+}
+
+/// Shown instead of raw markup when Moodle answers a web service call with an HTML page
+const MOODLE_HTML_ERROR: &str = "Moodle appears to be in maintenance mode or behind a gateway (it returned a web page instead of data). Please try again in a few minutes.";
+
+/// Body of a Moodle web service response. Maintenance pages, login redirects and WAF blocks
+/// come back as HTML; those become a friendly error instead of a JSON parse failure.
+async fn moodle_text(resp: reqwest::Response) -> Result<String, String> {
+    let status = resp.status();
+    let is_html_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.to_ascii_lowercase().contains("text/html"))
+        .unwrap_or(false);
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+
+    let head = text.trim_start().chars().take(64).collect::<String>().to_ascii_lowercase();
+    let looks_html = head.starts_with("<!doctype") || head.starts_with("<html") || head.starts_with("<head");
+    if looks_html || (is_html_type && !text.trim().is_empty()) {
+        println!("moodle returned html ({}): {}", status, text.chars().take(300).collect::<String>());
+        return Err(MOODLE_HTML_ERROR.to_string());
+    }
+    Ok(text)
+}
+
+// OS keyring entries: "<moodle base>" holds the last remembered username,
+// "<moodle base>|<username>" that user's token; the password is never stored
+const KEYRING_SERVICE: &str = "palantir";
+
+fn keyring_entry(user: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, user).map_err(|e| e.to_string())
+}
+
+/// (username, token) remembered for this Moodle site, if any
+fn load_saved_login(moodle_base: &str) -> Option<(String, String)> {
+    let username = keyring_entry(moodle_base).ok()?.get_password().ok()?;
+    let token = keyring_entry(&format!("{moodle_base}|{username}")).ok()?.get_password().ok()?;
+    Some((username, token))
+}
+
+fn save_login(moodle_base: &str, username: &str, token: &str) -> Result<(), String> {
+    keyring_entry(&format!("{moodle_base}|{username}"))?
+        .set_password(token)
+        .map_err(|e| e.to_string())?;
+    keyring_entry(moodle_base)?
+        .set_password(username)
+        .map_err(|e| e.to_string())
+}
+
+/// Forget the token of `username`; missing entries are fine
+fn clear_saved_login(moodle_base: &str, username: &str) {
+    if let Ok(e) = keyring_entry(&format!("{moodle_base}|{username}")) {
+        let _ = e.delete_credential();
+    }
+    if let Ok(e) = keyring_entry(moodle_base)
+        && e.get_password().is_ok_and(|u| u == username)
+    {
+        let _ = e.delete_credential();
+    }
 }
 
 async fn moodle_get_token(base: &str, service: &str, username: &str, password: &str) -> Result<String, String> {
@@ -832,7 +1950,7 @@ async fn moodle_get_token(base: &str, service: &str, username: &str, password: &
         urlencoding::encode(password)
     );
     let resp = reqwest::get(url).await.map_err(|e| e.to_string())?;
-    let text = resp.text().await.map_err(|e| e.to_string())?;
+    let text = moodle_text(resp).await?;
     let v: serde_json::Value =
         serde_json::from_str(&text).map_err(|_| format!("unexpected token response: {}", text))?;
 
@@ -844,7 +1962,85 @@ async fn moodle_get_token(base: &str, service: &str, username: &str, password: &
     Err(msg.to_string())
 }
 
-async fn moodle_upload_and_submit(base: &str, token: &str, assignment_id: &str, files: &[PathBuf]) -> Result<String, String> {
+/// Full name of the logged in user and the functions the service offers, from core_webservice_get_site_info
+/// Moodle error codes meaning the token itself is no longer accepted.
+const TOKEN_REJECTED_CODES: &[&str] = &["invalidtoken", "invalidtimedtoken"];
+
+/// Why the remembered token could not be confirmed at startup
+#[derive(Debug, Clone)]
+pub enum SavedLoginError {
+    // Moodle explicitly refused the token; it is forgotten
+    Rejected(String),
+    // network, proxy or any other failure; the token is kept
+    Unchecked(String),
+}
+
+async fn check_saved_login(base: &str, token: &str) -> Result<SiteInfo, SavedLoginError> {
+    let text = moodle_site_info_text(base, token).await.map_err(SavedLoginError::Unchecked)?;
+    let rejected = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|v| v.get("errorcode").and_then(|c| c.as_str()).map(|c| c.to_ascii_lowercase()))
+        .is_some_and(|c| TOKEN_REJECTED_CODES.contains(&c.as_str()));
+    if rejected {
+        return Err(SavedLoginError::Rejected(text));
+    }
+    parse_site_info(&text).map_err(SavedLoginError::Unchecked)
+}
+
+async fn moodle_get_site_info(base: &str, token: &str) -> Result<SiteInfo, String> {
+    parse_site_info(&moodle_site_info_text(base, token).await?)
+}
+
+async fn moodle_site_info_text(base: &str, token: &str) -> Result<String, String> {
+    let url = format!("{}/webservice/rest/server.php", base);
+    let form = [
+        ("wstoken", token),
+        ("wsfunction", "core_webservice_get_site_info"),
+        ("moodlewsrestformat", "json"),
+    ];
+
+    let client = reqwest::Client::new();
+    let resp = client.post(&url).form(&form).send().await.map_err(|e| e.to_string())?;
+    moodle_text(resp).await
+}
+
+fn parse_site_info(text: &str) -> Result<SiteInfo, String> {
+    let v: serde_json::Value =
+        serde_json::from_str(text).map_err(|_| format!("unexpected site info response: {}", text))?;
+
+    if let Some(ex) = v.get("exception") {
+        let msg = v.get("message").and_then(|m| m.as_str()).unwrap_or("error");
+        return Err(format!("{}: {}", ex, msg));
+    }
+
+    let first = v.get("firstname").and_then(|x| x.as_str()).unwrap_or("");
+    let last = v.get("lastname").and_then(|x| x.as_str()).unwrap_or("");
+    let full = v
+        .get("fullname")
+        .and_then(|x| x.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("{} {}", first, last));
+    let full = full.trim();
+    let functions = v
+        .get("functions")
+        .and_then(|x| x.as_array())
+        .map(|arr| arr.iter().filter_map(|f| f.get("name").and_then(|n| n.as_str())).map(|n| n.to_string()).collect())
+        .unwrap_or_default();
+    Ok(SiteInfo {
+        full_name: (!full.is_empty()).then(|| full.to_string()),
+        functions,
+    })
+}
+
+async fn moodle_upload_and_submit(
+    base: &str,
+    token: &str,
+    assignment_id: &str,
+    files: &[PathBuf],
+    fns: &MoodleFunctions,
+    retry: RetryPolicy,
+    on_retry: &(dyn Fn(u32, u32) + Send + Sync),
+) -> Result<MoodleSubmitResult, String> {
     let client = reqwest::Client::new();
     let mut itemid: Option<i64> = None;
 
@@ -860,30 +2056,29 @@ async fn moodle_upload_and_submit(base: &str, token: &str, assignment_id: &str,
             }
         }
 
-        let bytes = tokio::fs::read(path)
+        // size up front: fails early on unreadable files and lets the part declare its length
+        let len = tokio::fs::metadata(path)
             .await
-            .map_err(|e| format!("read {:?}: {}", path, e))?;
+            .map_err(|e| format!("read {:?}: {}", path, e))?
+            .len();
         
-        let part = reqwest::multipart::Part::bytes(bytes)
-            .file_name(path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string()
-            );
-        let form = reqwest::multipart::Form::new().part("file_1", part);
-
-        let resp = client
-            .post(url)
-            .multipart(form)
-            .send()
+        let file_name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        // the multipart body is consumed by each attempt, so it is rebuilt per try; the file
+        // is opened on first poll and streamed in chunks instead of being read into memory.
+        // Resending the first file only opens a fresh draft area; later ones would land twice.
+        let resp = retry_request(retry, itemid.is_none(), on_retry, || {
+            let form = reqwest::multipart::Form::new().part("file_1", file_part(path, len, &file_name));
+            client.post(url.clone()).multipart(form).send()
+        })
             .await
             .map_err(|e| format!("upload {:?}: {}", path, e))?;
 
-        let body = resp
-            .text()
-            .await
-            .map_err(|e| e.to_string())?;
+        let body = moodle_text(resp).await?;
         
         let arr: serde_json::Value = serde_json::from_str(&body)
             .map_err(|_| format!("unexpected upload response: {}", body))?;
@@ -908,28 +2103,42 @@ async fn moodle_upload_and_submit(base: &str, token: &str, assignment_id: &str,
 
     let url = format!("{}/webservice/rest/server.php", base);
     let body = format!(
-        "wstoken={}&wsfunction=mod_assign_save_submission&moodlewsrestformat=json&assignmentid={}&plugindata[files_filemanager]={}",
+        "wstoken={}&wsfunction={}&moodlewsrestformat=json&assignmentid={}&plugindata[{}]={}",
         urlencoding::encode(token),
+        urlencoding::encode(&fns.save_submission),
         assignment_id,
+        urlencoding::encode(&fns.files_plugindata_key),
         draft_id, // numeric, does not need encoding
     );
 
-    let resp = client
-        .post(&url)
-        .header(reqwest::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-        .body(body)
-        .send()
+    // saving the submission changes it; never resent once Moodle may have seen it
+    let resp = retry_request(retry, false, on_retry, || {
+        client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.clone())
+            .send()
+    })
         .await
         .map_err(|e| e.to_string())?;
 
 
-    let text = resp.text().await.map_err(|e| e.to_string())?;
+    let text = moodle_text(resp).await?;
 
-    check_save_submission_response(&text)?;
-    moodle_submit_for_grading(&client, base, token, assignment_id).await?;
+    if is_locked_response(&text) {
+        return Ok(MoodleSubmitResult::locked());
+    }
+    let warnings = check_save_submission_response(&text)?;
+    if moodle_submit_for_grading(&client, base, token, assignment_id, &fns.submit_for_grading).await? == MoodleSubmitStatus::AlreadySubmittedLocked {
+        return Ok(MoodleSubmitResult { warnings, ..MoodleSubmitResult::locked() });
+    }
 
 
-    Ok(format!("submitted assignment {} with draft {}", assignment_id, draft_id))
+    Ok(MoodleSubmitResult {
+        status: MoodleSubmitStatus::Submitted,
+        summary: format!("submitted assignment {} with draft {}", assignment_id, draft_id),
+        warnings,
+    })
 }
 
 async fn moodle_get_assignment_identifiers(base: &str, token: &str, cmid: &str) -> Result<AssignmentIdentifiers, String> {
@@ -948,7 +2157,7 @@ async fn moodle_get_assignment_identifiers(base: &str, token: &str, cmid: &str)
         .await
         .map_err(|e| e.to_string())?;
 
-    let text = resp.text().await.map_err(|e| e.to_string())?;
+    let text = moodle_text(resp).await?;
     let v: serde_json::Value =
         serde_json::from_str(&text).map_err(|_| format!("unexpected response: {}", text))?;
 
@@ -984,86 +2193,235 @@ async fn moodle_submit_for_grading(
     base: &str,
     token: &str,
     assignment_id: &str,
-) -> Result<(), String> {
+    function: &str,
+) -> Result<MoodleSubmitStatus, String> {
     let url = format!("{}/webservice/rest/server.php", base);
 
-    // attempt 1: with submission statement
-    let body_with = format!(
-        "wstoken={}&wsfunction=mod_assign_submit_for_grading&moodlewsrestformat=json&assignmentid={}&acceptsubmissionstatement=1",
-        urlencoding::encode(token),
-        assignment_id
-    );
+    // only send the statement flag when the assignment asks for it; some sites reject it otherwise.
+    // Tokens without mod_assign_get_assignments can still submit, so a failed lookup isn't fatal
+    let requires_statement = match moodle_requires_submission_statement(client, base, token, assignment_id).await {
+        Ok(required) => required,
+        Err(e) => {
+            println!("submission statement check failed, submitting without it: {}", e);
+            false
+        }
+    };
+
+    let mut form = vec![
+        ("wstoken", token.to_string()),
+        ("wsfunction", function.to_string()),
+        ("moodlewsrestformat", "json".to_string()),
+        ("assignmentid", assignment_id.to_string()),
+    ];
+    if requires_statement {
+        form.push(("acceptsubmissionstatement", "1".to_string()));
+    }
 
-    let resp1 = client.post(&url)
-        .header(reqwest::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-        .body(body_with)
+    let resp = client.post(&url)
+        .form(&form)
         .send().await.map_err(|e| e.to_string())?;
 
-    let text1 = resp1.text().await.map_err(|e| e.to_string())?;
-    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text1) {
-        if v.get("exception").is_none() {
-            return Ok(()); // success
+    let text = moodle_text(resp).await?;
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        // success is an empty warnings array (or a benign object without exception)
+        Ok(serde_json::Value::Array(arr)) if arr.is_empty() => Ok(MoodleSubmitStatus::Submitted),
+        Ok(serde_json::Value::Object(obj)) if obj.get("exception").is_none() => Ok(MoodleSubmitStatus::Submitted),
+        Ok(_) if is_locked_response(&text) => Ok(MoodleSubmitStatus::AlreadySubmittedLocked),
+        Ok(v) => {
+            let mentions_statement = text.to_ascii_lowercase().contains("submissionstatement");
+            if requires_statement && mentions_statement {
+                return Err(format!(
+                    "this assignment requires accepting the submission statement, but Moodle did not accept it: {}",
+                    v
+                ));
+            }
+            Err(format!("{} failed: {}", function, text))
         }
-        // if this exact failure is an invalid_parameter, fall back without the flag
-        if v.get("errorcode").and_then(|x| x.as_str()) == Some("invalidparameter") {
-            // attempt 2: without the flag
-            let body_no = format!(
-                "wstoken={}&wsfunction=mod_assign_submit_for_grading&moodlewsrestformat=json&assignmentid={}",
-                urlencoding::encode(token),
-                assignment_id
-            );
-            let resp2 = client.post(&url)
-                .header(reqwest::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-                .body(body_no)
-                .send().await.map_err(|e| e.to_string())?;
-            let text2 = resp2.text().await.map_err(|e| e.to_string())?;
-            if serde_json::from_str::<serde_json::Value>(&text2)
-                .ok()
-                .and_then(|v2| v2.get("exception").cloned())
-                .is_none()
-            {
-                return Ok(());
+        // empty or non-JSON body: older Moodle versions do this on success, but confirm explicitly
+        Err(_) => {
+            if moodle_confirm_submitted(client, base, token, assignment_id).await? {
+                Ok(MoodleSubmitStatus::Submitted)
+            } else {
+                Err(format!("submit_for_grading returned an unexpected response and the submission is not marked as submitted: {}", text))
+            }
+        }
+    }
+}
+
+/// Multipart part for an upload of `path` (`len` bytes), read in chunks as it is sent
+fn file_part(path: &Path, len: u64, file_name: &str) -> reqwest::multipart::Part {
+    reqwest::multipart::Part::stream_with_length(file_body(path.to_path_buf()), len)
+        .file_name(file_name.to_string())
+}
+
+/// Body that reads `path` in chunks as it is sent; the file is opened on first poll
+fn file_body(path: PathBuf) -> reqwest::Body {
+    let chunks = stream::once(tokio::fs::File::open(path))
+        .map_ok(ReaderStream::new)
+        .try_flatten();
+    reqwest::Body::wrap_stream(chunks)
+}
+
+/// Reads `requiresubmissionstatement` for the assignment via mod_assign_get_assignments.
+async fn moodle_requires_submission_statement(
+    client: &reqwest::Client,
+    base: &str,
+    token: &str,
+    assignment_id: &str,
+) -> Result<bool, String> {
+    let url = format!("{}/webservice/rest/server.php", base);
+    let form = [
+        ("wstoken", token),
+        ("wsfunction", "mod_assign_get_assignments"),
+        ("moodlewsrestformat", "json"),
+        ("assignmentids[0]", assignment_id),
+    ];
+
+    let resp = client.post(&url).form(&form).send().await.map_err(|e| e.to_string())?;
+    let text = moodle_text(resp).await?;
+    let v: serde_json::Value =
+        serde_json::from_str(&text).map_err(|_| format!("unexpected get_assignments response: {}", text))?;
+
+    if let Some(ex) = v.get("exception") {
+        let msg = v.get("message").and_then(|m| m.as_str()).unwrap_or("error");
+        return Err(format!("{}: {}", ex, msg));
+    }
+
+    let courses = v.get("courses").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    for course in courses {
+        let assignments = course.get("assignments").and_then(|a| a.as_array()).cloned().unwrap_or_default();
+        for a in assignments {
+            let id = a.get("id").and_then(|x| x.as_i64()).map(|x| x.to_string());
+            if id.as_deref() == Some(assignment_id) {
+                let flag = a.get("requiresubmissionstatement").and_then(|x| x.as_i64()).unwrap_or(0);
+                return Ok(flag == 1);
             }
-            return Err(format!("submit_for_grading failed: {}", text2));
         }
-        return Err(format!("submit_for_grading failed: {}", text1));
-    } else {
-        // non-JSON usually means success (older Moodle returns empty body), but be strict:
-        return Ok(());
     }
+
+    // assignment not visible through this call; assume no statement is needed
+    Ok(false)
 }
 
-fn check_save_submission_response(text: &str) -> Result<(), String> {
+/// Asks mod_assign_get_submission_status whether the last attempt is in state "submitted".
+async fn moodle_confirm_submitted(
+    client: &reqwest::Client,
+    base: &str,
+    token: &str,
+    assignment_id: &str,
+) -> Result<bool, String> {
+    let url = format!("{}/webservice/rest/server.php", base);
+    let form = [
+        ("wstoken", token),
+        ("wsfunction", "mod_assign_get_submission_status"),
+        ("moodlewsrestformat", "json"),
+        ("assignid", assignment_id),
+    ];
+
+    let resp = client.post(&url).form(&form).send().await.map_err(|e| e.to_string())?;
+    let text = moodle_text(resp).await?;
+    let v: serde_json::Value =
+        serde_json::from_str(&text).map_err(|_| format!("unexpected submission status response: {}", text))?;
+
+    let status = v
+        .pointer("/lastattempt/submission/status")
+        .and_then(|x| x.as_str())
+        .unwrap_or("");
+    Ok(status == "submitted")
+}
+
+/// Returns the non-fatal warnings on success; any fatal warning aborts with an error.
+fn check_save_submission_response(text: &str) -> Result<Vec<MoodleWarning>, String> {
     // success on many Moodle versions is exactly an empty array: []
     if let Ok(val) = serde_json::from_str::<serde_json::Value>(text) {
         match val {
             serde_json::Value::Array(arr) => {
-                if arr.is_empty() {
-                    return Ok(());
-                }
                 // warnings present
-                // build a compact, user friendly error using warningcode and message
-                let mut lines = Vec::new();
-                for w in arr {
-                    let code = w.get("warningcode").and_then(|x| x.as_str()).unwrap_or("warning");
-                    let msg  = w.get("item").and_then(|x| x.as_str())
-                         .or_else(|| w.get("message").and_then(|x| x.as_str()))
-                         .unwrap_or("unknown");
-                    lines.push(format!("{}: {}", code, msg));
+                // classify by warningcode, keep a user friendly message for each
+                let warnings: Vec<MoodleWarning> = arr
+                    .iter()
+                    .map(|w| MoodleWarning {
+                        code: w.get("warningcode").and_then(|x| x.as_str()).unwrap_or("warning").to_string(),
+                        message: w.get("item").and_then(|x| x.as_str())
+                            .or_else(|| w.get("message").and_then(|x| x.as_str()))
+                            .unwrap_or("unknown")
+                            .to_string(),
+                    })
+                    .collect();
+
+                if warnings.iter().any(|w| w.is_fatal()) {
+                    let lines: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+                    return Err(format!("save_submission warnings: {}", lines.join("; ")));
                 }
-                return Err(format!("save_submission warnings: {}", lines.join("; ")));
+                Ok(warnings)
             }
             serde_json::Value::Object(obj) => {
                 if obj.get("exception").is_some() {
                     return Err(format!("save_submission failed: {}", text));
                 }
                 // some sites may return {} or another benign object
-                return Ok(());
+                Ok(Vec::new())
             }
-            _ => return Ok(()),
+            _ => Ok(Vec::new()),
         }
     } else {
         // non-JSON or unexpected, treat as success to mirror Moodle’s older behaviors
-        Ok(())
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn uploads_stream_files_from_disk() {
+        // far past the 8 KiB hashing buffer, and not a multiple of any chunk size
+        let data: Vec<u8> = (0..3_000_007u32).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("palantir-upload-test-{}.bin", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        // a streamed body has no bytes of its own to hand out, so nothing was read up front
+        assert!(file_body(path.clone()).as_bytes().is_none());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut req = Vec::new();
+            let mut buf = vec![0u8; 64 * 1024];
+            // the multipart body ends with the closing boundary
+            while !req.ends_with(b"--\r\n") {
+                let n = sock.read(&mut buf).await.unwrap();
+                if n == 0 { break; }
+                req.extend_from_slice(&buf[..n]);
+            }
+            sock.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await.unwrap();
+            req
+        });
+
+        let len = std::fs::metadata(&path).unwrap().len();
+        let form = reqwest::multipart::Form::new().part("file_1", file_part(&path, len, "dataset.bin"));
+        let resp = reqwest::Client::builder().no_proxy().build().unwrap()
+            .post(format!("http://{addr}/webservice/upload.php"))
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let _ = std::fs::remove_file(&path);
+
+        let req = server.await.unwrap();
+        let head_end = req.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&req[..head_end]).to_ascii_lowercase();
+        // the length is known up front, so the body is not sent chunked
+        assert!(!head.contains("transfer-encoding: chunked"));
+
+        let disposition = b"filename=\"dataset.bin\"";
+        let at = req.windows(disposition.len()).position(|w| w == disposition).expect("file name kept");
+        let start = at + req[at..].windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        assert_eq!(&req[start..start + data.len()], &data[..]);
     }
 }
@@ -1,13 +1,22 @@
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, OptionalExtension};
+use rusqlite::{params, OptionalExtension, TransactionBehavior};
+use sha2::{Digest, Sha256};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use uuid::Uuid;
 
+use crate::routes::admin::util::consts::FK_ANALYSIS_MS;
+
 pub fn init_db(path: &str) -> Pool<SqliteConnectionManager> {
     let manager = SqliteConnectionManager::file(path);
     let pool = Pool::new(manager).expect("db pool");
     {
         let conn = pool.get().expect("conn");
+        let had_access_table: bool = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'assignment_access'",
+            [],
+            |r| r.get::<_, i64>(0),
+        ).map(|n| n > 0).unwrap_or(false);
         conn.execute_batch(
             r#"
             PRAGMA journal_mode = WAL;
@@ -52,8 +61,99 @@ pub fn init_db(path: &str) -> Pool<SqliteConnectionManager> {
             );
 
             CREATE INDEX IF NOT EXISTS idx_subscriptions_prof ON subscriptions(prof);
+
+            -- assignments a professor may open, granted by an admin (PAL_AUDIT_ADMINS); subscribing
+            -- needs a grant, so nobody can give themselves access to another course's submissions
+            CREATE TABLE IF NOT EXISTS assignment_access(
+              prof TEXT NOT NULL,
+              assignment_id TEXT NOT NULL,
+              granted_by TEXT NOT NULL,
+              created_at TEXT NOT NULL,
+              UNIQUE(prof, assignment_id)
+            );
+
+            -- domains an assignment legitimately allows (docs site, course LLM endpoint);
+            -- shared by every professor subscribed to it, applied when submissions are analyzed
+            CREATE TABLE IF NOT EXISTS allowlist(
+              assignment_id TEXT NOT NULL,
+              domain TEXT NOT NULL,
+              UNIQUE(assignment_id, domain)
+            );
+
+            -- domains an assignment's students are expected to visit (course LMS, docs site, sandbox);
+            -- lookups of anything else are counted as unexpected when submissions are analyzed
+            CREATE TABLE IF NOT EXISTS expected_domains(
+              assignment_id TEXT NOT NULL,
+              domain TEXT NOT NULL,
+              UNIQUE(assignment_id, domain)
+            );
+
+            -- per-seat credentials of live-streaming collectors; a token names its assignment and seat,
+            -- so a collector can only feed the seat it was issued for. Only the secret's sha256 is kept
+            CREATE TABLE IF NOT EXISTS live_tokens(
+              id TEXT PRIMARY KEY,
+              secret_sha256 TEXT NOT NULL,
+              assignment_id TEXT NOT NULL,
+              seat TEXT NOT NULL,
+              issued_by TEXT NOT NULL,
+              created_at TEXT NOT NULL,
+              UNIQUE(assignment_id, seat)
+            );
+
+            -- who looked at / exported / subscribed to what, for exam-integrity compliance
+            CREATE TABLE IF NOT EXISTS audit_log(
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              prof TEXT NOT NULL,
+              action TEXT NOT NULL,
+              target TEXT NOT NULL,
+              ts TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_audit_log_ts ON audit_log(ts);
+
+            -- per-submission data parsed out of the log at analysis time (timeline buckets,
+            -- process intervals, ...), JSON; dropped whenever the submission is analyzed again
+            CREATE TABLE IF NOT EXISTS derived_artifacts(
+              submission_ref TEXT NOT NULL,
+              kind TEXT NOT NULL,
+              json TEXT NOT NULL,
+              UNIQUE(submission_ref, kind),
+              FOREIGN KEY(submission_ref) REFERENCES submissions(id)
+            );
             "#
         ).expect("migrations");
+
+        // subscriptions made before grants existed keep working; admins can revoke them on /admin/access
+        if !had_access_table {
+            match conn.execute(
+                "INSERT OR IGNORE INTO assignment_access(prof, assignment_id, granted_by, created_at)
+                 SELECT prof, assignment_id, 'existing subscription', created_at FROM subscriptions",
+                [],
+            ) {
+                Ok(n) if n > 0 => log::warn!("granted access for {n} existing subscriptions, review them on /admin/access"),
+                Ok(_) => {}
+                Err(e) => log::error!("granting existing subscriptions: {e}"),
+            }
+        }
+
+        // columns added after the initial schema; "duplicate column" on existing dbs is expected
+        let _ = conn.execute("ALTER TABLE subscriptions ADD COLUMN profile TEXT NOT NULL DEFAULT 'ai-only'", []);
+        // comma separated exam-provided domains (docs, starter repo host, judge) per assignment
+        let _ = conn.execute("ALTER TABLE subscriptions ADD COLUMN provided_domains TEXT NOT NULL DEFAULT ''", []);
+        // moodle login name; student_name holds whichever identity the client chose as primary
+        let _ = conn.execute("ALTER TABLE submissions ADD COLUMN student_username TEXT", []);
+        // "Test my setup" uploads from the client; listed but left out of assignment stats
+        let _ = conn.execute("ALTER TABLE submissions ADD COLUMN is_test INTEGER NOT NULL DEFAULT 0", []);
+        // artifacts superseded by a re-upload; kept for the record, never analyzed again
+        let _ = conn.execute("ALTER TABLE logs ADD COLUMN archived INTEGER NOT NULL DEFAULT 0", []);
+        // when the professor last opened the assignment page, for "new since last view"
+        let _ = conn.execute("ALTER TABLE subscriptions ADD COLUMN last_seen_at TEXT", []);
+        // sha256 of the uploaded zip, to fold repeated identical uploads into one submission
+        let _ = conn.execute("ALTER TABLE submissions ADD COLUMN content_sha256 TEXT", []);
+        // 1 when the received bytes matched the sha256 the client declared for them
+        let _ = conn.execute("ALTER TABLE logs ADD COLUMN verified INTEGER NOT NULL DEFAULT 0", []);
+        // zip removed by retention; the findings stay but the submission can't be analyzed again
+        let _ = conn.execute("ALTER TABLE logs ADD COLUMN pruned INTEGER NOT NULL DEFAULT 0", []);
     }
     pool
 }
@@ -65,6 +165,9 @@ pub struct SubSummary {
     pub assignment_id: String,
     pub latest_status: String,
     pub count: i64,
+    pub profile: String,
+    pub allowlist: Vec<String>,
+    pub expected: Vec<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -73,6 +176,7 @@ pub struct SubmissionRow {
     pub student_name: String,
     pub created_at: String,
     pub status: String,
+    pub is_test: bool,
 }
 
 #[derive(serde::Serialize)]
@@ -80,8 +184,33 @@ pub struct SubmissionDetail {
     pub moodle_assignment_id: String,
     pub submission_id: String,
     pub student_name: String,
+    pub student_username: Option<String>,
     pub created_at: String,
     pub status: String,
+    pub is_test: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct AuditRow {
+    pub prof: String,
+    pub action: String,
+    pub target: String,
+    pub ts: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct AccessRow {
+    pub prof: String,
+    pub assignment_id: String,
+    pub granted_by: String,
+    pub created_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct LiveTokenRow {
+    pub seat: String,
+    pub issued_by: String,
+    pub created_at: String,
 }
 
 #[derive(serde::Serialize)]
@@ -89,6 +218,8 @@ pub struct LogRow {
     pub fs_path: String,
     pub sha256: String,
     pub size_bytes: i64,
+    pub archived: bool,
+    pub verified: bool,
 }
 
 /* Subscriptions */
@@ -104,7 +235,10 @@ pub fn list_subscription_summaries(pool: &Pool<SqliteConnectionManager>, prof: &
                  ORDER BY created_at DESC
                  LIMIT 1
                ), 'n/a') as latest_status,
-               (SELECT COUNT(*) FROM submissions WHERE submission_id = s.assignment_id) as cnt
+               (SELECT COUNT(*) FROM submissions WHERE submission_id = s.assignment_id AND is_test = 0) as cnt,
+               s.profile,
+               COALESCE((SELECT GROUP_CONCAT(domain) FROM allowlist WHERE assignment_id = s.assignment_id), ''),
+               COALESCE((SELECT GROUP_CONCAT(domain) FROM expected_domains WHERE assignment_id = s.assignment_id), '')
         FROM subscriptions s
         WHERE s.prof = ?1
         ORDER BY s.created_at DESC
@@ -116,6 +250,19 @@ pub fn list_subscription_summaries(pool: &Pool<SqliteConnectionManager>, prof: &
             assignment_id: r.get(0)?,
             latest_status: r.get(1)?,
             count: r.get::<_, i64>(2)?,
+            profile: r.get(3)?,
+            allowlist: r
+                .get::<_, String>(4)?
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            expected: r
+                .get::<_, String>(5)?
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -124,6 +271,64 @@ pub fn list_subscription_summaries(pool: &Pool<SqliteConnectionManager>, prof: &
     Ok(out)
 }
 
+/// Allowlisted domains of an assignment, sorted
+pub fn get_allowlist(pool: &Pool<SqliteConnectionManager>, assignment_id: &str) -> Result<Vec<String>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT domain FROM allowlist WHERE assignment_id = ?1 ORDER BY domain")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([assignment_id], |r| r.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for row in rows { out.push(row.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
+
+/// Replace the allowlist of an assignment
+pub fn set_allowlist(pool: &Pool<SqliteConnectionManager>, assignment_id: &str, domains: &[String]) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM allowlist WHERE assignment_id = ?1", [assignment_id])
+        .map_err(|e| e.to_string())?;
+    for d in domains {
+        tx.execute(
+            "INSERT OR IGNORE INTO allowlist(assignment_id, domain) VALUES(?1, ?2)",
+            params![assignment_id, d],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Expected domains of an assignment, sorted
+pub fn get_expected_domains(pool: &Pool<SqliteConnectionManager>, assignment_id: &str) -> Result<Vec<String>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT domain FROM expected_domains WHERE assignment_id = ?1 ORDER BY domain")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([assignment_id], |r| r.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for row in rows { out.push(row.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
+
+/// Replace the expected domains of an assignment
+pub fn set_expected_domains(pool: &Pool<SqliteConnectionManager>, assignment_id: &str, domains: &[String]) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM expected_domains WHERE assignment_id = ?1", [assignment_id])
+        .map_err(|e| e.to_string())?;
+    for d in domains {
+        tx.execute(
+            "INSERT OR IGNORE INTO expected_domains(assignment_id, domain) VALUES(?1, ?2)",
+            params![assignment_id, d],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
 pub fn subscribe(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str, created_at_rfc3339: &str) -> Result<(), String> {
     let conn = pool.get().map_err(|e| e.to_string())?;
     conn.execute(
@@ -133,6 +338,155 @@ pub fn subscribe(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id
     Ok(())
 }
 
+/// Subscribe to many assignments in one transaction.
+/// Returns (newly added, already present) ids, in input order.
+pub fn subscribe_many(
+    pool: &Pool<SqliteConnectionManager>,
+    prof: &str,
+    assignment_ids: &[String],
+    created_at_rfc3339: &str,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut added = Vec::new();
+    let mut existing = Vec::new();
+    for aid in assignment_ids {
+        let n = tx.execute(
+            "INSERT OR IGNORE INTO subscriptions(prof, assignment_id, created_at) VALUES(?1, ?2, ?3)",
+            params![prof, aid, created_at_rfc3339],
+        ).map_err(|e| e.to_string())?;
+        if n > 0 { added.push(aid.clone()); } else { existing.push(aid.clone()); }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok((added, existing))
+}
+
+/// Whether `prof` may see an assignment's submissions: only with a grant from an admin
+pub fn prof_can_access(pool: &Pool<SqliteConnectionManager>, prof: &str, aid: &str) -> Result<bool, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let n: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM assignment_access WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, aid],
+        |r| r.get(0),
+    ).map_err(|e| e.to_string())?;
+    Ok(n > 0)
+}
+
+/// Same check for a single submission (by its row id), through the assignment it belongs to
+pub fn prof_can_access_submission(pool: &Pool<SqliteConnectionManager>, prof: &str, id: &str) -> Result<bool, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let n: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM submissions s
+           JOIN assignment_access a ON a.assignment_id = s.submission_id
+          WHERE s.id = ?1 AND a.prof = ?2",
+        params![id, prof],
+        |r| r.get(0),
+    ).map_err(|e| e.to_string())?;
+    Ok(n > 0)
+}
+
+/// Sensitivity profile name a professor chose for an assignment (default when not subscribed)
+pub fn get_subscription_profile(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str) -> Result<String, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let profile: Option<String> = conn.query_row(
+        "SELECT profile FROM subscriptions WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, assignment_id],
+        |r| r.get(0),
+    ).optional().map_err(|e| e.to_string())?;
+    Ok(profile.unwrap_or_else(|| crate::routes::admin::util::consts::DEFAULT_PROFILE.to_string()))
+}
+
+/// Domains a professor marked as exam-provided for an assignment (empty when not subscribed)
+pub fn get_provided_domains(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str) -> Result<Vec<String>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let raw: Option<String> = conn.query_row(
+        "SELECT provided_domains FROM subscriptions WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, assignment_id],
+        |r| r.get(0),
+    ).optional().map_err(|e| e.to_string())?;
+    Ok(raw
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn set_provided_domains(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str, domains: &[String]) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE subscriptions SET provided_domains = ?3 WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, assignment_id, domains.join(",")],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Previous assignment page visit of a professor (None on the first visit or when not subscribed)
+pub fn get_last_seen(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str) -> Result<Option<String>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let seen: Option<Option<String>> = conn.query_row(
+        "SELECT last_seen_at FROM subscriptions WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, assignment_id],
+        |r| r.get(0),
+    ).optional().map_err(|e| e.to_string())?;
+    Ok(seen.flatten())
+}
+
+pub fn set_last_seen(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str, at_rfc3339: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE subscriptions SET last_seen_at = ?3 WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, assignment_id, at_rfc3339],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn set_subscription_profile(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str, profile: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE subscriptions SET profile = ?3 WHERE prof = ?1 AND assignment_id = ?2",
+        params![prof, assignment_id, profile],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// (status, count) over all submissions, for the worker queue overview
+pub fn queue_counts(pool: &Pool<SqliteConnectionManager>) -> Result<Vec<(String, i64)>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT status, COUNT(*) FROM submissions GROUP BY status ORDER BY status"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
+
+/// (submission id, student, processed at) of the most recently analyzed submission
+pub fn last_processed(pool: &Pool<SqliteConnectionManager>) -> Result<Option<(String, String, String)>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT s.id, s.student_name, f.created_at
+         FROM findings f JOIN submissions s ON s.id = f.submission_ref
+         WHERE f.key = ?1
+         ORDER BY f.created_at DESC LIMIT 1",
+        params![FK_ANALYSIS_MS],
+        |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+    ).optional().map_err(|e| e.to_string())
+}
+
+/// Mean analysis wall time in ms over all processed submissions
+pub fn avg_analysis_ms(pool: &Pool<SqliteConnectionManager>) -> Result<Option<f64>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT AVG(CAST(value AS REAL)) FROM findings WHERE key = ?1",
+        params![FK_ANALYSIS_MS],
+        |r| r.get(0),
+    ).map_err(|e| e.to_string())
+}
+
 pub fn unsubscribe(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_id: &str) -> Result<(), String> {
     let conn = pool.get().map_err(|e| e.to_string())?;
     conn.execute(
@@ -142,12 +496,102 @@ pub fn unsubscribe(pool: &Pool<SqliteConnectionManager>, prof: &str, assignment_
     Ok(())
 }
 
+/* Access grants */
+
+/// Let `prof` open assignment `aid`; false when the grant already existed
+pub fn grant_access(pool: &Pool<SqliteConnectionManager>, prof: &str, aid: &str, granted_by: &str) -> Result<bool, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let now = OffsetDateTime::now_utc().format(&Rfc3339).map_err(|e| e.to_string())?;
+    let n = conn.execute(
+        "INSERT OR IGNORE INTO assignment_access(prof, assignment_id, granted_by, created_at) VALUES(?1, ?2, ?3, ?4)",
+        params![prof, aid, granted_by, now],
+    ).map_err(|e| e.to_string())?;
+    Ok(n > 0)
+}
+
+/// Take a grant away, together with the professor's subscription to that assignment
+pub fn revoke_access(pool: &Pool<SqliteConnectionManager>, prof: &str, aid: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM assignment_access WHERE prof = ?1 AND assignment_id = ?2", params![prof, aid])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM subscriptions WHERE prof = ?1 AND assignment_id = ?2", params![prof, aid])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+pub fn list_access(pool: &Pool<SqliteConnectionManager>) -> Result<Vec<AccessRow>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT prof, assignment_id, granted_by, created_at FROM assignment_access ORDER BY prof, assignment_id"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |r| Ok(AccessRow {
+        prof: r.get(0)?,
+        assignment_id: r.get(1)?,
+        granted_by: r.get(2)?,
+        created_at: r.get(3)?,
+    })).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
+
+/* Live tokens */
+
+/// New token for a seat (replacing its previous one), as `<id>.<secret>`; only shown once
+pub fn issue_live_token(pool: &Pool<SqliteConnectionManager>, aid: &str, seat: &str, issued_by: &str) -> Result<String, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let now = OffsetDateTime::now_utc().format(&Rfc3339).map_err(|e| e.to_string())?;
+    let id = Uuid::new_v4().simple().to_string();
+    let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let secret_sha256 = hex::encode(Sha256::digest(secret.as_bytes()));
+    conn.execute(
+        "INSERT OR REPLACE INTO live_tokens(id, secret_sha256, assignment_id, seat, issued_by, created_at)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+        params![id, secret_sha256, aid, seat, issued_by, now],
+    ).map_err(|e| e.to_string())?;
+    Ok(format!("{id}.{secret}"))
+}
+
+/// (secret sha256 hex, assignment id, seat) of token `id`
+pub fn get_live_token(pool: &Pool<SqliteConnectionManager>, id: &str) -> Result<Option<(String, String, String)>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT secret_sha256, assignment_id, seat FROM live_tokens WHERE id = ?1",
+        [id],
+        |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+    ).optional().map_err(|e| e.to_string())
+}
+
+/// Seats of an assignment that have a token
+pub fn list_live_tokens(pool: &Pool<SqliteConnectionManager>, aid: &str) -> Result<Vec<LiveTokenRow>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT seat, issued_by, created_at FROM live_tokens WHERE assignment_id = ?1 ORDER BY seat"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([aid], |r| Ok(LiveTokenRow {
+        seat: r.get(0)?,
+        issued_by: r.get(1)?,
+        created_at: r.get(2)?,
+    })).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
+
+pub fn revoke_live_token(pool: &Pool<SqliteConnectionManager>, aid: &str, seat: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM live_tokens WHERE assignment_id = ?1 AND seat = ?2", params![aid, seat])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 /* Submissions listing and details */
 
 pub fn list_submissions_by_assignment(pool: &Pool<SqliteConnectionManager>, assignment_id: &str) -> Result<Vec<SubmissionRow>, String> {
     let conn = pool.get().map_err(|e| e.to_string())?;
     let mut stmt = conn.prepare(
-        "SELECT id, student_name, created_at, status
+        "SELECT id, student_name, created_at, status, is_test
          FROM submissions
          WHERE submission_id = ?1
          ORDER BY created_at DESC"
@@ -159,6 +603,7 @@ pub fn list_submissions_by_assignment(pool: &Pool<SqliteConnectionManager>, assi
             student_name: r.get(1)?,
             created_at: r.get(2)?,
             status: r.get(3)?,
+            is_test: r.get(4)?,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -170,7 +615,7 @@ pub fn list_submissions_by_assignment(pool: &Pool<SqliteConnectionManager>, assi
 pub fn get_submission_detail(pool: &Pool<SqliteConnectionManager>, id: &str) -> Result<Option<SubmissionDetail>, String> {
     let conn = pool.get().map_err(|e| e.to_string())?;
     let mut stmt = conn.prepare(
-        "SELECT submission_id, student_name, created_at, status, moodle_assignment_id
+        "SELECT submission_id, student_name, created_at, status, moodle_assignment_id, student_username, is_test
          FROM submissions
          WHERE id = ?1"
     ).map_err(|e| e.to_string())?;
@@ -182,6 +627,8 @@ pub fn get_submission_detail(pool: &Pool<SqliteConnectionManager>, id: &str) ->
             created_at: r.get(2)?,
             status: r.get(3)?,
             moodle_assignment_id: r.get(4)?,
+            student_username: r.get(5)?,
+            is_test: r.get(6)?,
         })
     }).optional().map_err(|e| e.to_string())?;
 
@@ -191,9 +638,10 @@ pub fn get_submission_detail(pool: &Pool<SqliteConnectionManager>, id: &str) ->
 pub fn list_logs_for_submission(pool: &Pool<SqliteConnectionManager>, submission_id: &str) -> Result<Vec<LogRow>, String> {
     let conn = pool.get().map_err(|e| e.to_string())?;
     let mut stmt = conn.prepare(
-        "SELECT fs_path, sha256, size_bytes
+        "SELECT fs_path, sha256, size_bytes, archived, verified
          FROM logs
-         WHERE submission_ref = ?1"
+         WHERE submission_ref = ?1
+         ORDER BY archived ASC, rowid DESC"
     ).map_err(|e| e.to_string())?;
 
     let rows = stmt.query_map([submission_id], |r| {
@@ -201,6 +649,8 @@ pub fn list_logs_for_submission(pool: &Pool<SqliteConnectionManager>, submission
             fs_path: r.get(0)?,
             sha256: r.get(1)?,
             size_bytes: r.get(2)?,
+            archived: r.get(3)?,
+            verified: r.get(4)?,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -209,31 +659,174 @@ pub fn list_logs_for_submission(pool: &Pool<SqliteConnectionManager>, submission
     Ok(out)
 }
 
+/// Submission an uploaded zip (by file name, as served from /uploads) belongs to
+pub fn submission_for_artifact(pool: &Pool<SqliteConnectionManager>, file_name: &str) -> Result<Option<String>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare("SELECT submission_ref, fs_path FROM logs WHERE fs_path LIKE '%' || ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([file_name], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?;
+    for r in rows {
+        let (sub, fs_path) = r.map_err(|e| e.to_string())?;
+        // LIKE treats _ and % in the name as wildcards; only an exact file name counts
+        if std::path::Path::new(&fs_path).file_name().is_some_and(|n| n == file_name) {
+            return Ok(Some(sub));
+        }
+    }
+    Ok(None)
+}
+
 /* create a new submission row and return its generated id */
-pub fn new_submission(
-    pool: &Pool<SqliteConnectionManager>,
-    submission_id: &str,
-    student_name: &str,
-    created_at_rfc3339: &str,
-    moodle_assignment_id: &str,
-    client_version: &str,
-) -> Result<String, String> {
+/// Identical uploads (same assignment, student and zip sha256) within this window are one submission
+const DUPLICATE_WINDOW_MINUTES: i64 = 60;
+
+/// A submission as received from the client
+pub struct NewSubmission<'a> {
+    pub submission_id: &'a str,
+    pub student_name: &'a str,
+    pub student_username: Option<&'a str>,
+    pub created_at_rfc3339: &'a str,
+    pub moodle_assignment_id: &'a str,
+    pub client_version: &'a str,
+    pub is_test: bool,
+    pub content_sha256: Option<&'a str>,
+}
+
+/// Insert a submission, or return the existing one when the same student uploaded the same
+/// zip for the same assignment within DUPLICATE_WINDOW_MINUTES (e.g. Submit clicked twice).
+/// Returns (id, created); `created` is false for such a duplicate. The lookup and the insert
+/// share one write transaction, so two concurrent identical uploads can't both insert.
+pub fn new_submission(pool: &Pool<SqliteConnectionManager>, sub: &NewSubmission) -> Result<(String, bool), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| e.to_string())?;
+    if let Some(sha) = sub.content_sha256 {
+        let since = OffsetDateTime::parse(sub.created_at_rfc3339, &Rfc3339)
+            .map(|t| t - time::Duration::minutes(DUPLICATE_WINDOW_MINUTES))
+            .map_err(|e| e.to_string())?
+            .format(&Rfc3339)
+            .map_err(|e| e.to_string())?;
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT id FROM submissions
+                 WHERE submission_id = ?1 AND student_name = ?2 AND content_sha256 = ?3 AND created_at >= ?4
+                 ORDER BY created_at DESC LIMIT 1",
+                params![sub.submission_id, sub.student_name, sha, since],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if let Some(id) = existing {
+            return Ok((id, false));
+        }
+    }
+
     let id = Uuid::new_v4().to_string();
-    let conn = pool.get().map_err(|e| e.to_string())?;
-    conn.execute(
-        "INSERT INTO submissions(id, submission_id, student_name, created_at, moodle_assignment_id, client_version, status)
-         VALUES(?1, ?2, ?3, ?4, ?5, ?6, 'received')",
+    tx.execute(
+        "INSERT INTO submissions(id, submission_id, student_name, student_username, created_at, moodle_assignment_id, client_version, status, is_test, content_sha256)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, 'received', ?8, ?9)",
         params![
             &id,
-            submission_id,
-            student_name,
-            created_at_rfc3339,
-            moodle_assignment_id,
-            client_version
+            sub.submission_id,
+            sub.student_name,
+            sub.student_username,
+            sub.created_at_rfc3339,
+            sub.moodle_assignment_id,
+            sub.client_version,
+            sub.is_test,
+            sub.content_sha256
         ],
     )
     .map_err(|e| e.to_string())?;
-    Ok(id)
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok((id, true))
+}
+
+pub fn set_submission_status(pool: &Pool<SqliteConnectionManager>, id: &str, status: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute("UPDATE submissions SET status = ?2 WHERE id = ?1", params![id, status])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Re-upload of an existing submission: archive its current artifacts, drop the findings
+/// of the old analysis and queue it again. Call after the new artifact is safely on disk.
+pub fn reset_for_reupload(pool: &Pool<SqliteConnectionManager>, id: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute("UPDATE logs SET archived = 1 WHERE submission_ref = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM findings WHERE submission_ref = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM derived_artifacts WHERE submission_ref = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.execute("UPDATE submissions SET status = 'received' WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Submissions (as `s`) whose current artifact is still on disk, i.e. that can be analyzed again
+const HAS_ARTIFACT: &str = "EXISTS (SELECT 1 FROM logs l WHERE l.submission_ref = s.id AND l.archived = 0 AND l.pruned = 0)";
+
+/// What `requeue_submission` did
+#[derive(Debug, PartialEq)]
+pub enum Requeue {
+    Queued,
+    // still queued or being analyzed
+    Busy,
+    // its zip was removed by retention; the findings are all that is left
+    Pruned,
+}
+
+/// Queue one analyzed submission again (e.g. after detection rules changed): drop its findings
+/// and mark it 'received'. Left alone when it is still queued or its zip is gone.
+pub fn requeue_submission(pool: &Pool<SqliteConnectionManager>, id: &str) -> Result<Requeue, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let n = tx
+        .execute(
+            &format!("UPDATE submissions SET status = 'received'
+                      WHERE id = ?1 AND status IN ('processed', 'failed')
+                        AND id IN (SELECT s.id FROM submissions s WHERE {HAS_ARTIFACT})"),
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+    if n == 0 {
+        let busy: bool = tx
+            .query_row("SELECT status NOT IN ('processed', 'failed') FROM submissions WHERE id = ?1", [id], |r| r.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or(false);
+        return Ok(if busy { Requeue::Busy } else { Requeue::Pruned });
+    }
+    tx.execute("DELETE FROM findings WHERE submission_ref = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM derived_artifacts WHERE submission_ref = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(Requeue::Queued)
+}
+
+/// Queue every analyzed submission of an assignment again (e.g. after its allowlist changed):
+/// drop their findings and mark them 'received'. Submissions whose zip was pruned keep their
+/// findings. Returns how many were queued.
+pub fn requeue_assignment(pool: &Pool<SqliteConnectionManager>, assignment_id: &str) -> Result<usize, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let which = format!(
+        "SELECT s.id FROM submissions s
+         WHERE s.submission_id = ?1 AND s.status IN ('processed', 'failed') AND {HAS_ARTIFACT}"
+    );
+    tx.execute(&format!("DELETE FROM findings WHERE submission_ref IN ({which})"), [assignment_id])
+        .map_err(|e| e.to_string())?;
+    tx.execute(&format!("DELETE FROM derived_artifacts WHERE submission_ref IN ({which})"), [assignment_id])
+        .map_err(|e| e.to_string())?;
+    let n = tx
+        .execute(&format!("UPDATE submissions SET status = 'received' WHERE id IN ({which})"), [assignment_id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(n)
 }
 
 /* add a log artifact row and return its generated id */
@@ -243,18 +836,37 @@ pub fn add_log_artifact(
     fs_path: &str,
     sha256_hex: &str,
     size_bytes: i64,
+    verified: bool,
 ) -> Result<String, String> {
     let id = Uuid::new_v4().to_string();
     let conn = pool.get().map_err(|e| e.to_string())?;
     conn.execute(
-        "INSERT INTO logs(id, submission_ref, fs_path, sha256, size_bytes)
-         VALUES(?1, ?2, ?3, ?4, ?5)",
-        params![&id, submission_ref, fs_path, sha256_hex, size_bytes],
+        "INSERT INTO logs(id, submission_ref, fs_path, sha256, size_bytes, verified)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+        params![&id, submission_ref, fs_path, sha256_hex, size_bytes, verified],
     )
     .map_err(|e| e.to_string())?;
     Ok(id)
 }
 
+/* add a single finding outside of the analyzer (e.g. data sent with the upload) */
+pub fn add_finding(
+    pool: &Pool<SqliteConnectionManager>,
+    submission_ref: &str,
+    kind: &str,
+    key: &str,
+    value: &str,
+    created_at_rfc3339: &str,
+) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO findings(id, submission_ref, kind, key, value, created_at)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+        params![Uuid::new_v4().to_string(), submission_ref, kind, key, value, created_at_rfc3339],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
 // in src/db.rs
 #[derive(serde::Serialize, Clone, Debug)]
@@ -306,13 +918,61 @@ pub fn list_findings_for_submissions(
 }
 
 
-pub fn fetch_durations_minutes(conn: &rusqlite::Connection, aid: &str) -> Vec<i64> {
+#[derive(serde::Serialize, Debug)]
+pub struct FindingExportRow {
+    pub submission_ref: String,
+    pub student: String,
+    pub kind: String,
+    pub key: String,
+    pub value: String,
+    pub created_at: String,
+}
+
+/// One page of an assignment's findings, keyed by findings.rowid so callers can stream
+/// the whole set in bounded batches. Returns (rowid, row) pairs in rowid order.
+pub fn list_assignment_findings_after(
+    pool: &Pool<SqliteConnectionManager>,
+    assignment_id: &str,
+    after_rowid: i64,
+    limit: usize,
+) -> Result<Vec<(i64, FindingExportRow)>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT f.rowid, f.submission_ref, s.student_name, f.kind, f.key, f.value, f.created_at
+         FROM findings f
+         JOIN submissions s ON s.id = f.submission_ref
+         WHERE s.submission_id = ?1 AND f.rowid > ?2
+         ORDER BY f.rowid ASC
+         LIMIT ?3"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![assignment_id, after_rowid, limit as i64], |r| {
+            Ok((r.get(0)?, FindingExportRow {
+                submission_ref: r.get(1)?,
+                student: r.get(2)?,
+                kind: r.get(3)?,
+                key: r.get(4)?,
+                value: r.get(5)?,
+                created_at: r.get(6)?,
+            }))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut out = Vec::with_capacity(limit);
+    for r in rows {
+        out.push(r.map_err(|e| e.to_string())?);
+    }
+    Ok(out)
+}
+
+/// `visible` narrows the submissions further (a condition over `s` starting with AND)
+pub fn fetch_durations_minutes(conn: &rusqlite::Connection, aid: &str, visible: &str) -> Vec<i64> {
     let mut out = Vec::new();
-    let mut q = conn.prepare(
+    let mut q = conn.prepare(&format!(
       "SELECT value FROM findings f
          JOIN submissions s ON s.id = f.submission_ref
-       WHERE s.submission_id = ?1 AND f.key = 'duration_minutes'"
-    ).unwrap();
+       WHERE s.submission_id = ?1 AND s.is_test = 0{visible} AND f.key = 'duration_minutes'"
+    )).unwrap();
     let rows = q.query_map(params![aid], |r| r.get::<_, String>(0)).unwrap();
     
     for r in rows {
@@ -354,4 +1014,132 @@ pub fn list_findings_for_submission(
         out.push(row.map_err(|e| e.to_string())?);
     }
     Ok(out)
-}
\ No newline at end of file
+}
+
+/* Derived artifacts */
+
+/// Cached JSON of one derived artifact of a submission (see crate::derived)
+pub fn get_derived(pool: &Pool<SqliteConnectionManager>, submission_ref: &str, kind: &str) -> Result<Option<String>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT json FROM derived_artifacts WHERE submission_ref = ?1 AND kind = ?2",
+        params![submission_ref, kind],
+        |r| r.get(0),
+    ).optional().map_err(|e| e.to_string())
+}
+
+/// Takes a connection so the worker can store them in the same transaction as the findings
+pub fn put_derived(conn: &rusqlite::Connection, submission_ref: &str, kind: &str, json: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO derived_artifacts(submission_ref, kind, json) VALUES(?1, ?2, ?3)",
+        params![submission_ref, kind, json],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/* Audit log */
+
+/// Append one admin action to the audit log, timestamped now
+pub fn record_audit(pool: &Pool<SqliteConnectionManager>, prof: &str, action: &str, target: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let now = OffsetDateTime::now_utc().format(&Rfc3339).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO audit_log(prof, action, target, ts) VALUES(?1, ?2, ?3, ?4)",
+        params![prof, action, target, now],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// One page of the audit log, newest first, plus the total number of entries
+pub fn list_audit(pool: &Pool<SqliteConnectionManager>, limit: i64, offset: i64) -> Result<(Vec<AuditRow>, i64), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let total: i64 = conn.query_row("SELECT COUNT(*) FROM audit_log", [], |r| r.get(0))
+        .map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT prof, action, target, ts FROM audit_log ORDER BY id DESC LIMIT ?1 OFFSET ?2"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![limit, offset], |r| Ok(AuditRow {
+        prof: r.get(0)?,
+        action: r.get(1)?,
+        target: r.get(2)?,
+        ts: r.get(3)?,
+    })).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
+    Ok((out, total))
+}
+
+/* Retention */
+
+/// (log id, fs_path as stored) of the artifacts of analyzed submissions created before the
+/// cutoff that are not pruned yet
+pub fn list_expired_logs(pool: &Pool<SqliteConnectionManager>, cutoff_rfc3339: &str) -> Result<Vec<(String, String)>, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT l.id, l.fs_path FROM logs l
+         JOIN submissions s ON s.id = l.submission_ref
+         WHERE s.created_at < ?1 AND s.status IN ('processed', 'failed') AND l.pruned = 0"
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([cutoff_rfc3339], |r| Ok((r.get(0)?, r.get(1)?))).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
+
+/// Record that retention removed the zip of a log row
+pub fn mark_log_pruned(pool: &Pool<SqliteConnectionManager>, log_id: &str) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    conn.execute("UPDATE logs SET pruned = 1 WHERE id = ?1", [log_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Delete analyzed submissions created before the cutoff together with their logs, findings
+/// and derived artifacts. Queued ones are left alone. Returns how many submissions went.
+pub fn delete_submissions_before(pool: &Pool<SqliteConnectionManager>, cutoff_rfc3339: &str) -> Result<usize, String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let which = "SELECT id FROM submissions WHERE created_at < ?1 AND status IN ('processed', 'failed')";
+    for table in ["findings", "derived_artifacts", "logs"] {
+        tx.execute(&format!("DELETE FROM {table} WHERE submission_ref IN ({which})"), [cutoff_rfc3339])
+            .map_err(|e| e.to_string())?;
+    }
+    let n = tx
+        .execute(&format!("DELETE FROM submissions WHERE id IN ({which})"), [cutoff_rfc3339])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_identical_uploads_make_one_submission() {
+        let data = crate::test_state("");
+        let created: Vec<(String, bool)> = std::thread::scope(|scope| {
+            let uploads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| {
+                    new_submission(&data.pool, &NewSubmission {
+                        submission_id: "a1",
+                        student_name: "student",
+                        student_username: None,
+                        created_at_rfc3339: "2024-03-01T10:00:00Z",
+                        moodle_assignment_id: "",
+                        client_version: "client",
+                        is_test: false,
+                        content_sha256: Some("abc"),
+                    }).unwrap()
+                }))
+                .collect();
+            uploads.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(created.iter().filter(|(_, c)| *c).count(), 1);
+        assert!(created.iter().all(|(id, _)| *id == created[0].0));
+        let conn = data.pool.get().unwrap();
+        let n: i64 = conn.query_row("SELECT COUNT(*) FROM submissions", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 1);
+    }
+}
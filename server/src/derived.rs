@@ -0,0 +1,190 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{BufRead, BufReader},
+};
+
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use palantir_core::log::Event;
+
+use crate::{db, routes::admin::util::{consts::{is_ai_domain, is_private_ip}, localtime::minute_bucket, zip::open_processed_zip_by_submission}, template::is_provided_domain, AppState};
+
+// derived_artifacts.kind values
+pub const DERIVED_NET_MINUTES: &str = "net_minutes";
+pub const DERIVED_PROC_INTERVALS: &str = "proc_intervals";
+pub const DERIVED_LAN_IPS: &str = "lan_ips";
+
+/// Net events of one UTC minute (unix minutes)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct NetMinute {
+    pub minute: i64,
+    pub total: i32,
+    pub ai: i32,
+}
+
+/// One run of a process, epoch ms; `still_running` when the log ended before its stop event
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ProcInterval {
+    pub start: i64,
+    pub end: i64,
+    pub still_running: bool,
+}
+
+/// Raw (unmerged) run intervals per process name, as logged
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ProcIntervals {
+    pub by_comm: BTreeMap<String, Vec<ProcInterval>>,
+}
+
+/// What the timeline and LAN views need from a log, computed once while it is analyzed
+/// and stored in `derived_artifacts`, so they don't unzip and re-parse it on every request.
+pub struct Derived {
+    pub net_minutes: Vec<NetMinute>,
+    pub proc_intervals: ProcIntervals,
+    // distinct private source addresses
+    pub lan_ips: Vec<String>,
+}
+
+/// Fed every parsed log line, in order
+#[derive(Default)]
+pub struct DerivedBuilder {
+    // the assignment's allowlisted domains, not counted as AI (as in `ai_hits_total`)
+    allowlist: Vec<String>,
+    net: BTreeMap<i64, (i32, i32)>,
+    open: HashMap<i32, (String, i64)>, // pid -> (comm, start ms)
+    intervals: BTreeMap<String, Vec<ProcInterval>>,
+    proc_max: Option<i64>,
+    lan_ips: BTreeSet<String>,
+}
+
+impl DerivedBuilder {
+    pub fn new(allowlist: &[String]) -> Self {
+        DerivedBuilder { allowlist: allowlist.to_vec(), ..Default::default() }
+    }
+
+    pub fn observe(&mut self, line: &Event) {
+        if !matches!(line, Event::Net(_) | Event::Proc(_)) {
+            return;
+        }
+        let Some(t) = line.ts().and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok()) else {
+            return;
+        };
+
+        let p = match line {
+            Event::Net(n) => {
+                let entry = self.net.entry(minute_bucket(t)).or_default();
+                entry.0 += 1;
+                if is_ai_domain(&n.dns_qname) && !is_provided_domain(&n.dns_qname, &self.allowlist) {
+                    entry.1 += 1;
+                }
+                if is_private_ip(&n.src_ip) && !self.lan_ips.contains(&n.src_ip) {
+                    self.lan_ips.insert(n.src_ip.clone());
+                }
+                return;
+            }
+            Event::Proc(p) => p,
+            _ => return,
+        };
+
+        let ms = (t.unix_timestamp_nanos() / 1_000_000) as i64;
+        self.proc_max = Some(self.proc_max.map_or(ms, |m| m.max(ms)));
+        match p.action.as_str() {
+            "start" => {
+                self.open.insert(p.pid, (p.comm.clone(), ms));
+            }
+            "stop" => {
+                if let Some((comm, start)) = self.open.remove(&p.pid) {
+                    self.intervals.entry(comm).or_default().push(ProcInterval { start, end: ms, still_running: false });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn finish(mut self) -> Derived {
+        // dangling starts run until the last proc event, flagged as open-ended
+        if let Some(end) = self.proc_max {
+            for (_pid, (comm, start)) in self.open.drain() {
+                self.intervals.entry(comm).or_default().push(ProcInterval { start, end, still_running: true });
+            }
+        }
+        Derived {
+            net_minutes: self.net.into_iter().map(|(minute, (total, ai))| NetMinute { minute, total, ai }).collect(),
+            proc_intervals: ProcIntervals { by_comm: self.intervals },
+            lan_ips: self.lan_ips.into_iter().collect(),
+        }
+    }
+}
+
+impl Derived {
+    /// (kind, json) rows for `derived_artifacts`
+    pub fn to_rows(&self) -> Result<Vec<(&'static str, String)>, String> {
+        Ok(vec![
+            (DERIVED_NET_MINUTES, serde_json::to_string(&self.net_minutes).map_err(|e| e.to_string())?),
+            (DERIVED_PROC_INTERVALS, serde_json::to_string(&self.proc_intervals).map_err(|e| e.to_string())?),
+            (DERIVED_LAN_IPS, serde_json::to_string(&self.lan_ips).map_err(|e| e.to_string())?),
+        ])
+    }
+}
+
+/// Parse the submission's processed log again and store the result; `None` when the
+/// archive has no log. Fallback for submissions analyzed before derived_artifacts existed.
+fn derive_and_store(data: &AppState, id: &str) -> Result<Option<Derived>, String> {
+    let mut zip = open_processed_zip_by_submission(data, id)?;
+    let mut log = match zip.by_name("snapshot/palantir.log") {
+        Ok(f) => BufReader::new(f),
+        Err(_) => return Ok(None),
+    };
+
+    let allowlist = match db::get_submission_detail(&data.pool, id)? {
+        // keyed like the worker does, by the assignment the submission was uploaded to
+        Some(sub) => db::get_allowlist(&data.pool, &sub.submission_id)?,
+        None => Vec::new(),
+    };
+    let mut builder = DerivedBuilder::new(&allowlist);
+    let mut line = String::new();
+    while let Ok(n) = log.read_line(&mut line) {
+        if n == 0 { break; }
+        if let Ok(entry) = Event::parse(&line) {
+            builder.observe(&entry);
+        }
+        line.clear();
+    }
+    let derived = builder.finish();
+
+    let conn = data.pool.get().map_err(|e| e.to_string())?;
+    for (kind, json) in derived.to_rows()? {
+        db::put_derived(&conn, id, kind, &json)?;
+    }
+    Ok(Some(derived))
+}
+
+fn cached<T: serde::de::DeserializeOwned>(data: &AppState, id: &str, kind: &str) -> Option<T> {
+    let json = db::get_derived(&data.pool, id, kind).ok().flatten()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Per-minute net activity of a submission; empty when it has no log
+pub fn net_minutes(data: &AppState, id: &str) -> Result<Vec<NetMinute>, String> {
+    if let Some(v) = cached(data, id, DERIVED_NET_MINUTES) {
+        return Ok(v);
+    }
+    Ok(derive_and_store(data, id)?.map(|d| d.net_minutes).unwrap_or_default())
+}
+
+/// Process run intervals of a submission; `None` when it has no log
+pub fn proc_intervals(data: &AppState, id: &str) -> Result<Option<ProcIntervals>, String> {
+    if let Some(v) = cached(data, id, DERIVED_PROC_INTERVALS) {
+        return Ok(Some(v));
+    }
+    Ok(derive_and_store(data, id)?.map(|d| d.proc_intervals))
+}
+
+/// Private source addresses seen in a submission's net events
+pub fn lan_ips(data: &AppState, id: &str) -> Result<Vec<String>, String> {
+    if let Some(v) = cached(data, id, DERIVED_LAN_IPS) {
+        return Ok(v);
+    }
+    Ok(derive_and_store(data, id)?.map(|d| d.lan_ips).unwrap_or_default())
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use sha2::{Digest, Sha256};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::db;
+
+/// In-memory relay between live collector streams and the professors watching them.
+/// Streams are keyed by `{assignment_id}/{seat}`; nothing here is persisted, the
+/// batch upload of palantir.log stays the source of truth.
+#[derive(Default)]
+pub struct LiveHub {
+    next_id: AtomicU64,
+    // stream key -> watching websocket sessions (with an id for removal)
+    watchers: Mutex<HashMap<String, Vec<(u64, actix_ws::Session)>>>,
+    // stream key -> (connection id, connected-since timestamp) of the collector feeding it
+    sources: Mutex<HashMap<String, (u64, String)>>,
+}
+
+pub fn stream_key(assignment_id: &str, seat: &str) -> String {
+    format!("{assignment_id}/{seat}")
+}
+
+/// (assignment id, seat) a collector's `<id>.<secret>` token was issued for; None for unknown
+/// or wrong tokens. The secret's hash is compared in constant time.
+pub fn check_token(pool: &Pool<SqliteConnectionManager>, token: &str) -> Option<(String, String)> {
+    let (id, secret) = token.trim().split_once('.')?;
+    let (stored, aid, seat) = db::get_live_token(pool, id).ok()??;
+    let presented = hex::encode(Sha256::digest(secret.as_bytes()));
+    constant_time_eq(presented.as_bytes(), stored.as_bytes()).then_some((aid, seat))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl LiveHub {
+    pub fn add_watcher(&self, key: &str, session: actix_ws::Session) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.watchers
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .push((id, session));
+        id
+    }
+
+    pub fn remove_watcher(&self, key: &str, id: u64) {
+        let mut w = self.watchers.lock().unwrap();
+        if let Some(list) = w.get_mut(key) {
+            list.retain(|(i, _)| *i != id);
+            if list.is_empty() {
+                w.remove(key);
+            }
+        }
+    }
+
+    /// Register the collector feeding `key`, replacing an older connection; returns its id
+    pub fn source_connected(&self, key: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let now = OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default();
+        self.sources.lock().unwrap().insert(key.to_string(), (id, now));
+        id
+    }
+
+    /// Forget connection `id`; a collector that already reconnected under `key` stays
+    pub fn source_disconnected(&self, key: &str, id: u64) {
+        let mut sources = self.sources.lock().unwrap();
+        if sources.get(key).is_some_and(|(i, _)| *i == id) {
+            sources.remove(key);
+        }
+    }
+
+    /// (seat, connected_since) of collectors currently streaming for an assignment
+    pub fn live_seats(&self, assignment_id: &str) -> Vec<(String, String)> {
+        let prefix = format!("{assignment_id}/");
+        let mut out: Vec<(String, String)> = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(k, (_, since))| k.strip_prefix(&prefix).map(|seat| (seat.to_string(), since.clone())))
+            .collect();
+        out.sort();
+        out
+    }
+
+    /// Forward one event line to every watcher of `key`, dropping closed sessions.
+    pub async fn publish(&self, key: &str, line: &str) {
+        let sessions: Vec<(u64, actix_ws::Session)> = match self.watchers.lock().unwrap().get(key) {
+            Some(list) => list.clone(),
+            None => return,
+        };
+
+        for (id, mut s) in sessions {
+            if s.text(line.to_string()).await.is_err() {
+                self.remove_watcher(key, id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_disconnect_keeps_the_reconnected_collector() {
+        let hub = LiveHub::default();
+        let key = stream_key("a1", "seat-3");
+        let old = hub.source_connected(&key);
+        let new = hub.source_connected(&key);
+
+        // the old socket finishes closing after the collector came back
+        hub.source_disconnected(&key, old);
+        assert_eq!(hub.live_seats("a1").len(), 1);
+
+        hub.source_disconnected(&key, new);
+        assert!(hub.live_seats("a1").is_empty());
+    }
+}
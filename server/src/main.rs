@@ -1,5 +1,5 @@
 use actix_session::{config::CookieContentSecurity, storage::CookieSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, App, HttpServer, web};
+use actix_web::{cookie::{Key, SameSite}, middleware::Compress, App, HttpServer, web};
 use once_cell::sync::Lazy;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -7,11 +7,18 @@ use std::{env, fs, path::PathBuf};
 use tera::Tera;
 
 mod db;
+mod derived;
+mod live;
+mod moodle;
+mod rate_limit;
+mod retention;
+mod risk;
 mod upload_processing;
+mod webhook;
 mod routes;
 mod template;
 
-use routes::{auth, api, files, admin};
+use routes::{auth, api, files, admin, health};
 
 static COOKIE_KEY: Lazy<Key> = Lazy::new(|| {
     let hex_key = env::var("COOKIE_KEY_HEX").expect("COOKIE_KEY_HEX not set");
@@ -19,12 +26,111 @@ static COOKIE_KEY: Lazy<Key> = Lazy::new(|| {
     Key::from(bytes.as_slice())
 });
 
+/// Finished-submission events kept for slow assignment feeds before they miss some
+const PROCESSED_EVENTS_BUFFER: usize = 256;
+
 #[derive(Clone)]
 pub struct AppState {
     pub pool: Pool<SqliteConnectionManager>,
     pub upload_dir: PathBuf,
+    // free space (bytes) to keep on upload_dir; uploads are refused below it
+    pub min_free_disk_bytes: u64,
+    // uploads larger than this are refused with 413 (PAL_MAX_UPLOAD_BYTES, 0 = no limit)
+    pub max_upload_bytes: u64,
+    // per-client allowance for starting uploads on the public API (PAL_UPLOAD_RATE_*, off by default)
+    pub upload_rate: std::sync::Arc<rate_limit::RateLimiter>,
+    // ... and for the chunk / status / complete requests of chunked uploads (PAL_UPLOAD_SESSION_RATE_*)
+    pub upload_session_rate: std::sync::Arc<rate_limit::RateLimiter>,
     pub processed_dir: PathBuf,
     pub tera: Tera,
+    // submissions below these thresholds are hidden from the default card/table view (0 disables)
+    pub hide_min_duration_minutes: i64,
+    pub hide_min_net_events: i64,
+    pub live: std::sync::Arc<live::LiveHub>,
+    pub worker: std::sync::Arc<upload_processing::WorkerStatus>,
+    pub analysis: upload_processing::AnalysisConfig,
+    // admin sessions expire this long after login, and after this long without a request (0 disables either)
+    pub session_ttl_secs: i64,
+    pub session_idle_secs: i64,
+    // url prefix when served behind a reverse proxy, e.g. "/palantir" ("" at the root)
+    pub base_path: String,
+    // SYSTEM_HIDE_PROCS merged with HIDE_PROCS_EXTRA, hidden in the process timeline
+    pub hide_procs: Vec<String>,
+    // outlier flagging and percentile displays need at least this many submissions
+    pub min_stats_submissions: usize,
+    // how /admin/login checks credentials (PAL_AUTH_MODE)
+    pub auth_mode: routes::auth::AuthMode,
+    // moodle site uploads are checked against (student_name must match the token's account); None skips the check
+    pub verify_identity_moodle: Option<String>,
+    // professors allowed to read /admin/audit and /admin/system and grant assignment access on /admin/access (PAL_AUDIT_ADMINS)
+    pub audit_admins: Vec<String>,
+    // per-submission net events per minute for the correlation view
+    pub net_minutes: std::sync::Arc<routes::admin::assignment::get_correlation::NetMinuteCache>,
+    // the background processor announces finished submissions here (assignment live feed)
+    pub processed_tx: tokio::sync::broadcast::Sender<upload_processing::ProcessedEvent>,
+    // notify PAL_WEBHOOK_URL about high-risk submissions (None disables)
+    pub webhook: Option<webhook::WebhookConfig>,
+}
+
+/// PAL_COOKIE_SAMESITE value; anything unrecognized falls back to Lax
+fn parse_same_site(raw: &str) -> SameSite {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "strict" => SameSite::Strict,
+        "none" => SameSite::None,
+        "lax" | "" => SameSite::Lax,
+        other => {
+            log::warn!("PAL_COOKIE_SAMESITE: unknown value '{other}', using Lax");
+            SameSite::Lax
+        }
+    }
+}
+
+/// "palantir/" -> "/palantir", "/" or "" -> ""
+fn normalize_base_path(raw: &str) -> String {
+    let trimmed = raw.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{trimmed}")
+    }
+}
+
+/// Defaults for the handler tests: a fresh database in the temp dir, no templates, static login
+/// as "prof" / "secret"
+#[cfg(test)]
+fn test_state(base_path: &str) -> AppState {
+    use sha2::{Digest, Sha256};
+    let dir = std::env::temp_dir().join(format!("palantir-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    AppState {
+        pool: db::init_db(dir.join("palantir.db").to_str().unwrap()),
+        upload_dir: dir.join("uploads"),
+        min_free_disk_bytes: 0,
+        max_upload_bytes: 0,
+        upload_rate: std::sync::Arc::new(rate_limit::RateLimiter::new(0.0, 1.0, vec![])),
+        upload_session_rate: std::sync::Arc::new(rate_limit::RateLimiter::new(0.0, 1.0, vec![])),
+        processed_dir: dir.join("processed_uploads"),
+        tera: Tera::default(),
+        hide_min_duration_minutes: 0,
+        hide_min_net_events: 0,
+        live: Default::default(),
+        worker: Default::default(),
+        analysis: Default::default(),
+        session_ttl_secs: 3600,
+        session_idle_secs: 3600,
+        base_path: base_path.to_string(),
+        hide_procs: vec![],
+        min_stats_submissions: 5,
+        auth_mode: auth::AuthMode::Static {
+            user: "prof".into(),
+            password_sha256: hex::encode(Sha256::digest(b"secret")),
+        },
+        verify_identity_moodle: None,
+        audit_admins: vec![],
+        net_minutes: Default::default(),
+        processed_tx: tokio::sync::broadcast::channel(PROCESSED_EVENTS_BUFFER).0,
+        webhook: None,
+    }
 }
 
 #[actix_web::main]
@@ -42,71 +148,218 @@ async fn main() -> std::io::Result<()> {
         if p.is_absolute() { p } else { std::env::current_dir().unwrap().join(p) }
     };
     let processed_dir = std::env::current_dir().unwrap().join("processed_uploads");
+    let hide_min_duration_minutes: i64 = env::var("HIDE_MIN_DURATION_MINUTES").ok().and_then(|s| s.parse().ok()).unwrap_or(0);
+    let hide_min_net_events: i64 = env::var("HIDE_MIN_NET_EVENTS").ok().and_then(|s| s.parse().ok()).unwrap_or(0);
+    let base_path = normalize_base_path(&env::var("BASE_PATH").unwrap_or_default());
+    let min_free_disk_mb: u64 = env::var("MIN_FREE_DISK_MB").ok().and_then(|s| s.parse().ok()).unwrap_or(512);
+    let max_upload_bytes: u64 = env::var("PAL_MAX_UPLOAD_BYTES").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(256 * 1024 * 1024);
+    let upload_rate = rate_limit::RateLimiter::from_env("PAL_UPLOAD_RATE", 10.0);
+    let upload_session_rate = rate_limit::RateLimiter::from_env("PAL_UPLOAD_SESSION_RATE", 120.0);
+    if upload_rate.enabled() || upload_session_rate.enabled() {
+        log::info!("uploads are rate limited per client address");
+    }
+    let hide_procs = admin::util::consts::effective_hide_procs(&env::var("HIDE_PROCS_EXTRA").unwrap_or_default());
+    let min_stats_submissions: usize = env::var("MIN_STATS_SUBMISSIONS").ok().and_then(|s| s.parse().ok()).unwrap_or(5);
+    let verify_identity_moodle = env::var("MOODLE_BASE_URL")
+        .ok()
+        .map(|s| s.trim().trim_end_matches('/').to_string())
+        .filter(|s| !s.is_empty())
+        .filter(|_| env::var("VERIFY_STUDENT_IDENTITY").map(|v| v.trim() != "false").unwrap_or(true));
+    let auth_mode = auth::AuthMode::from_env();
+    log::info!("admin login mode: {}", match &auth_mode {
+        auth::AuthMode::Ldap => "ldap",
+        auth::AuthMode::Static { .. } => "static",
+        auth::AuthMode::Disabled => "disabled",
+    });
+    let audit_admins: Vec<String> = env::var("PAL_AUDIT_ADMINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let retention = retention::RetentionConfig::from_env();
+    let webhook = webhook::WebhookConfig::from_env();
+    let workers: usize = env::var("PAL_WORKERS").ok().and_then(|s| s.parse().ok()).unwrap_or(1).max(1);
+    // SESSION_MAX_AGE_MINUTES is the older name of the absolute ttl
+    let session_ttl_secs: i64 = env::var("PAL_SESSION_TTL_SECS").ok().and_then(|s| s.parse().ok())
+        .or_else(|| env::var("SESSION_MAX_AGE_MINUTES").ok().and_then(|s| s.parse::<i64>().ok()).map(|m| m * 60))
+        .unwrap_or(12 * 3600);
+    let session_idle_secs: i64 = env::var("PAL_SESSION_IDLE_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(3600);
+    // the session cookie is only sent over https unless PAL_COOKIE_SECURE=false (plain http local dev);
+    // behind a TLS terminating proxy the browser sees https, so keep it true there too
+    let cookie_secure = env::var("PAL_COOKIE_SECURE").map(|v| v.trim() != "false").unwrap_or(true);
+    let cookie_same_site = parse_same_site(&env::var("PAL_COOKIE_SAMESITE").unwrap_or_default());
+    if cookie_same_site == SameSite::None && !cookie_secure {
+        log::warn!("PAL_COOKIE_SAMESITE=None without PAL_COOKIE_SECURE: browsers will drop the session cookie");
+    }
+    let cookie_name = env::var("PAL_COOKIE_NAME").ok().filter(|s| !s.trim().is_empty()).unwrap_or_else(|| "palantir_session".to_string());
+    let cookie_domain = env::var("PAL_COOKIE_DOMAIN").ok().filter(|s| !s.trim().is_empty());
+    let analysis = {
+        let d = upload_processing::AnalysisConfig::default();
+        upload_processing::AnalysisConfig {
+            burst_window_seconds: env::var("BURST_WINDOW_SECONDS").ok().and_then(|s| s.parse().ok()).unwrap_or(d.burst_window_seconds),
+            final_window_minutes: env::var("FINAL_WINDOW_MINUTES").ok().and_then(|s| s.parse().ok()).unwrap_or(d.final_window_minutes),
+            restart_merge_seconds: env::var("RESTART_MERGE_SECONDS").ok().and_then(|s| s.parse().ok()).unwrap_or(d.restart_merge_seconds),
+            risk_weights: env::var("RISK_WEIGHTS").map(|s| risk::RiskWeights::parse(&s)).unwrap_or(d.risk_weights),
+        }
+    };
 
     fs::create_dir_all(&upload_dir_abs).ok();
     fs::create_dir_all(&processed_dir).ok();
 
-    let tera = Tera::new("templates/**/*").expect("load templates");
+    let mut tera = Tera::new("templates/**/*").expect("load templates");
+    // templates prefix every app url with {{ base() }}
+    {
+        let base_path = base_path.clone();
+        tera.register_function("base", move |_: &std::collections::HashMap<String, tera::Value>| {
+            Ok(tera::Value::String(base_path.clone()))
+        });
+    }
 
     let pool = db::init_db(&db_path);
     let data = web::Data::new(AppState {
         pool,
         upload_dir: upload_dir_abs.clone(),
+        min_free_disk_bytes: min_free_disk_mb * 1024 * 1024,
+        max_upload_bytes,
+        upload_rate: std::sync::Arc::new(upload_rate),
+        upload_session_rate: std::sync::Arc::new(upload_session_rate),
         processed_dir: processed_dir.clone(),
         tera,
+        hide_min_duration_minutes,
+        hide_min_net_events,
+        live: Default::default(),
+        worker: Default::default(),
+        analysis,
+        session_ttl_secs,
+        session_idle_secs,
+        base_path: base_path.clone(),
+        hide_procs,
+        min_stats_submissions,
+        verify_identity_moodle,
+        auth_mode,
+        audit_admins,
+        net_minutes: Default::default(),
+        processed_tx: tokio::sync::broadcast::channel(PROCESSED_EVENTS_BUFFER).0,
+        webhook,
     });
 
-    // background worker without tokio dependencies
-    {
+    // background workers without tokio dependencies; they only sleep once the queue is empty
+    for _ in 0..workers {
         let data_clone = data.clone();
         std::thread::spawn(move || {
             loop {
-                if let Err(e) = upload_processing::process_pending(&data_clone) {
-                    eprintln!("processor error: {e}");
+                match upload_processing::process_pending(&data_clone) {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => {
+                        eprintln!("processor error: {e}");
+                        data_clone.worker.record_error(&e);
+                    }
                 }
                 std::thread::sleep(std::time::Duration::from_secs(2));
             }
         });
     }
 
+    // retention pass right after startup, then every PAL_RETENTION_INTERVAL_SECS
+    if retention.enabled() {
+        log::info!(
+            "retention: keeping uploads for {} days (files: {}, rows: {})",
+            retention.days, retention.delete_files, retention.delete_rows
+        );
+        let data_clone = data.clone();
+        std::thread::spawn(move || loop {
+            if let Err(e) = retention::prune_expired(&data_clone, &retention) {
+                eprintln!("retention error: {e}");
+                data_clone.worker.record_error(&format!("retention: {e}"));
+            }
+            std::thread::sleep(std::time::Duration::from_secs(retention.interval_secs));
+        });
+    }
+
     println!("Rrunning server...");
 
     HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
+            // gzip/br for the timeline json and pages; streamed bodies are encoded chunk by chunk,
+            // and responses that already set Content-Encoding (zip downloads) are left alone
+            .wrap(Compress::default())
             .wrap(SessionMiddleware::builder(CookieSessionStore::default(), COOKIE_KEY.clone())
-                .cookie_secure(false)
+                .cookie_secure(cookie_secure)
+                .cookie_same_site(cookie_same_site)
+                .cookie_name(cookie_name.clone())
+                .cookie_domain(cookie_domain.clone())
                 .cookie_content_security(CookieContentSecurity::Private)
                 .build())
-            .service(actix_files::Files::new("/favicon.ico", "./static/favicon.png"))
-            .service(actix_files::Files::new("/static", "./static").show_files_listing())
-            // .service(actix_files::Files::new("/uploads", "./processed_uploads"))
-            .service(auth::login_page)
-            .service(auth::do_login)
-            .service(auth::logout)
-            .service(admin::dashboard::dashboard)
-            .service(admin::assignment::page::assignment_page)
-            .service(admin::submission::page::submission_page)
-            .service(admin::subscribe::subscribe)
-            .service(admin::unsubscribe::unsubscribe)
-            .service(api::upload_logs)
-            .service(files::get_upload)
-            .service(admin::submission::get_timeline_network::net_timeline_json)
-            .service(admin::submission::get_timeline_network::net_timeline_fragment)
-            .service(admin::submission::get_timeline_process::proc_timeline_json)
-            .service(admin::submission::get_timeline_process::proc_timeline_fragment)
-            .service(admin::submission::get_artifacts::submission_artifacts_frag)
-            .service(admin::assignment::get_stats_activity::stats_activity)
-            .service(admin::assignment::get_stats_status::stats_status)
-            .service(admin::assignment::get_stats_duration::stats_duration)
-            .service(admin::assignment::get_stats_browser::stats_browser)
-            .service(admin::assignment::get_stats_domains::stats_domains)
-            .service(admin::assignment::get_stats_outliers::stats_outliers)
-            .service(admin::assignment::get_stats_shared_lan::stats_shared_lan)
-            .service(admin::assignment::get_cards::assignment_cards)
-            .service(admin::assignment::get_cards::assignment_table_page)
-            .service(admin::assignment::get_cards::assignment_table_rows)
-            .service(admin::dashboard::admin_root)            
+            // everything lives under BASE_PATH ("" when served at the root)
+            .service(web::scope(&base_path)
+                .service(actix_files::Files::new("/favicon.ico", "./static/favicon.png"))
+                .service(actix_files::Files::new("/static", "./static").show_files_listing())
+                // .service(actix_files::Files::new("/uploads", "./processed_uploads"))
+                .service(health::healthz)
+                .service(health::readyz)
+                .service(auth::login_page)
+                .service(auth::do_login)
+                .service(auth::logout)
+                .service(admin::dashboard::dashboard)
+                .service(admin::system::system_page)
+                .service(admin::audit::audit_page)
+                .service(admin::access::access_page)
+                .service(admin::access::grant_access)
+                .service(admin::access::revoke_access)
+                .service(admin::assignment::page::assignment_page)
+                .service(admin::submission::page::submission_page)
+                .service(admin::subscribe::subscribe)
+                .service(admin::subscribe::subscribe_bulk)
+                .service(admin::subscribe::set_profile)
+                .service(admin::unsubscribe::unsubscribe)
+                .service(api::upload_logs)
+                .service(api::upload_init)
+                .service(api::upload_status)
+                .service(api::upload_chunk)
+                .service(api::upload_complete)
+                .service(api::live_ingest)
+                .service(admin::live::live_page)
+                .service(admin::live::live_watch)
+                .service(admin::live::issue_tokens)
+                .service(admin::live::revoke_token)
+                .service(files::get_upload)
+                .service(admin::submission::get_timeline_network::net_timeline_json)
+                .service(admin::submission::get_timeline_network::net_timeline_fragment)
+                .service(admin::submission::get_timeline_process::proc_timeline_json)
+                .service(admin::submission::get_timeline_process::proc_timeline_fragment)
+                .service(admin::submission::get_timeline_image::timeline_image)
+                .service(admin::submission::get_artifacts::submission_artifacts_frag)
+                .service(admin::submission::reprocess::reprocess_submission)
+                .service(admin::submission::get_log::download_log)
+                .service(admin::assignment::get_stats_activity::stats_activity)
+                .service(admin::assignment::get_stats_status::stats_status)
+                .service(admin::assignment::get_stats_status::status_summary)
+                .service(admin::assignment::get_stats_duration::stats_duration)
+                .service(admin::assignment::get_stats_browser::stats_browser)
+                .service(admin::assignment::get_stats_domains::stats_domains)
+                .service(admin::assignment::get_stats_outliers::stats_outliers)
+                .service(admin::assignment::get_stats_shared_lan::stats_shared_lan)
+                .service(admin::assignment::get_stats_shared_device::stats_shared_device)
+                .service(admin::assignment::get_stats_shared_files::stats_shared_files)
+                .service(admin::assignment::get_stats_capture::stats_capture)
+                .service(admin::assignment::get_stats_unexpected::stats_unexpected)
+                .service(admin::assignment::get_stats_hidden::stats_hidden)
+                .service(admin::assignment::get_events::assignment_events)
+                .service(admin::assignment::get_findings_ndjson::findings_ndjson)
+                .service(admin::assignment::get_export_json::export_json)
+                .service(admin::assignment::get_correlation::correlation_json)
+                .service(admin::assignment::provided_domains::set_provided_domains)
+                .service(admin::assignment::reprocess::reprocess_assignment)
+                .service(admin::assignment::reprocess::reprocess_status)
+                .service(admin::assignment::get_cards::assignment_cards)
+                .service(admin::assignment::get_cards::assignment_table_page)
+                .service(admin::assignment::get_cards::assignment_table_rows)
+                .service(admin::assignment::get_cards::assignment_export_csv)
+                .service(admin::dashboard::admin_root)
+            )
         })
     .bind((host, port))?
     .run()
@@ -0,0 +1,55 @@
+//! Just enough of the Moodle web service API to check who an upload claims to come from.
+
+/// The account behind a Moodle token, from core_webservice_get_site_info
+#[derive(Debug, Clone)]
+pub struct MoodleIdentity {
+    pub username: String,
+    pub full_name: String,
+}
+
+impl MoodleIdentity {
+    /// The client sends either the profile name or the login as `student_name` (see the
+    /// desktop's STUDENT_NAME_SOURCE); a login name, when sent, must be this account's.
+    pub fn matches(&self, student_name: &str, student_username: Option<&str>) -> bool {
+        let eq = |a: &str, b: &str| !a.trim().is_empty() && a.trim().eq_ignore_ascii_case(b.trim());
+        let name_ok = eq(student_name, &self.full_name) || eq(student_name, &self.username);
+        let login_ok = student_username.is_none_or(|u| eq(u, &self.username));
+        name_ok && login_ok
+    }
+}
+
+/// Look up the account a web service token belongs to
+pub async fn site_info(base: &str, token: &str) -> Result<MoodleIdentity, String> {
+    let url = format!("{}/webservice/rest/server.php", base.trim_end_matches('/'));
+    let form = [
+        ("wstoken", token),
+        ("wsfunction", "core_webservice_get_site_info"),
+        ("moodlewsrestformat", "json"),
+    ];
+    let resp = reqwest::Client::new()
+        .post(&url)
+        .timeout(std::time::Duration::from_secs(10))
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let v: serde_json::Value = resp.json().await.map_err(|e| format!("unexpected site info response: {e}"))?;
+
+    if let Some(ex) = v.get("exception") {
+        let msg = v.get("message").and_then(|m| m.as_str()).unwrap_or("error");
+        return Err(format!("{ex}: {msg}"));
+    }
+    let username = v.get("username").and_then(|x| x.as_str()).unwrap_or("").to_string();
+    if username.is_empty() {
+        return Err("site info has no username".into());
+    }
+    let full_name = match v.get("fullname").and_then(|x| x.as_str()) {
+        Some(n) => n.to_string(),
+        None => {
+            let first = v.get("firstname").and_then(|x| x.as_str()).unwrap_or("");
+            let last = v.get("lastname").and_then(|x| x.as_str()).unwrap_or("");
+            format!("{first} {last}")
+        }
+    };
+    Ok(MoodleIdentity { username, full_name: full_name.trim().to_string() })
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::Instant,
+};
+
+use actix_web::HttpRequest;
+
+/// Above this many tracked clients, buckets that have refilled completely are dropped, then
+/// (if that is not enough) the least recently seen half
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Per-IP token bucket for the unauthenticated upload endpoints. Each client may make `burst`
+/// requests at once, refilled at `per_minute`; in memory only, so a restart forgets everyone.
+/// Off unless `per_minute` is set: a lab behind one NAT address shares a single bucket.
+pub struct RateLimiter {
+    per_minute: f64,
+    burst: f64,
+    // X-Forwarded-For is only believed when the connection comes from one of these
+    trusted_proxies: Vec<IpAddr>,
+    max_clients: usize,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: f64, burst: f64, trusted_proxies: Vec<IpAddr>) -> Self {
+        RateLimiter {
+            per_minute: per_minute.max(0.0),
+            burst: burst.max(1.0),
+            trusted_proxies,
+            max_clients: MAX_TRACKED_CLIENTS,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `{prefix}_PER_MIN` (0 = off, the default) and `{prefix}_BURST`, plus PAL_TRUSTED_PROXIES
+    pub fn from_env(prefix: &str, default_burst: f64) -> Self {
+        let var = |k: &str| std::env::var(k).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let trusted_proxies = var("PAL_TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|s| {
+                let s = s.trim();
+                if s.is_empty() { return None; }
+                s.parse().map_err(|_| log::warn!("PAL_TRUSTED_PROXIES: '{s}' is not an IP address, ignored")).ok()
+            })
+            .collect();
+        RateLimiter::new(
+            var(&format!("{prefix}_PER_MIN")).and_then(|s| s.parse().ok()).unwrap_or(0.0),
+            var(&format!("{prefix}_BURST")).and_then(|s| s.parse().ok()).unwrap_or(default_burst),
+            trusted_proxies,
+        )
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.per_minute > 0.0
+    }
+
+    /// Take one token for `ip`; `Err(seconds)` until the next one is available
+    pub fn check(&self, ip: IpAddr) -> Result<(), u64> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let per_sec = self.per_minute / 60.0;
+        let refilled = |b: &Bucket| (b.tokens + now.duration_since(b.updated).as_secs_f64() * per_sec).min(self.burst);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= self.max_clients && !buckets.contains_key(&ip) {
+            // a full bucket is the same as a new one
+            buckets.retain(|_, b| refilled(b) < self.burst);
+            if buckets.len() >= self.max_clients {
+                let mut seen: Vec<Instant> = buckets.values().map(|b| b.updated).collect();
+                seen.sort_unstable();
+                let cutoff = seen[seen.len() / 2];
+                buckets.retain(|_, b| b.updated > cutoff);
+            }
+        }
+
+        let b = buckets.entry(ip).or_insert(Bucket { tokens: self.burst, updated: now });
+        b.tokens = refilled(b);
+        b.updated = now;
+        if b.tokens >= 1.0 {
+            b.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - b.tokens) / per_sec).ceil() as u64)
+        }
+    }
+
+    /// The address the request came from: the peer, or behind a trusted proxy the last
+    /// X-Forwarded-For entry that isn't one of our proxies (earlier ones are client-supplied)
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+        if !self.trusted_proxies.contains(&peer) {
+            return Some(peer);
+        }
+        let forwarded: Vec<IpAddr> = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|s| s.trim().parse().ok())
+            .collect();
+        let client = forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.trusted_proxies.contains(ip))
+            .or(forwarded.first())
+            .copied();
+        Some(client.unwrap_or(peer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn burst_then_refused_per_client() {
+        let rl = RateLimiter::new(6.0, 2.0, vec![]);
+        assert!(rl.check(ip("10.0.0.1")).is_ok());
+        assert!(rl.check(ip("10.0.0.1")).is_ok());
+        // one token every 10s
+        assert_eq!(rl.check(ip("10.0.0.1")), Err(10));
+        assert!(rl.check(ip("10.0.0.2")).is_ok());
+    }
+
+    #[test]
+    fn off_by_default() {
+        let rl = RateLimiter::new(0.0, 1.0, vec![]);
+        for _ in 0..100 {
+            assert!(rl.check(ip("10.0.0.1")).is_ok());
+        }
+    }
+
+    #[test]
+    fn tracked_clients_stay_bounded() {
+        let mut rl = RateLimiter::new(1.0, 1.0, vec![]);
+        rl.max_clients = 100;
+        // every client is empty after its request, so none of them count as refilled
+        for i in 0..1000u32 {
+            let _ = rl.check(IpAddr::from(i.to_be_bytes()));
+            assert!(rl.buckets.lock().unwrap().len() <= 100);
+        }
+    }
+
+    #[test]
+    fn forwarded_for_only_from_trusted_proxies() {
+        let rl = RateLimiter::new(6.0, 1.0, vec![ip("127.0.0.1")]);
+        let direct = TestRequest::default()
+            .peer_addr("192.0.2.7:4000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "203.0.113.9"))
+            .to_http_request();
+        assert_eq!(rl.client_ip(&direct), Some(ip("192.0.2.7")));
+
+        // the left entry is whatever the client claimed, the right one is what our proxy saw
+        let proxied = TestRequest::default()
+            .peer_addr("127.0.0.1:4000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.9"))
+            .to_http_request();
+        assert_eq!(rl.client_ip(&proxied), Some(ip("203.0.113.9")));
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fs, path::Path};
+
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::{db, AppState};
+
+/// How long analyzed uploads are kept (PAL_RETENTION_*). Nothing is pruned unless `days` > 0.
+#[derive(Clone, Debug)]
+pub struct RetentionConfig {
+    pub days: i64,
+    // remove the processed zips of expired submissions
+    pub delete_files: bool,
+    // also remove their submissions/logs/findings rows; off by default so findings stay on record
+    pub delete_rows: bool,
+    pub interval_secs: u64,
+}
+
+impl RetentionConfig {
+    pub fn from_env() -> Self {
+        let var = |k: &str| std::env::var(k).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        RetentionConfig {
+            days: var("PAL_RETENTION_DAYS").and_then(|s| s.parse().ok()).unwrap_or(0),
+            delete_files: var("PAL_RETENTION_DELETE_FILES").map(|v| v != "false").unwrap_or(true),
+            delete_rows: var("PAL_RETENTION_DELETE_ROWS").map(|v| v == "true").unwrap_or(false),
+            interval_secs: var("PAL_RETENTION_INTERVAL_SECS").and_then(|s| s.parse().ok()).unwrap_or(3600).max(60),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.days > 0 && (self.delete_files || self.delete_rows)
+    }
+}
+
+/// One pass of the retention policy: delete the processed zips and (if enabled) the rows of
+/// analyzed submissions created more than `days` ago. Submissions still queued are skipped.
+pub fn prune_expired(data: &AppState, cfg: &RetentionConfig) -> Result<(), String> {
+    let cutoff = (OffsetDateTime::now_utc() - Duration::days(cfg.days))
+        .format(&Rfc3339)
+        .map_err(|e| e.to_string())?;
+
+    if cfg.delete_files {
+        let mut removed = 0usize;
+        let mut freed = 0u64;
+        for (log_id, fs_path) in db::list_expired_logs(&data.pool, &cutoff)? {
+            let Some(name) = Path::new(&fs_path).file_name() else { continue; };
+            let path = data.processed_dir.join(name);
+            // a zip that is already gone (removed by hand, or before logs were marked) counts as pruned
+            if let Ok(meta) = fs::metadata(&path) {
+                if let Err(e) = fs::remove_file(&path) {
+                    log::warn!("retention: remove {}: {e}", path.display());
+                    continue;
+                }
+                removed += 1;
+                freed += meta.len();
+            }
+            // keeps "Reanalyze" from dropping findings it can no longer recompute
+            db::mark_log_pruned(&data.pool, &log_id)?;
+        }
+        if removed > 0 {
+            log::info!("retention: removed {removed} processed zips older than {cutoff} ({} MB)", freed / (1024 * 1024));
+        }
+    }
+
+    if cfg.delete_rows {
+        let n = db::delete_submissions_before(&data.pool, &cutoff)?;
+        if n > 0 {
+            log::info!("retention: deleted {n} submissions (with their logs and findings) older than {cutoff}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> RetentionConfig {
+        RetentionConfig { days: 30, delete_files: true, delete_rows: false, interval_secs: 3600 }
+    }
+
+    /// A processed submission from 2020 with its zip in processed_dir and one finding
+    fn old_submission(data: &AppState, id: &str) {
+        let conn = data.pool.get().unwrap();
+        conn.execute(
+            "INSERT INTO submissions (id, submission_id, student_name, created_at, status)
+             VALUES (?1, 'a1', ?1, '2020-01-01T00:00:00Z', 'processed')",
+            [id],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO findings (id, submission_ref, kind, key, value, created_at)
+             VALUES (?1 || '-f', ?1, 'metric', 'duration_minutes', '90', '2020-01-01T00:00:00Z')",
+            [id],
+        ).unwrap();
+        fs::create_dir_all(&data.processed_dir).unwrap();
+        let path = data.processed_dir.join(format!("{id}.zip"));
+        fs::write(&path, b"zip").unwrap();
+        db::add_log_artifact(&data.pool, id, path.to_str().unwrap(), "00", 3, true).unwrap();
+    }
+
+    fn findings(data: &AppState, id: &str) -> i64 {
+        let conn = data.pool.get().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM findings WHERE submission_ref = ?1", [id], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn pruned_submissions_keep_their_findings_on_reanalyze() {
+        let data = crate::test_state("");
+        old_submission(&data, "s1");
+        prune_expired(&data, &cfg()).unwrap();
+        assert!(!data.processed_dir.join("s1.zip").exists());
+
+        assert_eq!(db::requeue_submission(&data.pool, "s1").unwrap(), db::Requeue::Pruned);
+        assert_eq!(db::requeue_assignment(&data.pool, "a1").unwrap(), 0);
+        assert_eq!(findings(&data, "s1"), 1);
+
+        // a second pass finds nothing left to prune
+        assert!(db::list_expired_logs(&data.pool, "2030-01-01T00:00:00Z").unwrap().is_empty());
+    }
+
+    #[test]
+    fn submissions_with_their_zip_are_requeued() {
+        let data = crate::test_state("");
+        old_submission(&data, "s1");
+        old_submission(&data, "s2");
+
+        assert_eq!(db::requeue_submission(&data.pool, "s1").unwrap(), db::Requeue::Queued);
+        assert_eq!(db::requeue_submission(&data.pool, "s1").unwrap(), db::Requeue::Busy);
+        assert_eq!(db::requeue_assignment(&data.pool, "a1").unwrap(), 1);
+        assert_eq!(findings(&data, "s2"), 0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use crate::routes::admin::util::consts::*;
+
+/// One input of the risk score: the finding it reads and the value at which it counts in full.
+/// Boolean-ish findings (`"true"`, `"3/4"`) count in full when present and truthy.
+struct RiskFactor {
+    key: &'static str,
+    saturates_at: Option<f64>,
+    default_weight: f64,
+}
+
+const RISK_FACTORS: &[RiskFactor] = &[
+    RiskFactor { key: FK_AI_RATIO_PERCENT, saturates_at: Some(100.0), default_weight: 35.0 },
+    RiskFactor { key: FK_SHELL_INVOCATIONS, saturates_at: Some(10.0), default_weight: 10.0 },
+    RiskFactor { key: FK_EXTERNAL_DOWNLOAD_TOOL_COUNT, saturates_at: Some(5.0), default_weight: 10.0 },
+    RiskFactor { key: FK_REMOTE_COLLAB_TOOL_SEEN, saturates_at: None, default_weight: 15.0 },
+    RiskFactor { key: FK_SSH_ACTIVITY, saturates_at: None, default_weight: 10.0 },
+    RiskFactor { key: FK_LOOPBACK_DOMINATED, saturates_at: None, default_weight: 10.0 },
+    RiskFactor { key: FK_FINAL5_NET_EVENTS, saturates_at: Some(50.0), default_weight: 10.0 },
+];
+
+/// Weight per risk factor key. Only the ratios matter: the score is normalized by their sum.
+#[derive(Clone, Debug)]
+pub struct RiskWeights(HashMap<&'static str, f64>);
+
+impl Default for RiskWeights {
+    fn default() -> Self {
+        RiskWeights(RISK_FACTORS.iter().map(|f| (f.key, f.default_weight)).collect())
+    }
+}
+
+impl RiskWeights {
+    /// Defaults overridden by `key=weight` pairs, comma separated, e.g.
+    /// `ai_ratio_percent=50,ssh_activity=0`. Unknown keys and bad numbers are logged and skipped.
+    pub fn parse(spec: &str) -> Self {
+        let mut w = RiskWeights::default();
+        for pair in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let Some((k, v)) = pair.split_once('=') else {
+                log::warn!("RISK_WEIGHTS: ignoring '{pair}', expected key=weight");
+                continue;
+            };
+            let Some(factor) = RISK_FACTORS.iter().find(|f| f.key == k.trim()) else {
+                log::warn!("RISK_WEIGHTS: unknown key '{}'", k.trim());
+                continue;
+            };
+            match v.trim().parse::<f64>() {
+                Ok(n) if n >= 0.0 => { w.0.insert(factor.key, n); }
+                _ => log::warn!("RISK_WEIGHTS: bad weight '{}' for {}", v.trim(), factor.key),
+            }
+        }
+        w
+    }
+
+    fn get(&self, key: &str) -> f64 {
+        self.0.get(key).copied().unwrap_or(0.0)
+    }
+}
+
+/// Overall risk of a submission, 0-100, with the points each factor contributed
+#[derive(Debug)]
+pub struct RiskScore {
+    pub score: i64,
+    pub breakdown: Vec<(&'static str, i64)>,
+}
+
+/// Weighted sum of the risk factors found in `findings` (key, value pairs; the first value
+/// of a key counts), scaled so that every factor at its saturation point gives 100.
+pub fn compute_risk_score<'a>(
+    findings: impl IntoIterator<Item = (&'a str, &'a str)>,
+    weights: &RiskWeights,
+) -> RiskScore {
+    let mut values: HashMap<&str, &str> = HashMap::new();
+    for (k, v) in findings {
+        values.entry(k).or_insert(v);
+    }
+
+    let total_weight: f64 = RISK_FACTORS.iter().map(|f| weights.get(f.key)).sum();
+    if total_weight <= 0.0 {
+        return RiskScore { score: 0, breakdown: Vec::new() };
+    }
+
+    let mut sum = 0.0;
+    let mut breakdown = Vec::new();
+    for f in RISK_FACTORS {
+        let Some(v) = values.get(f.key) else { continue; };
+        let level = match f.saturates_at {
+            Some(max) => (v.trim().parse::<f64>().unwrap_or(0.0) / max).clamp(0.0, 1.0),
+            None if matches!(v.trim().to_ascii_lowercase().as_str(), "" | "false" | "no" | "0") => 0.0,
+            None => 1.0,
+        };
+        let points = weights.get(f.key) * level * 100.0 / total_weight;
+        if points > 0.0 {
+            sum += points;
+            breakdown.push((f.key, points.round() as i64));
+        }
+    }
+    breakdown.sort_by_key(|b| std::cmp::Reverse(b.1));
+
+    RiskScore { score: (sum.round() as i64).clamp(0, 100), breakdown }
+}
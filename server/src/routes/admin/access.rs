@@ -0,0 +1,89 @@
+use actix_session::Session;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use serde::Deserialize;
+use tera::Context;
+
+use crate::{db, routes::{admin::{audit::audit, subscribe::parse_id_list, util::access::is_admin}, auth::Authorized}, AppState};
+
+#[derive(Deserialize)]
+pub struct GrantForm {
+    pub prof: String,
+    // pasted list, same format as the bulk subscribe box
+    pub assignment_ids: String,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeForm {
+    pub prof: String,
+    pub assignment_id: String,
+}
+
+fn back_to_page(data: &AppState) -> HttpResponse {
+    HttpResponse::Found().append_header(("Location", format!("{}/admin/access", data.base_path))).finish()
+}
+
+/// Which professor may open which assignment; only PAL_AUDIT_ADMINS can see and change it
+#[get("/admin/access")]
+pub async fn access_page(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if !is_admin(&session, &data) {
+        return HttpResponse::Forbidden().body("access grants are only managed by PAL_AUDIT_ADMINS");
+    }
+    let grants = match db::list_access(&data.pool) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let mut ctx = Context::new();
+    ctx.insert("grants", &grants);
+    match data.tera.render("access/page.html", &ctx) {
+        Ok(html) => HttpResponse::Ok().body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[post("/admin/access/grant")]
+pub async fn grant_access(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    form: web::Form<GrantForm>,
+) -> impl Responder {
+    if !is_admin(&session, &data) {
+        return HttpResponse::Forbidden().body("access grants are only managed by PAL_AUDIT_ADMINS");
+    }
+    let admin = session.get::<String>("prof").ok().flatten().unwrap_or_default();
+    let prof = form.prof.trim();
+    if prof.is_empty() {
+        return HttpResponse::BadRequest().body("professor login is required");
+    }
+    for aid in parse_id_list(&form.assignment_ids) {
+        match db::grant_access(&data.pool, prof, &aid, &admin) {
+            Ok(true) => audit(&data, &session, "grant_access", &format!("{prof}:{aid}")),
+            Ok(false) => {}
+            Err(e) => return HttpResponse::InternalServerError().body(e),
+        }
+    }
+    back_to_page(&data)
+}
+
+#[post("/admin/access/revoke")]
+pub async fn revoke_access(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    form: web::Form<RevokeForm>,
+) -> impl Responder {
+    if !is_admin(&session, &data) {
+        return HttpResponse::Forbidden().body("access grants are only managed by PAL_AUDIT_ADMINS");
+    }
+    let (prof, aid) = (form.prof.trim(), form.assignment_id.trim());
+    if let Err(e) = db::revoke_access(&data.pool, prof, aid) {
+        return HttpResponse::InternalServerError().body(e);
+    }
+    audit(&data, &session, "revoke_access", &format!("{prof}:{aid}"));
+    back_to_page(&data)
+}
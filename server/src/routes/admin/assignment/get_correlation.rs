@@ -0,0 +1,179 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use actix_session::Session;
+use actix_web::{get, web, HttpResponse, Responder};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{db, routes::{admin::{submission::get_timeline_network::net_points, util::{access::deny_assignment, localtime::minute_label}}, auth::Authorized}, AppState};
+
+// students in the correlation matrix unless ?top= says otherwise
+const DEFAULT_TOP: usize = 10;
+const MAX_TOP: usize = 50;
+// the cache is dropped wholesale beyond this many submissions; rebuilding is only slow, never wrong
+const CACHE_MAX_ENTRIES: usize = 5000;
+
+type MinuteCounts = Arc<Vec<(i64, i32)>>;
+
+/// Net events per UTC minute of each submission's log, so the correlation view doesn't
+/// unzip every log of the assignment on each request. Keyed by submission id and checked
+/// against the current log file name, so a re-upload is never served stale counts.
+#[derive(Default)]
+pub struct NetMinuteCache {
+    entries: Mutex<HashMap<String, (String, MinuteCounts)>>,
+}
+
+impl NetMinuteCache {
+    fn get(&self, id: &str, log_name: &str) -> Option<MinuteCounts> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(id).filter(|(name, _)| name == log_name).map(|(_, counts)| counts.clone())
+    }
+
+    fn put(&self, id: &str, log_name: &str, counts: MinuteCounts) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= CACHE_MAX_ENTRIES {
+            entries.clear();
+        }
+        entries.insert(id.to_string(), (log_name.to_string(), counts));
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CorrelationQuery {
+    #[serde(default)]
+    top: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct StudentSeries {
+    id: String,
+    student_name: String,
+    total: i64,
+}
+
+#[derive(Serialize)]
+struct Correlation {
+    // local "YYYY-MM-DD HH:MM" labels, one per minute from the first to the last event
+    minutes: Vec<String>,
+    students: Vec<StudentSeries>,
+    // matrix[minute][student], same order as `minutes` and `students`
+    matrix: Vec<Vec<i32>>,
+    // indices into `students` of the most active ones, and their pairwise Pearson r
+    // (None when a series is constant)
+    top: Vec<usize>,
+    pearson: Vec<Vec<Option<f64>>>,
+}
+
+fn minute_counts(data: &AppState, id: &str) -> Result<MinuteCounts, String> {
+    let conn = data.pool.get().map_err(|e| e.to_string())?;
+    let log_name: Option<String> = conn.query_row(
+        "SELECT fs_path FROM logs WHERE submission_ref = ?1 AND archived = 0 ORDER BY rowid DESC LIMIT 1",
+        params![id],
+        |r| r.get(0),
+    ).optional().map_err(|e| e.to_string())?;
+    let Some(log_name) = log_name else { return Ok(Arc::new(Vec::new())); };
+
+    if let Some(hit) = data.net_minutes.get(id, &log_name) {
+        return Ok(hit);
+    }
+    let counts: MinuteCounts = Arc::new(net_points(data, id)?.into_iter().map(|p| (p.minute, p.total)).collect());
+    data.net_minutes.put(id, &log_name, counts.clone());
+    Ok(counts)
+}
+
+fn pearson(x: &[i32], y: &[i32]) -> Option<f64> {
+    let n = x.len() as f64;
+    if n < 2.0 { return None; }
+    let mx = x.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let my = y.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (&a, &b) in x.iter().zip(y) {
+        let (dx, dy) = (a as f64 - mx, b as f64 - my);
+        sxy += dx * dy;
+        sxx += dx * dx;
+        syy += dy * dy;
+    }
+    if sxx == 0.0 || syy == 0.0 { return None; }
+    Some(sxy / (sxx * syy).sqrt())
+}
+
+fn correlation(data: &AppState, aid: &str, top_n: usize) -> Result<Correlation, String> {
+    let subs: Vec<_> = db::list_submissions_by_assignment(&data.pool, aid)?
+        .into_iter()
+        .filter(|s| !s.is_test && s.status == "processed")
+        .collect();
+
+    let mut series: Vec<(StudentSeries, HashMap<i64, i32>)> = Vec::with_capacity(subs.len());
+    let mut all_minutes: BTreeSet<i64> = BTreeSet::new();
+    for s in subs {
+        let counts = match minute_counts(data, &s.id) {
+            Ok(c) => c,
+            Err(e) => {
+                log::warn!("correlation: skipping {}: {e}", s.id);
+                continue;
+            }
+        };
+        all_minutes.extend(counts.iter().map(|(m, _)| *m));
+        let total = counts.iter().map(|(_, n)| *n as i64).sum();
+        series.push((StudentSeries { id: s.id, student_name: s.student_name, total }, counts.iter().copied().collect()));
+    }
+
+    // a contiguous axis, so quiet minutes count as zeros in the correlation
+    let axis: Vec<i64> = match (all_minutes.first(), all_minutes.last()) {
+        (Some(&a), Some(&b)) => (a..=b).collect(),
+        _ => Vec::new(),
+    };
+    let columns: Vec<Vec<i32>> = series
+        .iter()
+        .map(|(_, by_min)| axis.iter().map(|m| by_min.get(m).copied().unwrap_or(0)).collect())
+        .collect();
+    let matrix: Vec<Vec<i32>> = (0..axis.len()).map(|i| columns.iter().map(|c| c[i]).collect()).collect();
+
+    let mut top: Vec<usize> = (0..series.len()).collect();
+    top.sort_by(|&a, &b| series[b].0.total.cmp(&series[a].0.total));
+    top.truncate(top_n);
+    let pearson: Vec<Vec<Option<f64>>> = top
+        .iter()
+        .map(|&a| top.iter().map(|&b| pearson(&columns[a], &columns[b])).collect())
+        .collect();
+
+    let minutes = axis
+        .iter()
+        .map(|m| OffsetDateTime::from_unix_timestamp(m * 60).map(minute_label).unwrap_or_default())
+        .collect();
+
+    Ok(Correlation {
+        minutes,
+        students: series.into_iter().map(|(s, _)| s).collect(),
+        matrix,
+        top,
+        pearson,
+    })
+}
+
+/// Net activity of every student of the assignment on one shared minute axis, plus the
+/// pairwise correlation of the most active ones, to spot synchronized bursts.
+#[get("/admin/assignment/{aid}/correlation.json")]
+pub async fn correlation_json(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    q: web::Query<CorrelationQuery>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let top_n = q.top.unwrap_or(DEFAULT_TOP).clamp(2, MAX_TOP);
+
+    // unzips every log that isn't cached yet
+    let data = data.clone();
+    match web::block(move || correlation(&data, &aid, top_n)).await {
+        Ok(Ok(c)) => HttpResponse::Ok().json(c),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("worker error: {e}")),
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use actix_session::Session;
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{routes::auth::Authorized, AppState};
+use crate::routes::admin::util::access::deny_assignment;
+
+/// Comment line sent when nothing happened for this long, so proxies don't close an idle feed
+const KEEPALIVE: Duration = Duration::from_secs(25);
+
+/// Server-sent events for an assignment page: a `processed` event (JSON `ProcessedEvent`)
+/// whenever one of its submissions finishes analysis, so the page can re-fetch its table.
+#[get("/admin/assignment/{aid}/events")]
+pub async fn assignment_events(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+
+    let rx = data.processed_tx.subscribe();
+    let stream = futures_util::stream::unfold(rx, move |mut rx| {
+        let aid = aid.clone();
+        async move {
+            loop {
+                let chunk = match tokio::time::timeout(KEEPALIVE, rx.recv()).await {
+                    Err(_) => ": keepalive\n\n".to_string(),
+                    Ok(Ok(ev)) if ev.assignment_id == aid => {
+                        let json = serde_json::to_string(&ev).unwrap_or_default();
+                        format!("event: processed\ndata: {json}\n\n")
+                    }
+                    Ok(Ok(_)) => continue,
+                    // fell behind; the next event triggers a full re-fetch anyway
+                    Ok(Err(RecvError::Lagged(_))) => continue,
+                    Ok(Err(RecvError::Closed)) => return None,
+                };
+                return Some((Ok::<_, actix_web::Error>(web::Bytes::from(chunk)), rx));
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // keeps the Compress middleware (and nginx) from buffering the stream
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream)
+}
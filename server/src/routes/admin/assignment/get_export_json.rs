@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use actix_session::Session;
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+use futures_util::stream;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{db, routes::auth::Authorized, AppState};
+use crate::routes::admin::{assignment::get_cards::filename_safe, audit::audit, util::access::deny_assignment};
+
+// submissions whose findings are fetched per round trip
+const BATCH: usize = 200;
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    // comma separated finding keys to keep; all keys when absent
+    pub keys: Option<String>,
+}
+
+/// One object per submission with its findings flattened to key -> value. A key that
+/// occurs more than once (top_domain, ai_domain, ...) maps to an array of its values.
+#[get("/admin/assignment/{aid}/export.json")]
+pub async fn export_json(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    q: web::Query<ExportQuery>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    audit(&data, &session, "export_json", &aid);
+    let subs = match db::list_submissions_by_assignment(&data.pool, &aid) {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let keys: Option<HashSet<String>> = q.keys.as_deref().map(|k| {
+        k.split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(str::to_string)
+            .collect()
+    });
+    let filename = format!("export-{}.json", filename_safe(&aid));
+    let pool = data.pool.clone();
+
+    // state = index of the next submission; None once the closing bracket was sent
+    let body = stream::unfold(Some(0usize), move |at| {
+        let item = at.map(|at| {
+            let end = (at + BATCH).min(subs.len());
+            let next = (end < subs.len()).then_some(end);
+            (export_chunk(&pool, &subs, at, end, keys.as_ref()), next)
+        });
+        async move {
+            match item? {
+                (Ok(buf), next) => Some((Ok::<_, actix_web::Error>(web::Bytes::from(buf)), next)),
+                (Err(e), _) => Some((Err(actix_web::error::ErrorInternalServerError(e)), None)),
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")))
+        .streaming(body)
+}
+
+/// JSON text of `subs[at..end]`, with the array's opening / closing bracket on the first / last chunk
+fn export_chunk(
+    pool: &Pool<SqliteConnectionManager>,
+    subs: &[db::SubmissionRow],
+    at: usize,
+    end: usize,
+    keys: Option<&HashSet<String>>,
+) -> Result<String, String> {
+    let chunk = &subs[at..end];
+    let ids: Vec<String> = chunk.iter().map(|s| s.id.clone()).collect();
+    let findings = db::list_findings_for_submissions(pool, &ids)?;
+
+    let mut buf = String::new();
+    if at == 0 {
+        buf.push('[');
+    }
+    for (i, s) in chunk.iter().enumerate() {
+        let mut flat = Map::new();
+        for f in findings.iter().filter(|f| f.submission_ref == s.id) {
+            if keys.is_some_and(|k| !k.contains(&f.key)) {
+                continue;
+            }
+            let v = Value::String(f.value.clone());
+            match flat.get_mut(&f.key) {
+                None => { flat.insert(f.key.clone(), v); }
+                Some(Value::Array(vs)) => vs.push(v),
+                Some(first) => *first = Value::Array(vec![first.take(), v]),
+            }
+        }
+        let obj = serde_json::json!({
+            "id": s.id,
+            "student_name": s.student_name,
+            "created_at": s.created_at,
+            "status": s.status,
+            "is_test": s.is_test,
+            "findings": flat,
+        });
+        if at + i > 0 {
+            buf.push(',');
+        }
+        buf.push_str(&obj.to_string());
+    }
+    if end == subs.len() {
+        buf.push(']');
+    }
+    Ok(buf)
+}
@@ -0,0 +1,51 @@
+use actix_session::Session;
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+use futures_util::stream;
+
+use crate::{db, routes::auth::Authorized, AppState};
+use crate::routes::admin::{assignment::get_cards::filename_safe, audit::audit, util::access::deny_assignment};
+
+// rows fetched per round trip; bounds memory regardless of assignment size
+const BATCH: usize = 500;
+
+/// Every finding of the assignment's submissions, one JSON object per line.
+#[get("/admin/assignment/{aid}/findings.ndjson")]
+pub async fn findings_ndjson(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    audit(&data, &session, "export_ndjson", &aid);
+    let filename = format!("findings-{}.ndjson", filename_safe(&aid));
+    let pool = data.pool.clone();
+
+    // state = rowid to continue after, None once the last batch was sent
+    let body = stream::unfold(Some(0i64), move |after| {
+        let pool = pool.clone();
+        let aid = aid.clone();
+        async move {
+            let after = after?;
+            match db::list_assignment_findings_after(&pool, &aid, after, BATCH) {
+                Ok(rows) if rows.is_empty() => None,
+                Ok(rows) => {
+                    let next = if rows.len() < BATCH { None } else { rows.last().map(|(id, _)| *id) };
+                    let mut buf = String::new();
+                    for (_, row) in &rows {
+                        buf.push_str(&serde_json::to_string(row).unwrap_or_default());
+                        buf.push('\n');
+                    }
+                    Some((Ok::<_, actix_web::Error>(web::Bytes::from(buf)), next))
+                }
+                Err(e) => Some((Err(actix_web::error::ErrorInternalServerError(e)), None)),
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")))
+        .streaming(body)
+}
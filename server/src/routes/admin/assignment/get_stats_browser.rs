@@ -1,31 +1,37 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, Responder};
 use rusqlite::params;
 
-use crate::{routes::auth::Authorized, AppState};
+use crate::{routes::{admin::util::{hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
 
 #[get("/admin/assignment/{aid}/stats_browser")]
 pub async fn stats_browser(
     _: Authorized,
+    session: Session,
+    req: HttpRequest,
     data: web::Data<AppState>, 
     path: web::Path<String>
 ) -> impl Responder {
     let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
     let conn = data.pool.get().unwrap();
 
     let mut total: i64 = 0;
-    conn.query_row("SELECT COUNT(*) FROM submissions WHERE submission_id = ?1", params![&aid], |r| r.get(0)).map(|n: i64| total=n).ok();
+    conn.query_row(&format!("SELECT COUNT(*) FROM submissions s WHERE s.submission_id = ?1 AND s.is_test = 0{visible}"), params![&aid], |r| r.get(0)).map(|n: i64| total=n).ok();
 
     let mut has: i64 = 0;
     // try findings key first
     conn.query_row(
-        "SELECT COUNT(*) FROM submissions s JOIN findings f ON s.id=f.submission_ref
-         WHERE s.submission_id=?1 AND f.key='had_browser' AND LOWER(f.value) IN ('1','true','yes')",
+        &format!("SELECT COUNT(*) FROM submissions s JOIN findings f ON s.id=f.submission_ref
+         WHERE s.submission_id=?1 AND s.is_test = 0{visible} AND f.key='had_browser' AND LOWER(f.value) IN ('1','true','yes')"),
         params![&aid], |r| r.get(0)).map(|n: i64| has=n).ok();
 
     let mut ai_has: i64 = 0;
     conn.query_row(
-        "SELECT COUNT(DISTINCT s.id) FROM findings f JOIN submissions s ON s.id=f.submission_ref
-         WHERE s.submission_id=?1 AND f.key='ai_domain'",
+        &format!("SELECT COUNT(DISTINCT s.id) FROM findings f JOIN submissions s ON s.id=f.submission_ref
+         WHERE s.submission_id=?1 AND s.is_test = 0{visible} AND f.key='ai_domain'"),
         params![&aid], |r| r.get(0)).map(|n: i64| ai_has=n).ok();
 
     let mut ctx = tera::Context::new();
@@ -33,6 +39,5 @@ pub async fn stats_browser(
     ctx.insert("has", &has);
     ctx.insert("ai_has", &ai_has);
     ctx.insert("total", &total);
-    let html = data.tera.render("assignment/stats_browser.html", &ctx).unwrap();
-    HttpResponse::Ok().body(html)
+    render_or_json(&req, &data.tera, "assignment/stats_browser.html", ctx)
 }
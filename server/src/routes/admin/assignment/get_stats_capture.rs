@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashMap};
+
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::{routes::{admin::util::{consts::CAPTURE_QUALITY_KEYS, hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
+
+#[derive(Serialize)]
+struct DegradedRow {
+    id: String,
+    student_name: String,
+    reasons: Vec<String>,
+}
+
+/// How many processed submissions have a trustworthy capture vs a degraded one
+/// (any of CAPTURE_QUALITY_KEYS present), with the reasons per student.
+#[get("/admin/assignment/{aid}/stats_capture")]
+pub async fn stats_capture(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>, 
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
+    let conn = match data.pool.get() {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let total: i64 = match conn.query_row(
+        &format!("SELECT COUNT(*) FROM submissions s WHERE s.submission_id = ?1 AND s.is_test = 0 AND s.status = 'processed'{visible}"),
+        params![&aid],
+        |r| r.get(0),
+    ) {
+        Ok(n) => n,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let placeholders = vec!["?"; CAPTURE_QUALITY_KEYS.len()].join(",");
+    let sql = format!(
+        "SELECT s.id, s.student_name, f.key FROM findings f
+           JOIN submissions s ON s.id = f.submission_ref
+         WHERE s.submission_id = ? AND s.is_test = 0 AND s.status = 'processed'{visible} AND f.key IN ({placeholders})"
+    );
+    let mut stmt = match conn.prepare(&sql) {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let mut binds: Vec<&dyn rusqlite::ToSql> = vec![&aid];
+    for k in CAPTURE_QUALITY_KEYS {
+        binds.push(k);
+    }
+    let rows = match stmt.query_map(binds.as_slice(), |r| {
+        Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?))
+    }) {
+        Ok(it) => it,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let mut by_sub: HashMap<String, DegradedRow> = HashMap::new();
+    let mut by_reason: BTreeMap<String, i64> = BTreeMap::new();
+    for (id, student_name, key) in rows.flatten() {
+        *by_reason.entry(key.clone()).or_default() += 1;
+        by_sub
+            .entry(id.clone())
+            .or_insert_with(|| DegradedRow { id, student_name, reasons: vec![] })
+            .reasons
+            .push(key);
+    }
+
+    let mut degraded: Vec<DegradedRow> = by_sub.into_values().collect();
+    degraded.sort_by(|a, b| b.reasons.len().cmp(&a.reasons.len()).then_with(|| a.student_name.cmp(&b.student_name)));
+    let degraded_count = degraded.len() as i64;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("total", &total);
+    ctx.insert("trusted", &(total - degraded_count).max(0));
+    ctx.insert("degraded_count", &degraded_count);
+    ctx.insert("by_reason", &by_reason);
+    ctx.insert("degraded", &degraded);
+    render_or_json(&req, &data.tera, "assignment/stats_capture.html", ctx)
+}
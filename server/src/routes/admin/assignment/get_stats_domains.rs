@@ -1,22 +1,28 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 use rusqlite::params;
 
-use crate::{routes::auth::Authorized, AppState};
+use crate::{routes::{admin::util::{hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
 
 
 #[get("/admin/assignment/{aid}/stats_domains")]
 pub async fn stats_domains(
     _: Authorized,
+    session: Session,
+    req: HttpRequest,
     data: web::Data<AppState>, 
     path: web::Path<String>
 ) -> impl Responder {
     let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
     let conn = data.pool.get().unwrap();
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare(&format!(
         "SELECT f.value FROM findings f
            JOIN submissions s ON s.id=f.submission_ref
-         WHERE s.submission_id = ?1 AND f.key='top_domain'"
-    ).unwrap();
+         WHERE s.submission_id = ?1 AND s.is_test = 0{visible} AND f.key='top_domain'"
+    )).unwrap();
     let rows = stmt.query_map(params![&aid], |r| r.get::<_, String>(0)).unwrap();
     use std::collections::HashMap;
     let mut map: HashMap<String, i64> = HashMap::new();
@@ -54,7 +60,6 @@ pub async fn stats_domains(
     ctx.insert("domains_json", &domains_json);
     ctx.insert("hits_json", &hits_json);
 
-    let html = data.tera.render("assignment/stats_domains.html", &ctx).unwrap();
-    HttpResponse::Ok().body(html)
+    render_or_json(&req, &data.tera, "assignment/stats_domains.html", ctx)
 
 }
\ No newline at end of file
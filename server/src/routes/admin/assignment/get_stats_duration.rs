@@ -1,21 +1,35 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, Responder};
 
-use crate::{db::fetch_durations_minutes, routes::auth::Authorized, AppState};
+use crate::{db::fetch_durations_minutes, routes::{admin::util::{hidden::{show_hidden, visible_clause}, negotiate::render_or_json, stats::percentile_i64}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
 
 #[get("/admin/assignment/{aid}/stats_duration")]
 pub async fn stats_duration(
     _: Authorized,
+    session: Session,
+    req: HttpRequest,
     data: web::Data<AppState>, 
     path: web::Path<String>
 ) -> impl Responder {
     let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
     let conn = data.pool.get().unwrap();
-    let vals = fetch_durations_minutes(&conn, &aid);
+    let vals = fetch_durations_minutes(&conn, &aid, &visible_clause(&data, show_hidden(&req)));
 
     let mut avg_display = String::from("N/A");
     let mut max_display = String::from("N/A");
     let mut min_display = String::from("N/A");
+    let mut pcts: Vec<(String, String)> = Vec::new();
+    // percentiles of a handful of submissions say nothing, leave them out below the gate
+    let enough_data = vals.len() >= data.min_stats_submissions;
     if !vals.is_empty() {
+        if enough_data {
+            for p in [50u8, 90, 95] {
+                pcts.push((format!("p{p}"), to_display_time(&percentile_i64(&vals, p as f64))));
+            }
+        }
+
         if let Some(max_time) = vals.iter().max() {
             max_display = to_display_time(max_time);
         };
@@ -43,10 +57,11 @@ pub async fn stats_duration(
     ctx.insert("avg", &avg_display);
     ctx.insert("max", &max_display);
     ctx.insert("min", &min_display);
+    ctx.insert("percentiles", &pcts);
+    ctx.insert("enough_data", &enough_data);
+    ctx.insert("min_required", &data.min_stats_submissions);
 
-    let html = data.tera.render("assignment/stats_duration.html", &ctx).unwrap();
-    
-    HttpResponse::Ok().body(html)
+    render_or_json(&req, &data.tera, "assignment/stats_duration.html", ctx)
 }
 
 fn to_display_time(minutes: &i64) -> String {
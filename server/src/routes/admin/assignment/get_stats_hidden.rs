@@ -0,0 +1,28 @@
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+
+use crate::{routes::{admin::util::{hidden::{count_hidden, show_hidden}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
+
+/// How many trivial/test submissions the statistics leave out (or include, when shown)
+#[get("/admin/assignment/{aid}/stats_hidden")]
+pub async fn stats_hidden(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let conn = match data.pool.get() {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("aid", &aid);
+    ctx.insert("hidden_count", &count_hidden(&conn, &data, &aid, false));
+    ctx.insert("show_hidden", &show_hidden(&req));
+    render_or_json(&req, &data.tera, "assignment/stats_hidden.html", ctx)
+}
@@ -1,8 +1,13 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 
 use crate::routes::auth::Authorized;
 use crate::AppState;
 use crate::routes::admin::util::consts::OUTLIER_MIN_FLAG_PERCENTILE;
+use crate::routes::admin::util::hidden::{show_hidden, visible_clause};
+use crate::routes::admin::util::negotiate::render_or_json;
+use crate::routes::admin::util::stats::{median_i64, percentile_i64, percentile_rank, robust_score};
+use crate::routes::admin::util::access::deny_assignment;
 
 #[derive(serde::Serialize)]
 struct NetOut {
@@ -10,32 +15,37 @@ struct NetOut {
     sub_id: String,
     total_net: i64,
     over_median: i64,     // total_net - median
-    pctl: u8,             // percentile estimate like 97
-    rscore: f64,          // robust score (mad-based)
+    pctl: u8,             // mid-rank percentile like 97, ties share one value
+    rscore: Option<f64>,  // robust score (mad-based), none when mad is 0
 }
 
 #[get("/admin/assignment/{aid}/stats_outliers")]
 pub async fn stats_outliers(
     _: Authorized,
+    session: Session,
+    req: HttpRequest,
     data: web::Data<AppState>, 
     path: web::Path<String>
 ) -> impl Responder {
     use rusqlite::params;
 
     let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
     let conn = match data.pool.get() {
         Ok(c) => c,
         Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
     };
 
     // Pull total_net_events per submission in this assignment
-    let mut stmt = match conn.prepare(
+    let mut stmt = match conn.prepare(&format!(
         "SELECT s.id, s.student_name,
                 COALESCE((SELECT value FROM findings
                           WHERE submission_ref = s.id AND key = 'total_net_events' LIMIT 1), '0')
          FROM submissions s
-         WHERE s.submission_id = ?1"
-    ) {
+         WHERE s.submission_id = ?1 AND s.is_test = 0{visible}
+         ORDER BY s.id"
+    )) {
         Ok(s) => s,
         Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
     };
@@ -65,84 +75,121 @@ pub async fn stats_outliers(
         return HttpResponse::Ok().finish();
     }
 
-    let totals: Vec<i64> = submision_net_events
+    // too few submissions to tell an outlier from noise, so nobody gets flagged
+    if submision_net_events.len() < data.min_stats_submissions {
+        let mut ctx = tera::Context::new();
+        ctx.insert("not_enough_data", &true);
+        ctx.insert("count", &submision_net_events.len());
+        ctx.insert("min_required", &data.min_stats_submissions);
+        return render_or_json(&req, &data.tera, "assignment/stats_outliers.html", ctx);
+    }
+
+    let (med, flagged) = flag_outliers(submision_net_events);
+
+    if flagged.is_empty() {
+        return HttpResponse::Ok().finish();
+    }
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("rows", &flagged);
+    ctx.insert("median", &med);
+    ctx.insert("pctl_min", &OUTLIER_MIN_FLAG_PERCENTILE);
+
+    render_or_json(&req, &data.tera, "assignment/stats_outliers.html", ctx)
+}
+
+/// Submissions whose totals stand out, most extreme first (at most 8), and the median.
+/// Thresholds: at or above the percentile cut, and 3 MADs above the median when there is a
+/// spread. MAD 0 means most submissions are identical, so only the percentile cut applies.
+/// Never flags at or below the median, which rules out all-equal totals and a lone submission.
+fn flag_outliers(submissions: Vec<(String, String, i64)>) -> (i64, Vec<NetOut>) {
+    let totals: Vec<i64> = submissions
         .iter()
         .map(|t| t.2)
         .collect();
 
-    let med = median_i64(totals.clone());
+    let med = median_i64(&totals);
     
     let abs_dev: Vec<i64> = totals
         .iter()
         .map(|&x| (x - med).abs())
         .collect();
-    let mad = median_i64(abs_dev);
+    let mad = median_i64(&abs_dev);
 
-    // thresholds
-    let p_min = percentile_i64(totals.clone(), OUTLIER_MIN_FLAG_PERCENTILE as f64);
-    let robust_cut = med + 3 * mad.max(1);
-    let cut = robust_cut.max(p_min);
+    let p_min = percentile_i64(&totals, OUTLIER_MIN_FLAG_PERCENTILE as f64);
+    let cut = if mad > 0 { p_min.max(med + 3 * mad) } else { p_min };
 
-    // precompute percentiles to display
-    // quick-and-dirty percentile estimate from the sorted list
     let mut sorted = totals.clone();
     sorted.sort_unstable();
-    let n = sorted.len() as f64;
-    let pct_of = |x: i64| -> u8 {
-        // position of last value <= x
-        let idx = match sorted.binary_search(&x) {
-            Ok(i) => i,
-            Err(i) => i.saturating_sub(1),
-        };
-        let p = ((idx as f64) / (n - 1.0).max(1.0)) * 100.0;
-        p.round() as u8
-    };
 
     // build flagged rows
-    let mut flagged: Vec<NetOut> = submision_net_events.into_iter()
-        .filter(|(_, _, tn)| *tn >= cut)
-        .map(|(id, student, tn)| {
-            let rscore = if mad == 0 { 0.0 } else { (tn - med) as f64 / mad as f64 };
-            NetOut {
-                student,
-                sub_id: id,
-                total_net: tn,
-                over_median: tn - med,
-                pctl: pct_of(tn).min(100),
-                rscore,
-            }
+    let mut flagged: Vec<NetOut> = submissions.into_iter()
+        .filter(|(_, _, tn)| *tn >= cut && *tn > med)
+        .map(|(id, student, tn)| NetOut {
+            student,
+            sub_id: id,
+            total_net: tn,
+            over_median: tn - med,
+            pctl: percentile_rank(&sorted, tn),
+            rscore: robust_score(tn, med, mad),
         })
         .collect();
 
-    // sort by how far above median, then by total
-    flagged.sort_by(|a, b| b.over_median.cmp(&a.over_median).then_with(|| b.total_net.cmp(&a.total_net)));
+    // sort by how far above median; equal totals fall back to student then id so the
+    // truncated list does not change between reloads
+    flagged.sort_by(|a, b| {
+        b.over_median.cmp(&a.over_median)
+            .then_with(|| a.student.cmp(&b.student))
+            .then_with(|| a.sub_id.cmp(&b.sub_id))
+    });
     flagged.truncate(8);
 
-    if flagged.is_empty() {
-        return HttpResponse::Ok().finish();
+    (med, flagged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subs(totals: &[i64]) -> Vec<(String, String, i64)> {
+        totals.iter().enumerate().map(|(i, t)| (format!("s{i}"), format!("student {i}"), *t)).collect()
     }
 
-    let mut ctx = tera::Context::new();
-    ctx.insert("rows", &flagged);
-    ctx.insert("median", &med);
-    ctx.insert("pctl_min", &OUTLIER_MIN_FLAG_PERCENTILE);
+    #[test]
+    fn nothing_flagged_when_all_equal() {
+        let (med, flagged) = flag_outliers(subs(&[120; 30]));
+        assert_eq!(med, 120);
+        assert!(flagged.is_empty());
+    }
 
-    let html = match data.tera.render("assignment/stats_outliers.html", &ctx) {
-        Ok(h) => h,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("render error: {e}")),
-    };
-    HttpResponse::Ok().body(html)
-}
+    #[test]
+    fn nothing_flagged_without_submissions() {
+        let (med, flagged) = flag_outliers(vec![]);
+        assert_eq!(med, 0);
+        assert!(flagged.is_empty());
+        assert!(flag_outliers(subs(&[500])).1.is_empty());
+    }
 
+    #[test]
+    fn mad_zero_uses_the_percentile_cut() {
+        // most submissions identical: no MAD scale, the one above the cut is still reported
+        let mut totals = vec![100; 20];
+        totals.push(900);
+        let (_, flagged) = flag_outliers(subs(&totals));
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].total_net, 900);
+        assert_eq!(flagged[0].over_median, 800);
+        assert_eq!(flagged[0].rscore, None);
+        assert_eq!(flagged[0].pctl, 98);
+    }
 
-fn median_i64(mut v: Vec<i64>) -> i64 {
-    v.sort_unstable();
-    let n = v.len();
-    if n % 2 == 1 { v[n/2] } else { (v[n/2 - 1] + v[n/2]) / 2 }
+    #[test]
+    fn ties_are_flagged_together_in_a_stable_order() {
+        let mut totals: Vec<i64> = (1..=20).map(|i| i * 10).collect();
+        totals.extend([1000, 1000]);
+        let (_, flagged) = flag_outliers(subs(&totals));
+        let ids: Vec<&str> = flagged.iter().map(|f| f.sub_id.as_str()).collect();
+        assert_eq!(ids, ["s20", "s21"]);
+        assert_eq!(flagged[0].pctl, flagged[1].pctl);
+    }
 }
-fn percentile_i64(mut v: Vec<i64>, p: f64) -> i64 {
-    v.sort_unstable();
-    if v.is_empty() { return 0; }
-    let rank = ((p.clamp(0.0, 100.0) / 100.0) * (v.len() as f64 - 1.0)).round() as usize;
-    v[rank]
-}
\ No newline at end of file
@@ -0,0 +1,62 @@
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+
+use crate::{routes::{admin::util::{consts::FK_DEVICE_KEY, hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
+use crate::upload_processing::DEVICE_KEY_PREFIX;
+
+
+#[get("/admin/assignment/{aid}/stats_shared_device")]
+pub async fn stats_shared_device(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>, 
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
+    let conn = match data.pool.get() {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    // older uploads stored OS/process fingerprints or raw seat IPs here, which lab images share
+    let mut stmt = match conn.prepare(&format!(
+        "SELECT f.value, s.student_name FROM findings f
+           JOIN submissions s ON s.id = f.submission_ref
+         WHERE s.submission_id = ?1 AND s.is_test = 0{visible} AND f.key = ?2
+           AND f.value LIKE ?3 || '%'"
+    )) {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let rows = match stmt.query_map(params![&aid, FK_DEVICE_KEY, DEVICE_KEY_PREFIX], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))) {
+        Ok(it) => it,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    use std::collections::{BTreeSet, HashMap};
+    let mut key_to_students: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for (key, student) in rows.flatten() {
+        key_to_students.entry(key).or_default().insert(student);
+    }
+
+    // keep only devices used by more than one student
+    let mut rows: Vec<(String, Vec<String>)> = key_to_students.into_iter()
+        .filter(|(_, set)| set.len() > 1)
+        .map(|(key, set)| (key, set.into_iter().collect()))
+        .collect();
+
+    if rows.is_empty() {
+        return HttpResponse::Ok().finish();
+    }
+
+    rows.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("rows", &rows);
+    render_or_json(&req, &data.tera, "assignment/stats_shared_device.html", ctx)
+}
@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::{routes::{admin::util::{access::deny_assignment, consts::{EMPTY_FILE_SHA256, FK_FILES_SHA256, FK_FILE_HASH}, hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+
+#[derive(Serialize)]
+struct SharedPair {
+    a: String,
+    b: String,
+    // names (as submitted by a) of the files with identical content
+    files: Vec<String>,
+    // every file of both submissions matches
+    identical: bool,
+}
+
+/// Pairs of students whose submissions contain files with the same sha256 (possible copy)
+#[get("/admin/assignment/{aid}/stats_shared_files")]
+pub async fn stats_shared_files(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
+    let conn = match data.pool.get() {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let mut stmt = match conn.prepare(&format!(
+        "SELECT f.key, f.value, s.student_name FROM findings f
+           JOIN submissions s ON s.id = f.submission_ref
+         WHERE s.submission_id = ?1 AND s.is_test = 0{visible} AND f.key IN (?2, ?3)"
+    )) {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let rows = match stmt.query_map(params![&aid, FK_FILE_HASH, FK_FILES_SHA256], |r| {
+        Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?))
+    }) {
+        Ok(it) => it,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    // hash -> student -> file name, and student -> combined hash of all files
+    let mut by_hash: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+    let mut combined: HashMap<String, String> = HashMap::new();
+    for (key, value, student) in rows.flatten() {
+        if key == FK_FILES_SHA256 {
+            combined.insert(student, value);
+            continue;
+        }
+        let Some((hash, name)) = value.split_once(':') else { continue; };
+        if hash == EMPTY_FILE_SHA256 { continue; }
+        by_hash.entry(hash.to_string()).or_default().entry(student).or_insert_with(|| name.to_string());
+    }
+
+    let mut pairs: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+    for students in by_hash.values().filter(|s| s.len() > 1) {
+        let list: Vec<(&String, &String)> = students.iter().collect();
+        for (i, (a, name)) in list.iter().enumerate() {
+            for (b, _) in &list[i + 1..] {
+                pairs.entry(((*a).clone(), (*b).clone())).or_default().insert((*name).clone());
+            }
+        }
+    }
+
+    if pairs.is_empty() {
+        return HttpResponse::Ok().finish();
+    }
+
+    let mut rows: Vec<SharedPair> = pairs
+        .into_iter()
+        .map(|((a, b), files)| {
+            let identical = matches!((combined.get(&a), combined.get(&b)), (Some(x), Some(y)) if x == y);
+            SharedPair { a, b, files: files.into_iter().collect(), identical }
+        })
+        .collect();
+    rows.sort_by(|x, y| y.identical.cmp(&x.identical).then_with(|| y.files.len().cmp(&x.files.len())));
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("rows", &rows);
+    render_or_json(&req, &data.tera, "assignment/stats_shared_files.html", ctx)
+}
@@ -1,24 +1,38 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 use rusqlite::params;
 
-use crate::{routes::auth::Authorized, AppState};
+use crate::{routes::{admin::util::{hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
+
+/// (status, count) pairs for the submissions of an assignment, narrowed by `visible`
+/// (a condition over `s` starting with AND, empty for all of them)
+pub fn status_counts(conn: &rusqlite::Connection, aid: &str, visible: &str) -> Result<Vec<(String, i64)>, String> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT s.status, COUNT(*) FROM submissions s WHERE s.submission_id = ?1 AND s.is_test = 0{visible} GROUP BY s.status ORDER BY s.status"
+    )).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![aid], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))
+        .map_err(|e| e.to_string())?;
+
+    let mut out = Vec::new();
+    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
+    Ok(out)
+}
 
 #[get("/admin/assignment/{aid}/stats_status")]
 pub async fn stats_status(
     _: Authorized,
+    session: Session,
+    req: HttpRequest,
     data: web::Data<AppState>, 
     path: web::Path<String>
 ) -> impl Responder {
     let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
     let conn = data.pool.get().unwrap();
-    let mut stmt = conn.prepare(
-        "SELECT status, COUNT(*) FROM submissions WHERE submission_id = ?1 GROUP BY status"
-    ).unwrap();
     let mut labels = Vec::new();
     let mut counts = Vec::new();
-    let rows = stmt.query_map(params![&aid], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))) .unwrap();
-    for r in rows {
-      let (s, n) = r.unwrap();
+    for (s, n) in status_counts(&conn, &aid, &visible_clause(&data, show_hidden(&req))).unwrap() {
       labels.push(s);
       counts.push(n);
     }
@@ -28,6 +42,41 @@ pub async fn stats_status(
     let counts_json = serde_json::to_string(&counts).unwrap();
     ctx.insert("labels_json", &labels_json);
     ctx.insert("counts_json", &counts_json);
-    let html = data.tera.render("assignment/stats_status.html", &ctx).unwrap();
-    HttpResponse::Ok().body(html)
+    render_or_json(&req, &data.tera, "assignment/stats_status.html", ctx)
+}
+
+#[get("/admin/assignment/{aid}/status_summary")]
+pub async fn status_summary(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>, 
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let conn = match data.pool.get() {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    // processing progress, hidden or not
+    let counts = match status_counts(&conn, &aid, "") {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let total: i64 = counts.iter().map(|(_, n)| n).sum();
+    // anything not yet analyzed
+    let pending: i64 = counts
+        .iter()
+        .filter(|(s, _)| s == "received" || s == "processing")
+        .map(|(_, n)| n)
+        .sum();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("aid", &aid);
+    ctx.insert("counts", &counts);
+    ctx.insert("total", &total);
+    ctx.insert("pending", &pending);
+    render_or_json(&req, &data.tera, "assignment/status_summary.html", ctx)
 }
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use actix_session::Session;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::{db, routes::{admin::util::{consts::{FK_UNEXPECTED_DOMAIN, FK_UNEXPECTED_DOMAIN_COUNT}, hidden::{show_hidden, visible_clause}, negotiate::render_or_json}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_assignment;
+
+#[derive(Serialize)]
+struct UnexpectedRow {
+    id: String,
+    student_name: String,
+    count: i64,
+    // (base domain, lookups), most looked-up first
+    domains: Vec<(String, i64)>,
+}
+
+/// Students ranked by lookups of domains outside the assignment's expected set,
+/// with their most visited unexpected domains. Empty until expected domains are configured.
+#[get("/admin/assignment/{aid}/stats_unexpected")]
+pub async fn stats_unexpected(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+    let visible = visible_clause(&data, show_hidden(&req));
+    let expected = match db::get_expected_domains(&data.pool, &aid) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let conn = match data.pool.get() {
+        Ok(c) => c,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let mut stmt = match conn.prepare(&format!(
+        "SELECT s.id, s.student_name, f.key, f.value FROM findings f
+           JOIN submissions s ON s.id = f.submission_ref
+         WHERE s.submission_id = ?1 AND s.is_test = 0 AND s.status = 'processed'{visible} AND f.key IN (?2, ?3)"
+    )) {
+        Ok(s) => s,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let rows = match stmt.query_map(params![&aid, FK_UNEXPECTED_DOMAIN_COUNT, FK_UNEXPECTED_DOMAIN], |r| {
+        Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?, r.get::<_, String>(3)?))
+    }) {
+        Ok(it) => it,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let mut by_sub: HashMap<String, UnexpectedRow> = HashMap::new();
+    for (id, student_name, key, value) in rows.flatten() {
+        let row = by_sub
+            .entry(id.clone())
+            .or_insert_with(|| UnexpectedRow { id, student_name, count: 0, domains: vec![] });
+        if key == FK_UNEXPECTED_DOMAIN_COUNT {
+            row.count = value.parse().unwrap_or(0);
+        } else if let Some((dom, cnt)) = value.rsplit_once(':') {
+            row.domains.push((dom.to_string(), cnt.parse().unwrap_or(0)));
+        }
+    }
+
+    let mut ranked: Vec<UnexpectedRow> = by_sub.into_values().filter(|r| r.count > 0).collect();
+    for r in &mut ranked {
+        r.domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        r.domains.truncate(5);
+    }
+    ranked.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.student_name.cmp(&b.student_name)));
+    ranked.truncate(50);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("aid", &aid);
+    ctx.insert("expected", &expected);
+    ctx.insert("rows", &ranked);
+    render_or_json(&req, &data.tera, "assignment/stats_unexpected.html", ctx)
+}
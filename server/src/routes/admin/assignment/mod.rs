@@ -5,5 +5,16 @@ pub mod get_stats_duration;
 pub mod get_stats_browser;
 pub mod get_stats_domains;
 pub mod get_stats_shared_lan;
+pub mod get_stats_shared_device;
+pub mod get_stats_shared_files;
 pub mod get_stats_outliers;
-pub mod get_cards;
\ No newline at end of file
+pub mod get_cards;
+pub mod get_stats_capture;
+pub mod get_stats_unexpected;
+pub mod get_stats_hidden;
+pub mod get_events;
+pub mod get_findings_ndjson;
+pub mod get_export_json;
+pub mod get_correlation;
+pub mod provided_domains;
+pub mod reprocess;
\ No newline at end of file
@@ -1,7 +1,8 @@
 use actix_session::Session;
 use actix_web::{get, web, HttpResponse, Responder};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
-use crate::{db, routes::auth::Authorized, template, AppState};
+use crate::{db, routes::{admin::util::consts::profile_by_name, auth::Authorized}, template, AppState};
 
 
 #[get("/admin/assignment/{aid}")]
@@ -11,10 +12,21 @@ pub async fn assignment_page(
     data: web::Data<AppState>, 
     path: web::Path<String>
 ) -> impl Responder {
-    if session.get::<String>("prof").ok().flatten().is_none() {
-        return HttpResponse::Found().append_header(("Location", "/admin/login")).finish();
-    }
+    let Some(prof) = session.get::<String>("prof").ok().flatten() else {
+        return HttpResponse::Found().append_header(("Location", format!("{}/admin/login", data.base_path))).finish();
+    };
     let aid = path.into_inner();
+    if !db::prof_can_access(&data.pool, &prof, &aid).unwrap_or(false) {
+        return HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it");
+    }
+    let profile = profile_by_name(&db::get_subscription_profile(&data.pool, &prof, &aid).unwrap_or_default());
+    let provided = db::get_provided_domains(&data.pool, &prof, &aid).unwrap_or_default();
+    // cards newer than the previous visit are marked "new"; this visit becomes the next reference.
+    // the previous one stays in the session so the htmx row refreshes keep marking against it
+    let last_seen = db::get_last_seen(&data.pool, &prof, &aid).unwrap_or_default();
+    let now = OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default();
+    let _ = db::set_last_seen(&data.pool, &prof, &aid, &now);
+    let _ = session.insert(format!("seen_ref:{aid}"), last_seen.clone());
 
     // list submissions for this assignment
     let rows = match db::list_submissions_by_assignment(&data.pool, &aid) {
@@ -30,10 +42,10 @@ pub async fn assignment_page(
     };
 
     // build cards
-    let cards = template::build_cards(&rows, &findings);
+    let cards = template::build_cards(&rows, &findings, profile, &provided, last_seen.as_deref());
 
     // render card grid
-    match template::assignment_cards_page(&data.tera, &aid, &cards) {
+    match template::assignment_cards_page(&data.tera, &aid, &cards, profile, &provided) {
         Ok(html) => HttpResponse::Ok().body(html),
         Err(e) => HttpResponse::InternalServerError().body(e.0),
     }
@@ -0,0 +1,54 @@
+use actix_session::Session;
+use actix_web::{post, web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::{db, routes::auth::Authorized, AppState};
+
+#[derive(Deserialize)]
+pub struct ProvidedForm { pub domains: String }
+
+/// "https://Docs.Python.org/3/", "*.judge.example" -> "docs.python.org", "judge.example"
+pub(crate) fn parse_domains(raw: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for d in raw.split(|c: char| c == ',' || c == ';' || c.is_whitespace()) {
+        let d = d.trim().to_ascii_lowercase();
+        let d = d.split("://").last().unwrap_or("");
+        let d = d.split('/').next().unwrap_or("");
+        let d = d.trim_start_matches("*.").trim_matches('.');
+        if d.is_empty() || !d.contains('.') || out.iter().any(|x| x == d) { continue; }
+        out.push(d.to_string());
+    }
+    out
+}
+
+/// Exam-provided domains of an assignment: hits are still listed, but never raise severity.
+#[post("/admin/assignment/{aid}/provided_domains")]
+pub async fn set_provided_domains(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    form: web::Form<ProvidedForm>,
+) -> impl Responder {
+    let Some(prof) = session.get::<String>("prof").ok().flatten() else {
+        return HttpResponse::Unauthorized().finish();
+    };
+    let aid = path.into_inner();
+    let domains = parse_domains(&form.domains);
+
+    if let Err(e) = db::set_provided_domains(&data.pool, &prof, &aid, &domains) {
+        return HttpResponse::InternalServerError().body(e);
+    }
+    let provided = db::get_provided_domains(&data.pool, &prof, &aid).unwrap_or_default();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("assignment_id", &aid);
+    ctx.insert("provided", &provided);
+    match data.tera.render("assignment/provided_domains.html", &ctx) {
+        Ok(html) => HttpResponse::Ok()
+            // severities on the page depend on the list
+            .insert_header(("HX-Refresh", "true"))
+            .body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
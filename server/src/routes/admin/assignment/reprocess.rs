@@ -0,0 +1,64 @@
+use actix_session::Session;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+
+use crate::{db, routes::{admin::{assignment::get_stats_status::status_counts, audit::audit, util::{access::deny_assignment, negotiate::render_or_json}}, auth::Authorized}, AppState};
+
+/// (received, processing) submissions of the assignment
+fn queue_progress(data: &AppState, aid: &str) -> Result<(i64, i64), String> {
+    let conn = data.pool.get().map_err(|e| e.to_string())?;
+    let counts = status_counts(&conn, aid, "")?;
+    let count_of = |status: &str| counts.iter().find(|(s, _)| s == status).map(|(_, n)| *n).unwrap_or(0);
+    Ok((count_of("received"), count_of("processing")))
+}
+
+fn progress_context(aid: &str, received: i64, processing: i64, queued: Option<usize>) -> tera::Context {
+    let mut ctx = tera::Context::new();
+    ctx.insert("aid", aid);
+    ctx.insert("received", &received);
+    ctx.insert("processing", &processing);
+    ctx.insert("queued", &queued);
+    ctx
+}
+
+/// Analyze every submission of the assignment again with the current rules (after the AI
+/// domain or tool lists changed). Only queues them; the background worker does the rest.
+#[post("/admin/assignment/{aid}/reprocess")]
+pub async fn reprocess_assignment(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+
+    let queued = match db::requeue_assignment(&data.pool, &aid) {
+        Ok(n) => n,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    audit(&data, &session, "reprocess_assignment", &aid);
+    let (received, processing) = match queue_progress(&data, &aid) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    render_or_json(&req, &data.tera, "assignment/reprocess_status.html", progress_context(&aid, received, processing, Some(queued)))
+}
+
+#[get("/admin/assignment/{aid}/reprocess_status")]
+pub async fn reprocess_status(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if let Some(resp) = deny_assignment(&session, &data, &aid) { return resp; }
+
+    let (received, processing) = match queue_progress(&data, &aid) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    render_or_json(&req, &data.tera, "assignment/reprocess_status.html", progress_context(&aid, received, processing, None))
+}
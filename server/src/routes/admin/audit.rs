@@ -0,0 +1,51 @@
+use actix_session::Session;
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use tera::Context;
+
+use crate::{db, routes::{admin::util::access::is_admin, auth::Authorized}, AppState};
+
+const AUDIT_PER_PAGE: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    #[serde(default)]
+    page: Option<i64>,
+}
+
+/// Record an admin action; failures are logged, never surfaced to the professor
+pub fn audit(data: &AppState, session: &Session, action: &str, target: &str) {
+    let Some(prof) = session.get::<String>("prof").ok().flatten() else { return; };
+    if let Err(e) = db::record_audit(&data.pool, &prof, action, target) {
+        log::warn!("audit log: {action} {target} by {prof}: {e}");
+    }
+}
+
+#[get("/admin/audit")]
+pub async fn audit_page(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    q: web::Query<AuditQuery>,
+) -> impl Responder {
+    if !is_admin(&session, &data) {
+        return HttpResponse::Forbidden().body("the audit log is only visible to PAL_AUDIT_ADMINS");
+    }
+
+    let page = q.page.unwrap_or(1).max(1);
+    let (entries, total) = match db::list_audit(&data.pool, AUDIT_PER_PAGE, (page - 1) * AUDIT_PER_PAGE) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let pages = ((total + AUDIT_PER_PAGE - 1) / AUDIT_PER_PAGE).max(1);
+
+    let mut ctx = Context::new();
+    ctx.insert("entries", &entries);
+    ctx.insert("total", &total);
+    ctx.insert("page", &page);
+    ctx.insert("pages", &pages);
+    match data.tera.render("audit/page.html", &ctx) {
+        Ok(html) => HttpResponse::Ok().body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
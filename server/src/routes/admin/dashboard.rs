@@ -2,14 +2,14 @@ use actix_session::Session;
 use actix_web::{get, web, HttpResponse, Responder};
 use tera::Context;
 
-use crate::{db, routes::auth::Authorized, AppState};
+use crate::{db, routes::{admin::util::consts::PROFILES, auth::Authorized}, AppState};
 
 
 
 #[get("/admin")]
-pub async fn admin_root(_: Authorized) -> impl Responder {
+pub async fn admin_root(_: Authorized, data: web::Data<AppState>) -> impl Responder {
     HttpResponse::Found()
-        .append_header(("Location", "/admin/dashboard"))
+        .append_header(("Location", format!("{}/admin/dashboard", data.base_path)))
         .finish()
 }
 
@@ -27,6 +27,7 @@ pub async fn dashboard(
     
     let mut ctx = Context::new();
     ctx.insert("subs", &subs);
+    ctx.insert("profiles", &PROFILES);
     match data.tera.render("dashboard/page.html", &ctx) {
         Ok(html) => HttpResponse::Ok().body(html),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
@@ -0,0 +1,135 @@
+use actix_session::Session;
+use actix_web::{get, post, web, Error, HttpRequest, HttpResponse, Responder};
+use futures_util::StreamExt as _;
+use serde::Deserialize;
+
+use crate::{db, live, routes::{admin::{audit::audit, subscribe::parse_id_list}, auth::Authorized}, AppState};
+
+#[derive(Deserialize)]
+pub struct IssueForm {
+    // seat names (usually host names), comma or space separated
+    pub seats: String,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeForm {
+    pub seat: String,
+}
+
+/// Live watching is limited to professors granted access to the assignment.
+fn may_watch(session: &Session, data: &AppState, aid: &str) -> bool {
+    let Some(prof) = session.get::<String>("prof").ok().flatten() else { return false; };
+    db::prof_can_access(&data.pool, &prof, aid).unwrap_or(false)
+}
+
+#[get("/admin/live/{aid}")]
+pub async fn live_page(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if !may_watch(&session, &data, &aid) {
+        return HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it");
+    }
+
+    render_page(&data, &aid, &[])
+}
+
+/// The live page; `issued` (seat, token) pairs are shown once, right after issuing
+fn render_page(data: &AppState, aid: &str, issued: &[(String, String)]) -> HttpResponse {
+    let seats = data.live.live_seats(aid);
+    let tokens = db::list_live_tokens(&data.pool, aid).unwrap_or_default();
+    let mut ctx = tera::Context::new();
+    ctx.insert("assignment_id", aid);
+    ctx.insert("seats", &seats);
+    ctx.insert("tokens", &tokens);
+    ctx.insert("issued", issued);
+    match data.tera.render("live/page.html", &ctx) {
+        Ok(html) => HttpResponse::Ok().body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Issue (or replace) the collector tokens of some seats
+#[post("/admin/live/{aid}/tokens")]
+pub async fn issue_tokens(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    form: web::Form<IssueForm>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if !may_watch(&session, &data, &aid) {
+        return HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it");
+    }
+    let prof = session.get::<String>("prof").ok().flatten().unwrap_or_default();
+    let mut issued = Vec::new();
+    for seat in parse_id_list(&form.seats) {
+        match db::issue_live_token(&data.pool, &aid, &seat, &prof) {
+            Ok(token) => issued.push((seat, token)),
+            Err(e) => return HttpResponse::InternalServerError().body(e),
+        }
+    }
+    if !issued.is_empty() {
+        let seats: Vec<&str> = issued.iter().map(|(s, _)| s.as_str()).collect();
+        audit(&data, &session, "issue_live_token", &format!("{aid}:{}", seats.join(",")));
+    }
+    render_page(&data, &aid, &issued)
+}
+
+#[post("/admin/live/{aid}/tokens/revoke")]
+pub async fn revoke_token(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    form: web::Form<RevokeForm>,
+) -> impl Responder {
+    let aid = path.into_inner();
+    if !may_watch(&session, &data, &aid) {
+        return HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it");
+    }
+    let seat = form.seat.trim();
+    if let Err(e) = db::revoke_live_token(&data.pool, &aid, seat) {
+        return HttpResponse::InternalServerError().body(e);
+    }
+    audit(&data, &session, "revoke_live_token", &format!("{aid}:{seat}"));
+    HttpResponse::Found().append_header(("Location", format!("{}/admin/live/{aid}", data.base_path))).finish()
+}
+
+#[get("/admin/live/{aid}/{seat}/ws")]
+pub async fn live_watch(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    body: web::Payload,
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (aid, seat) = path.into_inner();
+    if !may_watch(&session, &data, &aid) {
+        return Ok(HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it"));
+    }
+
+    let (resp, ws, mut msgs) = actix_ws::handle(&req, body)?;
+    let key = live::stream_key(&aid, &seat);
+    let id = data.live.add_watcher(&key, ws.clone());
+
+    // the watcher only listens; keep the socket alive until the browser goes away
+    actix_web::rt::spawn(async move {
+        let mut ws = ws;
+        while let Some(Ok(msg)) = msgs.next().await {
+            match msg {
+                actix_ws::Message::Ping(b) if ws.pong(&b).await.is_err() => break,
+                actix_ws::Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        data.live.remove_watcher(&key, id);
+    });
+
+    Ok(resp)
+}
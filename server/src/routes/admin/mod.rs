@@ -3,4 +3,8 @@ pub mod unsubscribe;
 pub mod dashboard;
 pub mod assignment;
 pub mod submission;
+pub mod live;
+pub mod system;
+pub mod audit;
+pub mod access;
 pub mod util;
\ No newline at end of file
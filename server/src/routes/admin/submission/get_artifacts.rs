@@ -1,23 +1,31 @@
 use std::collections::HashMap;
 
 // src/routes/admin.rs (or routes/submission.rs)
+use actix_session::Session;
 use actix_web::{get, web, HttpResponse, Responder};
-use crate::{db::{self, FindingRow}, routes::auth::Authorized, AppState};
+use crate::{db::{self, FindingRow}, routes::{admin::util::consts::{FK_MANIFEST_PARSE_ERROR, FK_PRIVATE_IP, FK_STUDENT_NOTE}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_submission;
 
 #[get("/admin/submissions/{id}/artifacts")]
 pub async fn submission_artifacts_frag(
     _: Authorized,
+    session: Session,
     data: web::Data<AppState>,
     path: web::Path<String>,
 ) -> impl Responder {
     let id = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
+    render_artifacts(&data, &id, false)
+}
 
+/// The artifacts & findings fragment; `requeued` adds a notice that the findings are being recomputed
+pub fn render_artifacts(data: &AppState, id: &str, requeued: bool) -> HttpResponse {
     // fetch artifacts + findings
-    let logs = match db::list_logs_for_submission(&data.pool, &id) {
+    let logs = match db::list_logs_for_submission(&data.pool, id) {
         Ok(v) => v,
         Err(e) => return HttpResponse::InternalServerError().body(e),
     };
-    let findings = match db::list_findings_for_submission(&data.pool, &id) {
+    let findings = match db::list_findings_for_submission(&data.pool, id) {
         Ok(v) => v,
         Err(e) => return HttpResponse::InternalServerError().body(e),
     };
@@ -30,8 +38,26 @@ pub async fn submission_artifacts_frag(
         by_kind.entry(f.kind.clone()).or_default().push(f);
     }
     println!("by_kind: {by_kind:#?}");
+
+    // distinct private IPs (ip, count), already sorted by count at analysis time
+    let private_ips: Vec<(String, i64)> = findings
+        .iter()
+        .filter(|f| f.key == FK_PRIVATE_IP)
+        .filter_map(|f| f.value.rsplit_once(':'))
+        .map(|(ip, cnt)| (ip.to_string(), cnt.parse::<i64>().unwrap_or(0)))
+        .collect();
+
+    let manifest_error = findings
+        .iter()
+        .find(|f| f.key == FK_MANIFEST_PARSE_ERROR)
+        .map(|f| f.value.clone());
     
+    ctx.insert("id", id);
     ctx.insert("logs", &logs);
+    ctx.insert("requeued", &requeued);
+    ctx.insert("manifest_error", &manifest_error);
+    ctx.insert("student_note", &findings.iter().find(|f| f.key == FK_STUDENT_NOTE).map(|f| f.value.clone()));
+    ctx.insert("private_ips", &private_ips);
     ctx.insert("by_kind", &by_kind);
     match data.tera.render("submission/artifacts.html", &ctx) {
         Ok(html) => HttpResponse::Ok().body(html),
@@ -0,0 +1,85 @@
+use std::{fs::File, io::{Seek, SeekFrom, Write}};
+
+use actix_files::NamedFile;
+use actix_session::Session;
+use actix_web::{get, http::header::{ContentDisposition, DispositionParam, DispositionType}, web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{routes::{admin::{audit::audit, util::{access::deny_submission, zip::open_processed_zip_by_submission}}, auth::Authorized}, AppState};
+
+#[derive(Deserialize)]
+pub struct LogQuery {
+    // "manifest" prepends the client's manifest.json
+    #[serde(default)]
+    include: Option<String>,
+}
+
+/// Unpack the log (and optionally the manifest) of a submission's processed zip into an
+/// unlinked temp file, so it is streamed from disk instead of being held in memory.
+fn unpack_log(data: &AppState, id: &str, with_manifest: bool) -> Result<File, String> {
+    // the zip is located through the logs table, never from a client supplied name
+    let mut zip = open_processed_zip_by_submission(data, id)?;
+
+    let tmp_path = std::env::temp_dir().join(format!("palantir-log-{}", Uuid::new_v4()));
+    let mut out = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&tmp_path)
+        .map_err(|e| format!("temp file: {e}"))?;
+    // the open handle keeps the data until the response is sent
+    let _ = std::fs::remove_file(&tmp_path);
+
+    if with_manifest {
+        writeln!(out, "==> manifest.json <==").map_err(|e| e.to_string())?;
+        match zip.by_name("manifest.json") {
+            Ok(mut f) => { std::io::copy(&mut f, &mut out).map_err(|e| e.to_string())?; }
+            Err(_) => { writeln!(out, "(missing)").map_err(|e| e.to_string())?; }
+        }
+        writeln!(out, "\n\n==> snapshot/palantir.log <==").map_err(|e| e.to_string())?;
+    }
+    let mut log = zip.by_name("snapshot/palantir.log").map_err(|_| "no log in this submission".to_string())?;
+    std::io::copy(&mut log, &mut out).map_err(|e| e.to_string())?;
+
+    out.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// The raw collector log as uploaded, for handing to an academic-integrity committee
+#[get("/admin/submissions/{id}/log.txt")]
+pub async fn download_log(
+    _: Authorized,
+    session: Session,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    q: web::Query<LogQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
+    let with_manifest = q.include.as_deref() == Some("manifest");
+
+    let file = {
+        let data = data.clone();
+        let id = id.clone();
+        match web::block(move || unpack_log(&data, &id, with_manifest)).await {
+            Ok(Ok(f)) => f,
+            Ok(Err(e)) => return HttpResponse::NotFound().body(e),
+            Err(e) => return HttpResponse::InternalServerError().body(format!("worker error: {e}")),
+        }
+    };
+    audit(&data, &session, "download_log", &id);
+
+    let name = format!("palantir-{id}.log.txt");
+    match NamedFile::from_file(file, &name) {
+        Ok(f) => f
+            .set_content_type(mime::TEXT_PLAIN_UTF_8)
+            .set_content_disposition(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(name)],
+            })
+            .into_response(&req),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
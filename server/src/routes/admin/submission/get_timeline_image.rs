@@ -0,0 +1,279 @@
+use actix_session::Session;
+use actix_web::{get, http::header, web, HttpResponse, Responder};
+use plotters::{coord::Shift, prelude::*};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    db,
+    routes::{
+        admin::{
+            submission::{get_timeline_network::net_points, get_timeline_process::{proc_payload, ProcPayload, DEFAULT_MERGE_GAP_SECS, DEFAULT_PROC_ROW_LIMIT}},
+            util::{consts::CHEAT_HIGHLIGHT_PROCS, localtime::to_local, point::Point},
+        },
+        auth::Authorized,
+    },
+    AppState,
+};
+use crate::routes::admin::util::access::deny_submission;
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 1100;
+// process rows shown in the static export (longest running first)
+const MAX_PROC_ROWS: usize = 30;
+
+/// One process row of the export: its segments on the x axis (start, end, still running)
+/// and their total length, which orders the rows
+struct ProcRow<'a> {
+    label: &'a str,
+    segs: Vec<(f64, f64, bool)>,
+    total: f64,
+}
+
+/// Static rendering of the network + process timelines for case files.
+/// `ext` is `png` or `svg`.
+#[get("/admin/submissions/{id}/timeline.{ext}")]
+pub async fn timeline_image(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (id, ext) = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
+    if ext != "png" && ext != "svg" {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let student = match db::get_submission_detail(&data.pool, &id) {
+        Ok(Some(info)) => info.student_name,
+        Ok(None) => return HttpResponse::NotFound().body("submission not found"),
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let points = match net_points(&data, &id) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let procs = match proc_payload(&data, &id, DEFAULT_MERGE_GAP_SECS, DEFAULT_PROC_ROW_LIMIT) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let Some(window) = session_window(&points, procs.as_ref()) else {
+        return HttpResponse::NotFound().body("no timeline data");
+    };
+
+    let hide = &data.hide_procs;
+    let rendered = if ext == "svg" {
+        render_svg(&student, &points, procs.as_ref(), hide, window).map(|s| s.into_bytes())
+    } else {
+        render_png(&student, &points, procs.as_ref(), hide, window)
+    };
+    match rendered {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type(if ext == "svg" { "image/svg+xml" } else { "image/png" })
+            .insert_header((header::CONTENT_DISPOSITION, format!("inline; filename=\"timeline-{id}.{ext}\"")))
+            .body(bytes),
+        Err(e) => HttpResponse::InternalServerError().body(format!("render: {e}")),
+    }
+}
+
+/// (start, end) of the session in epoch ms, over both net buckets and process intervals
+fn session_window(points: &[Point], procs: Option<&ProcPayload>) -> Option<(i128, i128)> {
+    let mut lo: Option<i128> = None;
+    let mut hi: Option<i128> = None;
+    let mut widen = |s: i128, e: i128| {
+        lo = Some(lo.map_or(s, |x| x.min(s)));
+        hi = Some(hi.map_or(e, |x| x.max(e)));
+    };
+    for p in points {
+        let s = p.minute as i128 * 60_000;
+        widen(s, s + 60_000);
+    }
+    if let Some(pp) = procs
+        && !pp.rows.is_empty()
+    {
+        widen(pp.tmin, pp.tmax);
+    }
+    match (lo, hi) {
+        (Some(s), Some(e)) if e > s => Some((s, e)),
+        (Some(s), Some(_)) => Some((s, s + 60_000)),
+        _ => None,
+    }
+}
+
+fn render_svg(student: &str, points: &[Point], procs: Option<&ProcPayload>, hide: &[String], window: (i128, i128)) -> Result<String, String> {
+    let mut out = String::new();
+    {
+        let root = SVGBackend::with_string(&mut out, (WIDTH, HEIGHT)).into_drawing_area();
+        draw(&root, student, points, procs, hide, window)?;
+        root.present().map_err(|e| e.to_string())?;
+    }
+    Ok(out)
+}
+
+fn render_png(student: &str, points: &[Point], procs: Option<&ProcPayload>, hide: &[String], window: (i128, i128)) -> Result<Vec<u8>, String> {
+    // the bitmap encoder writes to a path, so go through a temp file
+    let tmp = std::env::temp_dir().join(format!("palantir-timeline-{}.png", Uuid::new_v4()));
+    let res = (|| {
+        let root = BitMapBackend::new(&tmp, (WIDTH, HEIGHT)).into_drawing_area();
+        draw(&root, student, points, procs, hide, window)?;
+        root.present().map_err(|e| e.to_string())
+    })();
+    let bytes = res.and_then(|_| std::fs::read(&tmp).map_err(|e| e.to_string()));
+    let _ = std::fs::remove_file(&tmp);
+    bytes
+}
+
+fn hhmm(ms: i128) -> String {
+    match OffsetDateTime::from_unix_timestamp_nanos(ms * 1_000_000) {
+        Ok(dt) => {
+            let l = to_local(dt);
+            format!("{:02}:{:02}", l.hour(), l.minute())
+        }
+        Err(_) => String::new(),
+    }
+}
+
+fn draw<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    student: &str,
+    points: &[Point],
+    procs: Option<&ProcPayload>,
+    hide: &[String],
+    (t0, t1): (i128, i128),
+) -> Result<(), String> {
+    let err = |e: DrawingAreaErrorKind<DB::ErrorType>| e.to_string();
+    // x axis: minutes since session start
+    let to_x = |ms: i128| (ms - t0) as f64 / 60_000.0;
+    let span = to_x(t1).max(1.0);
+    let x_label = |x: &f64| hhmm(t0 + (*x * 60_000.0) as i128);
+
+    let date = OffsetDateTime::from_unix_timestamp_nanos(t0 * 1_000_000)
+        .map(|dt| to_local(dt).date().to_string())
+        .unwrap_or_default();
+    let title = format!(
+        "{}  |  {} {} - {} ({} min)",
+        student, date, hhmm(t0), hhmm(t1), ((t1 - t0) / 60_000).max(0)
+    );
+
+    root.fill(&WHITE).map_err(err)?;
+    let root = root.titled(&title, ("sans-serif", 24)).map_err(err)?;
+    let (upper, lower) = root.split_vertically(340);
+
+    // minutes (x start) with AI traffic, reused as overlay in both charts
+    let ai_minutes: Vec<f64> = points
+        .iter()
+        .filter(|p| p.ai > 0)
+        .map(|p| to_x(p.minute as i128 * 60_000))
+        .collect();
+
+    // --- network ---
+    let y_max = points.iter().map(|p| p.total).max().unwrap_or(0).max(1) as f64 * 1.1;
+    let mut net = ChartBuilder::on(&upper)
+        .caption("Network events per minute", ("sans-serif", 18))
+        .margin(12)
+        .x_label_area_size(30)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0f64..span, 0f64..y_max)
+        .map_err(err)?;
+    net.configure_mesh()
+        .x_label_formatter(&x_label)
+        .y_desc("events")
+        .draw()
+        .map_err(err)?;
+
+    net.draw_series(points.iter().map(|p| {
+        let x = to_x(p.minute as i128 * 60_000);
+        Rectangle::new([(x, 0.0), (x + 1.0, p.total as f64)], BLUE.mix(0.45).filled())
+    }))
+    .map_err(err)?
+    .label("total")
+    .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 12, y + 5)], BLUE.mix(0.45).filled()));
+
+    net.draw_series(points.iter().filter(|p| p.ai > 0).map(|p| {
+        let x = to_x(p.minute as i128 * 60_000);
+        Rectangle::new([(x, 0.0), (x + 1.0, p.ai as f64)], RED.filled())
+    }))
+    .map_err(err)?
+    .label("AI hits")
+    .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 12, y + 5)], RED.filled()));
+
+    net.draw_series(LineSeries::new(
+        points.iter().map(|p| (to_x(p.minute as i128 * 60_000) + 0.5, p.ma100 as f64)),
+        BLACK.stroke_width(2),
+    ))
+    .map_err(err)?
+    .label("MA(100)")
+    .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 12, y)], BLACK.stroke_width(2)));
+
+    net.configure_series_labels()
+        .background_style(WHITE.mix(0.85))
+        .border_style(BLACK)
+        .draw()
+        .map_err(err)?;
+
+    // --- processes ---
+    let mut rows: Vec<ProcRow> = procs
+        .map(|pp| {
+            pp.rows
+                .iter()
+                .filter(|r| !hide.contains(&r.label))
+                .map(|r| {
+                    let segs: Vec<(f64, f64, bool)> = r
+                        .segments
+                        .iter()
+                        .map(|s| (to_x(s.start), to_x(s.end), s.still_running))
+                        .collect();
+                    let total = segs.iter().map(|(s, e, _)| e - s).sum::<f64>();
+                    ProcRow { label: r.label.as_str(), segs, total }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    rows.sort_by(|a, b| b.total.total_cmp(&a.total));
+    rows.truncate(MAX_PROC_ROWS);
+
+    let n = rows.len().max(1) as f64;
+    let labels: Vec<String> = rows.iter().map(|r| r.label.to_string()).collect();
+    let y_label = |y: &f64| {
+        let i = y.floor() as usize;
+        if (*y - y.floor()).abs() < f64::EPSILON { labels.get(i).cloned().unwrap_or_default() } else { String::new() }
+    };
+
+    let mut proc = ChartBuilder::on(&lower)
+        .caption(format!("Processes (top {} by runtime, system processes hidden)", rows.len()), ("sans-serif", 18))
+        .margin(12)
+        .x_label_area_size(30)
+        .y_label_area_size(160)
+        .build_cartesian_2d(0f64..span, 0f64..n)
+        .map_err(err)?;
+    proc.configure_mesh()
+        .disable_y_mesh()
+        .y_labels(rows.len().max(1))
+        .x_label_formatter(&x_label)
+        .y_label_formatter(&y_label)
+        .x_desc("local time")
+        .draw()
+        .map_err(err)?;
+
+    // AI overlay across all rows
+    proc.draw_series(ai_minutes.iter().map(|x| Rectangle::new([(*x, 0.0), (*x + 1.0, n)], RED.mix(0.15).filled())))
+        .map_err(err)?;
+
+    for (i, ProcRow { label, segs, .. }) in rows.iter().enumerate() {
+        let color = if CHEAT_HIGHLIGHT_PROCS.contains(label) { RED.mix(0.8) } else { GREEN.mix(0.6) };
+        let y = i as f64;
+        proc.draw_series(segs.iter().map(|(s, e, _)| {
+            Rectangle::new([(*s, y + 0.15), (e.max(s + 0.05), y + 0.85)], color.filled())
+        }))
+        .map_err(err)?;
+        // no stop logged: mark the open right edge
+        proc.draw_series(segs.iter().filter(|(_, _, running)| *running).map(|(_, e, _)| {
+            PathElement::new(vec![(*e, y + 0.05), (*e, y + 0.95)], BLACK.stroke_width(3))
+        }))
+        .map_err(err)?;
+    }
+
+    Ok(())
+}
@@ -1,21 +1,21 @@
-use std::{fs::File, io::{BufRead, BufReader}};
-
+use actix_session::Session;
 use actix_web::{get, web, HttpResponse, Responder};
-use rusqlite::params;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
-use zip::ZipArchive;
+use time::OffsetDateTime;
 
-use crate::{routes::{admin::util::point::Point, auth::Authorized}, AppState};
+use crate::{derived, routes::{admin::util::{localtime::minute_label, point::Point}, auth::Authorized}, AppState};
+use crate::routes::admin::util::access::deny_submission;
 
 
 
 #[get("/admin/submissions/{id}/net_timeline")]
 pub async fn net_timeline_fragment(
     _: Authorized,
+    session: Session,
     data: web::Data<AppState>,
     path: web::Path<String>
 ) -> impl Responder {
     let id = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
     let mut ctx = tera::Context::new();
     ctx.insert("id", &id);
     match data.tera.render("submission/timeline_network.html", &ctx) {
@@ -27,113 +27,39 @@ pub async fn net_timeline_fragment(
 
 #[get("/admin/submissions/{id}/net_timeline.json")]
 pub async fn net_timeline_json(
+    _: Authorized,
+    session: Session,
     data: web::Data<AppState>,
     path: web::Path<String>,
 ) -> impl Responder {
     let id = path.into_inner();
-
-    // locate the uploaded zip path for this submission
-    let conn = match data.pool.get() {
-        Ok(c) => c,
-        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-    };
-
-    let filename: String = match conn.query_row(
-        "SELECT fs_path FROM logs WHERE submission_ref = ?1 ORDER BY rowid ASC LIMIT 1",
-        params![&id],
-        |r| {
-            let full: String = r.get(0)?;
-            // just keep the filename
-            Ok(std::path::Path::new(&full)
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned())
-        },
-    ) {
-        Ok(r) => r,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("query: {e}"))
-    };
-
-    // now open from processed_uploads
-    let zip_path = data.processed_dir.join(&filename);
-    let file = match File::open(&zip_path) {
-        Ok(f) => f,
-        Err(e) => return HttpResponse::InternalServerError()
-            .body(format!("open zip {}: {}", zip_path.display(), e))
-    };
-
-    let mut zip = match ZipArchive::new(file) {
-        Ok(zip) => zip,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("zip: {e}"))
-    };
-
-
-    let mut log = match zip.by_name("snapshot/palantir.log") {
-        Ok(f) => BufReader::new(f),
-        Err(_) => return HttpResponse::Ok().json(Vec::<Point>::new()),
-    };
-
-    // classify AI domains
-    fn is_ai_domain(d: &str) -> bool {
-        let d = d.to_ascii_lowercase();
-        let hits = [
-            "openai.com","chatgpt.com","anthropic.com","claude.ai",
-            "gemini.google.com","googleapis.com","googleai","ai.google",
-            "huggingface.co","cohere.ai","replicate.com","perplexity.ai",
-            "openrouter.ai","stability.ai","midjourney.com"
-        ];
-        hits.iter().any(|s| d.contains(s))
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
+    match net_points(&data, &id) {
+        Ok(points) => HttpResponse::Ok().json(points),
+        Err(e) => HttpResponse::InternalServerError().body(e),
     }
+}
 
-    // bucket by minute in local time for user-friendly x labels
-    let local = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
-    use std::collections::BTreeMap;
-    let mut buckets: BTreeMap<String, (i32, i32)> = BTreeMap::new();
-
-    let mut line = String::new();
-    while let Ok(n) = log.read_line(&mut line) {
-        if n == 0 { break; }
-        // only net lines
-        if !line.contains("\"kind\":\"net\"") {
-            line.clear();
-            continue;
-        }
-        // quick and safe parse
-        let v: serde_json::Value = match serde_json::from_str(&line) {
-            Ok(x) => x,
-            Err(_) => { line.clear(); continue; }
-        };
-        let ts = match v.get("ts").and_then(|x| x.as_str()) {
-            Some(s) => s, None => { line.clear(); continue; }
-        };
-        let minute_key = match OffsetDateTime::parse(ts, &Rfc3339)
-            .map(|dt| dt.to_offset(local))
-            .ok().map(|dt| format!("{:04}-{:02}-{:02} {:02}:{:02}",
-                dt.year(), dt.month() as u8, dt.day(), dt.hour(), dt.minute()))
-        {
-            Some(k) => k,
-            None => { line.clear(); continue; }
-        };
-        let domain = v.get("dns_qname").and_then(|x| x.as_str()).unwrap_or("");
-        let entry = buckets.entry(minute_key).or_insert((0, 0));
-        entry.0 += 1;
-        if is_ai_domain(domain) {
-            entry.1 += 1;
-        }
-        line.clear();
-    }
+/// Per-minute network activity (total / AI hits / MA(100)) of a submission's log.
+/// Shared by the JSON endpoint and the static timeline export.
+pub fn net_points(data: &AppState, id: &str) -> Result<Vec<Point>, String> {
+    // bucketed by UTC minute at analysis time (keeps order and repeated DST hours apart),
+    // local time only for x labels
+    let minutes = derived::net_minutes(data, id)?;
 
-    // compute MA(100) over total
-    let mut out: Vec<Point> = Vec::with_capacity(buckets.len());
-    for (t, (tot, ai)) in buckets {
+    let mut out: Vec<Point> = Vec::with_capacity(minutes.len());
+    for m in minutes {
+        let t = OffsetDateTime::from_unix_timestamp(m.minute * 60).map(minute_label).unwrap_or_default();
         out.push(Point { 
             t, 
-            total: tot, 
-            ai, 
+            minute: m.minute,
+            total: m.total, 
+            ai: m.ai, 
             ma100: 0.0 
         });
     }
+
+    // compute MA(100) over total
     let w = 100usize;
     if !out.is_empty() {
         let mut acc: i64 = 0;
@@ -156,6 +82,5 @@ pub async fn net_timeline_json(
         }
     }
 
-    HttpResponse::Ok().json(out)
+    Ok(out)
 }
-
@@ -1,46 +1,66 @@
-use std::{collections::HashMap, io::{BufRead, BufReader}};
-
+use actix_session::Session;
 use actix_web::{get, web, HttpResponse, Responder};
-use serde::Serialize;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use serde::{Deserialize, Serialize};
 
-use crate::routes::{admin::util::{consts::{CHEAT_HIGHLIGHT_PROCS, SYSTEM_HIDE_PROCS}, point::Point, zip::open_processed_zip_by_submission}, auth::Authorized};
+use crate::{derived::{self, ProcInterval, ProcIntervals}, routes::{admin::util::{consts::CHEAT_HIGHLIGHT_PROCS, point::Point}, auth::Authorized}};
+use crate::routes::admin::util::access::deny_submission;
 
 
 #[derive(Serialize)]
-struct ProcSeg { 
-    start: i128, 
-    end: i128 
+pub struct ProcSeg { 
+    pub start: i128, 
+    pub end: i128,
+    // no stop event was logged; `end` is just the end of the log
+    pub still_running: bool,
 } 
 
 
 #[derive(Serialize)]
-struct ProcRow { 
-    label: String, 
-    segments: Vec<ProcSeg> 
+pub struct ProcRow { 
+    pub label: String, 
+    pub segments: Vec<ProcSeg>,
+    // logged runs before merging, i.e. how often it was launched
+    pub launches: usize,
+}
+
+// runs of the same process closer than this are drawn as one segment
+pub const DEFAULT_MERGE_GAP_SECS: i64 = 5;
+const MAX_MERGE_GAP_SECS: i64 = 3600;
+// processes kept, longest total runtime first
+pub const DEFAULT_PROC_ROW_LIMIT: usize = 500;
+const MAX_PROC_ROW_LIMIT: usize = 5000;
+
+#[derive(Deserialize)]
+pub struct ProcTimelineQuery {
+    #[serde(default)]
+    merge_gap_secs: Option<i64>,
+    #[serde(default)]
+    limit: Option<usize>,
 }
 
 
 #[derive(Serialize)]
-struct ProcPayload {
-    labels: Vec<String>,
-    rows: Vec<ProcRow>,
-    tmin: i128,
-    tmax: i128,
+pub struct ProcPayload {
+    pub labels: Vec<String>,
+    pub rows: Vec<ProcRow>,
+    pub tmin: i128,
+    pub tmax: i128,
 }
 
 
 #[get("/admin/submissions/{id}/proc_timeline")]
 pub async fn proc_timeline_fragment(
     _: Authorized,
+    session: Session,
     data: web::Data<crate::AppState>,
     path: web::Path<String>
 ) -> impl Responder {
     let id = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
     let mut ctx = tera::Context::new();
     ctx.insert("id", &id);
     let cheat_json = serde_json::to_string(&CHEAT_HIGHLIGHT_PROCS).unwrap();
-    let system_json = serde_json::to_string(&SYSTEM_HIDE_PROCS).unwrap();
+    let system_json = serde_json::to_string(&data.hide_procs).unwrap();
     ctx.insert("CHEAT_HIGHLIGHT_JSON", &cheat_json);
     ctx.insert("SYSTEM_HIDE_JSON", &system_json);
     match data.tera.render("submission/timeline_process.html", &ctx) {
@@ -52,128 +72,142 @@ pub async fn proc_timeline_fragment(
 #[get("/admin/submissions/{id}/proc_timeline.json")]
 pub async fn proc_timeline_json(
     _: Authorized,
+    session: Session,
     data: web::Data<crate::AppState>,
     path: web::Path<String>,
+    q: web::Query<ProcTimelineQuery>,
 ) -> impl Responder {
     let id = path.into_inner();
-
-    // open zip and palantir.log
-    let mut zip = match open_processed_zip_by_submission(&data, &id) {
-        Ok(z) => z,
-        Err(e) => return HttpResponse::InternalServerError().body(e),
-    };
-    
-    let mut log = match zip.by_name("snapshot/palantir.log") {
-        Ok(f) => BufReader::new(f),
-        Err(_) => return HttpResponse::Ok().json(Vec::<Point>::new()),
-    };
-
-    // parse events and build intervals
-    let mut line = String::new();
-    let mut open: HashMap<i32, (String, OffsetDateTime)> = HashMap::new(); // pid -> (comm, start)
-    let mut intervals_by_comm: HashMap<String, Vec<(OffsetDateTime, OffsetDateTime)>> = HashMap::new();
-    let mut global_min: Option<OffsetDateTime> = None;
-    let mut global_max: Option<OffsetDateTime> = None;
-
-    while let Ok(n) = log.read_line(&mut line) {
-        if n == 0 { break; }
-        if !line.contains("\"kind\":\"proc\"") { line.clear(); continue; }
-
-        let v: serde_json::Value = match serde_json::from_str(&line) {
-            Ok(x) => x, Err(_) => { line.clear(); continue; }
-        };
-        let ts = match v.get("ts").and_then(|x| x.as_str()) {
-            Some(s) => s, None => { line.clear(); continue; }
-        };
-        let action = v.get("action").and_then(|x| x.as_str()).unwrap_or("");
-        let pid    = v.get("pid").and_then(|x| x.as_i64()).unwrap_or(-1) as i32;
-        let comm   = v.get("comm").and_then(|x| x.as_str()).unwrap_or("").to_string();
-
-        let t = match OffsetDateTime::parse(ts, &Rfc3339) { Ok(t) => t, Err(_) => { line.clear(); continue; } };
-        global_min = Some(global_min.map_or(t, |m| m.min(t)));
-        global_max = Some(global_max.map_or(t, |m| m.max(t)));
-
-        match action {
-            "start" => { open.insert(pid, (comm, t)); }
-            "stop"  => {
-                if let Some((c, s)) = open.remove(&pid) {
-                    let e = t;
-                    intervals_by_comm.entry(c).or_default().push((s, e));
-                }
-            }
-            _ => {}
-        }
-
-        line.clear();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
+    let merge_gap_secs = q.merge_gap_secs.unwrap_or(DEFAULT_MERGE_GAP_SECS);
+    if !(0..=MAX_MERGE_GAP_SECS).contains(&merge_gap_secs) {
+        return HttpResponse::BadRequest().body(format!("merge_gap_secs must be between 0 and {MAX_MERGE_GAP_SECS}"));
     }
-
-    // close any dangling starts at global_max
-    if let Some(tmax) = global_max {
-        for (_pid, (c, s)) in open.drain() {
-            intervals_by_comm.entry(c).or_default().push((s, tmax));
-        }
+    let limit = q.limit.unwrap_or(DEFAULT_PROC_ROW_LIMIT);
+    if !(1..=MAX_PROC_ROW_LIMIT).contains(&limit) {
+        return HttpResponse::BadRequest().body(format!("limit must be between 1 and {MAX_PROC_ROW_LIMIT}"));
+    }
+    match proc_payload(&data, &id, merge_gap_secs, limit) {
+        Ok(Some(payload)) => HttpResponse::Ok().json(payload),
+        Ok(None) => HttpResponse::Ok().json(Vec::<Point>::new()),
+        Err(e) => HttpResponse::InternalServerError().body(e),
     }
+}
 
+/// Merged per-process run intervals (epoch ms) of a submission's log; `None` when the
+/// archive has no log. Runs less than `merge_gap_secs` apart are merged into one segment, and
+/// only the `limit` processes with the longest total runtime are kept.
+/// Shared by the JSON endpoint and the static timeline export.
+pub fn proc_payload(data: &crate::AppState, id: &str, merge_gap_secs: i64, limit: usize) -> Result<Option<ProcPayload>, String> {
+    // raw intervals were collected at analysis time (or parsed now for older submissions)
+    let Some(intervals) = derived::proc_intervals(data, id)? else {
+        return Ok(None);
+    };
+    Ok(Some(build_payload(intervals, merge_gap_secs, limit)))
+}
+
+fn build_payload(intervals: ProcIntervals, merge_gap_secs: i64, limit: usize) -> ProcPayload {
     // merge small gaps per comm and compute total duration
-    fn merge(mut ivals: Vec<(OffsetDateTime, OffsetDateTime)>) -> (Vec<(OffsetDateTime, OffsetDateTime)>, i128) {
-        ivals.sort_by_key(|x| x.0);
-        let mut out: Vec<(OffsetDateTime, OffsetDateTime)> = Vec::new();
-        let mut total_ms: i128 = 0;
-        let gap = time::Duration::seconds(5); // merge gaps less than 5s
-        for (s, e) in ivals {
+    // a merged segment is open-ended if any of its parts is
+    fn merge(mut ivals: Vec<ProcInterval>, gap_ms: i64) -> (Vec<ProcInterval>, i64) {
+        ivals.sort_by_key(|x| x.start);
+        let mut out: Vec<ProcInterval> = Vec::new();
+        let mut total_ms: i64 = 0;
+        for iv in ivals {
             if let Some(last) = out.last_mut() {
-                if s <= last.1 + gap {
-                    if e > last.1 { last.1 = e; }
+                if iv.start <= last.end + gap_ms {
+                    if iv.end > last.end { last.end = iv.end; }
+                    last.still_running |= iv.still_running;
                 } else {
-                    total_ms += (last.1 - last.0).whole_milliseconds();
-                    out.push((s, e));
+                    total_ms += last.end - last.start;
+                    out.push(iv);
                 }
             } else {
-                out.push((s, e));
+                out.push(iv);
             }
         }
         if let Some(last) = out.last() {
-            total_ms += (last.1 - last.0).whole_milliseconds();
+            total_ms += last.end - last.start;
         }
         (out, total_ms)
     }
 
     // rank by total duration and limit
-    let mut rows_tmp: Vec<(String, Vec<(OffsetDateTime, OffsetDateTime)>, i128)> = Vec::new();
-    for (comm, ivals) in intervals_by_comm {
-        let (merged, tot) = merge(ivals);
-        rows_tmp.push((comm, merged, tot));
+    let mut rows_tmp: Vec<(String, Vec<ProcInterval>, i64, usize)> = Vec::new();
+    for (comm, ivals) in intervals.by_comm {
+        let launches = ivals.len();
+        let (merged, tot) = merge(ivals, merge_gap_secs * 1000);
+        rows_tmp.push((comm, merged, tot, launches));
     }
-    rows_tmp.sort_by(|a, b| a.2.cmp(&b.2));
-    let limit = 500;
+    // longest running first, so the limit drops the short-lived noise
+    rows_tmp.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
     let rows_tmp = rows_tmp.into_iter().take(limit).collect::<Vec<_>>();
 
     // build payload
-    let local = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
-    let to_ms = |dt: OffsetDateTime| (dt.to_offset(local).unix_timestamp_nanos() / 1_000_000);
-
+    // epoch ms is offset independent; the browser renders local time per point
     let labels: Vec<String> = rows_tmp.iter().map(|x| x.0.clone()).collect();
     let mut rows: Vec<ProcRow> = Vec::new();
     let mut tmin_ms = i128::MAX;
     let mut tmax_ms = i128::MIN;
 
-    for (label, ivals, _) in rows_tmp {
+    for (label, ivals, _, launches) in rows_tmp {
         let mut segs: Vec<ProcSeg> = Vec::new();
-        for (s, e) in ivals {
-            let sm = to_ms(s);
-            let em = to_ms(e);
+        for iv in ivals {
+            let sm = iv.start as i128;
+            let em = iv.end as i128;
             if sm < tmin_ms { tmin_ms = sm; }
             if em > tmax_ms { tmax_ms = em; }
-            segs.push(ProcSeg { start: sm, end: em });
+            segs.push(ProcSeg { start: sm, end: em, still_running: iv.still_running });
         }
-        rows.push(ProcRow { label, segments: segs });
+        rows.push(ProcRow { label, segments: segs, launches });
     }
 
-    let payload = ProcPayload {
+    ProcPayload {
         labels,
         rows,
         tmin: if tmin_ms == i128::MAX { 0 } else { tmin_ms },
         tmax: if tmax_ms == i128::MIN { 0 } else { tmax_ms },
-    };
-    HttpResponse::Ok().json(payload)
-}
\ No newline at end of file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_keeps_the_longest_running_processes() {
+        // 600 processes; proc-<n> runs n seconds, names deliberately not in runtime order
+        let mut by_comm = std::collections::BTreeMap::new();
+        for n in 1..=600i64 {
+            let start = (n * 7919 % 600) * 1000;
+            by_comm.insert(
+                format!("proc-{:03}", (n * 37) % 600),
+                vec![ProcInterval { start, end: start + n * 1000, still_running: false }],
+            );
+        }
+        let runtime: std::collections::HashMap<String, i64> = by_comm
+            .iter()
+            .map(|(comm, iv)| (comm.clone(), iv[0].end - iv[0].start))
+            .collect();
+
+        let payload = build_payload(ProcIntervals { by_comm }, DEFAULT_MERGE_GAP_SECS, DEFAULT_PROC_ROW_LIMIT);
+        assert_eq!(payload.rows.len(), 500);
+        assert_eq!(payload.labels.len(), 500);
+
+        let kept: Vec<i64> = payload.labels.iter().map(|l| runtime[l]).collect();
+        // longest first, and exactly the 100 shortest (1..=100 s) dropped
+        assert!(kept.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(kept[0], 600_000);
+        assert_eq!(*kept.last().unwrap(), 101_000);
+    }
+
+    #[test]
+    fn limit_is_applied_as_given() {
+        let mut by_comm = std::collections::BTreeMap::new();
+        for (comm, secs) in [("bash", 30), ("firefox", 600), ("curl", 1)] {
+            by_comm.insert(comm.to_string(), vec![ProcInterval { start: 0, end: secs * 1000, still_running: false }]);
+        }
+        let payload = build_payload(ProcIntervals { by_comm }, 0, 2);
+        assert_eq!(payload.labels, ["firefox", "bash"]);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod page;
 pub mod get_timeline_network;
 pub mod get_timeline_process;
-pub mod get_artifacts;
\ No newline at end of file
+pub mod get_timeline_image;
+pub mod get_artifacts;
+pub mod reprocess;
+pub mod get_log;
\ No newline at end of file
@@ -1,18 +1,22 @@
+use actix_session::Session;
 use actix_web::{get, web, HttpResponse, Responder};
 
-use crate::{db, routes::auth::Authorized, template, AppState};
+use crate::{db, routes::{admin::{audit::audit, util::access::deny_submission}, auth::Authorized}, template, AppState};
 
 
 #[get("/admin/submissions/{id}")]
 pub async fn submission_page(
     _: Authorized,
+    session: Session,
     data: web::Data<AppState>,
     path: web::Path<String>,
 ) -> impl Responder {
     let id = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
 
     match db::get_submission_detail(&data.pool, &id) {
         Ok(Some(info)) => {
+            audit(&data, &session, "view_submission", &id);
             let logs = db::list_logs_for_submission(&data.pool, &id).unwrap_or_default();
             match template::submission_detail_page(&data.tera, &id, &info, &logs) {
                 Ok(html) => HttpResponse::Ok().body(html),
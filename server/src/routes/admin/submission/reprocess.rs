@@ -0,0 +1,27 @@
+use actix_session::Session;
+use actix_web::{post, web, HttpResponse, Responder};
+
+use crate::{db, routes::{admin::{audit::audit, submission::get_artifacts::render_artifacts, util::access::deny_submission}, auth::Authorized}, AppState};
+
+/// Drop the findings of one submission and queue it for the worker again, so it is
+/// analyzed with the current rules. The zip is read back from processed_dir.
+#[post("/admin/submissions/{id}/reprocess")]
+pub async fn reprocess_submission(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if let Some(resp) = deny_submission(&session, &data, &id) { return resp; }
+
+    match db::requeue_submission(&data.pool, &id) {
+        Ok(db::Requeue::Queued) => {
+            audit(&data, &session, "reprocess", &id);
+            render_artifacts(&data, &id, true)
+        }
+        Ok(db::Requeue::Busy) => HttpResponse::Conflict().body("submission is already queued for analysis"),
+        Ok(db::Requeue::Pruned) => HttpResponse::Conflict().body("the upload was removed by the retention policy, only its findings are kept"),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
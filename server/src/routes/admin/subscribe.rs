@@ -3,10 +3,24 @@ use actix_web::{post, web, HttpResponse, Responder};
 use serde::Deserialize;
 use time::OffsetDateTime;
 
-use crate::{db, routes::auth::Authorized, AppState};
+use crate::{db, routes::{admin::{assignment::provided_domains::parse_domains, audit::audit, util::consts::{profile_by_name, PROFILES}}, auth::Authorized}, AppState};
 
 #[derive(Deserialize)]
-pub struct SubForm { pub assignment_id: String }
+pub struct SubForm {
+    pub assignment_id: String,
+    // allowlisted domains; blank keeps the current list, "-" clears it
+    #[serde(default)]
+    pub allowlist: Option<String>,
+    // domains students are expected to visit, same rules
+    #[serde(default)]
+    pub expected: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkSubForm { pub assignment_ids: String }
+
+#[derive(Deserialize)]
+pub struct ProfileForm { pub assignment_id: String, pub profile: String }
 
 
 #[post("/admin/subscribe")]
@@ -21,13 +35,126 @@ pub async fn subscribe(
     let aid = form.assignment_id.trim().to_string();
     let now = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap();
 
+    // access comes from an admin grant (/admin/access), subscribing only adds it to the dashboard
+    if !db::prof_can_access(&data.pool, &prof, &aid).unwrap_or(false) {
+        return render_list(&data, &prof, |ctx| ctx.insert("denied", &[&aid]));
+    }
     let _ = db::subscribe(&data.pool, &prof, &aid, &now);
-    let subs = db::list_subscription_summaries(&data.pool, &prof).unwrap_or_default();
-    
+    audit(&data, &session, "subscribe", &aid);
+
+    let allowlist = update_domain_list(
+        form.allowlist.as_deref(),
+        || db::get_allowlist(&data.pool, &aid),
+        |domains| db::set_allowlist(&data.pool, &aid, domains),
+    );
+    let expected = update_domain_list(
+        form.expected.as_deref(),
+        || db::get_expected_domains(&data.pool, &aid),
+        |domains| db::set_expected_domains(&data.pool, &aid, domains),
+    );
+    match (allowlist, expected) {
+        (Err(e), _) | (_, Err(e)) => return HttpResponse::InternalServerError().body(e),
+        // findings were computed with the old lists
+        (Ok(a), Ok(x)) if a || x => {
+            if let Err(e) = db::requeue_assignment(&data.pool, &aid) {
+                return HttpResponse::InternalServerError().body(e);
+            }
+        }
+        _ => {}
+    }
+    render_list(&data, &prof, |_| {})
+}
+
+/// The dashboard's assignment list fragment, with whatever notices `extra` adds
+fn render_list(data: &AppState, prof: &str, extra: impl FnOnce(&mut tera::Context)) -> HttpResponse {
+    let subs = db::list_subscription_summaries(&data.pool, prof).unwrap_or_default();
+
     let mut ctx = tera::Context::new();
     ctx.insert("subs", &subs);
+    ctx.insert("profiles", &PROFILES);
+    extra(&mut ctx);
     match data.tera.render("dashboard/assignment_list.html", &ctx) {
         Ok(frag) => HttpResponse::Ok().body(frag),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
+}
+
+/// Store a posted domain list unless it is blank ("-" clears it); true when it changed
+fn update_domain_list(
+    raw: Option<&str>,
+    get: impl FnOnce() -> Result<Vec<String>, String>,
+    set: impl FnOnce(&[String]) -> Result<(), String>,
+) -> Result<bool, String> {
+    let raw = raw.unwrap_or("").trim();
+    if raw.is_empty() {
+        return Ok(false);
+    }
+    let domains = if raw == "-" { vec![] } else { parse_domains(raw) };
+    let current = get().unwrap_or_default();
+    let mut sorted = domains.clone();
+    sorted.sort();
+    if sorted == current {
+        return Ok(false);
+    }
+    set(&domains)?;
+    Ok(true)
+}
+
+#[post("/admin/subscription_profile")]
+pub async fn set_profile(
+    _: Authorized, 
+    session: Session,
+    data: web::Data<AppState>, 
+    form: web::Form<ProfileForm>
+) -> impl Responder {
+    
+    let prof = session.get::<String>("prof").unwrap().unwrap();
+    let aid = form.assignment_id.trim().to_string();
+    // only accept known profile names
+    let profile = profile_by_name(form.profile.trim());
+
+    if let Err(e) = db::set_subscription_profile(&data.pool, &prof, &aid, profile.name) {
+        return HttpResponse::InternalServerError().body(e);
+    }
+    audit(&data, &session, "set_profile", &format!("{aid}:{}", profile.name));
+    render_list(&data, &prof, |_| {})
+}
+
+/// Split a pasted list / CSV body into distinct, non-empty assignment ids
+pub fn parse_id_list(raw: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for id in raw.split(|c: char| c == ',' || c == ';' || c.is_whitespace()) {
+        let id = id.trim().trim_matches('"');
+        if id.is_empty() || out.iter().any(|x| x == id) { continue; }
+        out.push(id.to_string());
+    }
+    out
+}
+
+#[post("/admin/subscribe_bulk")]
+pub async fn subscribe_bulk(
+    _: Authorized, 
+    session: Session,
+    data: web::Data<AppState>, 
+    form: web::Form<BulkSubForm>
+) -> impl Responder {
+    
+    let prof = session.get::<String>("prof").unwrap().unwrap();
+    let (ids, denied): (Vec<String>, Vec<String>) = parse_id_list(&form.assignment_ids)
+        .into_iter()
+        .partition(|aid| db::prof_can_access(&data.pool, &prof, aid).unwrap_or(false));
+    let now = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap();
+
+    let (added, existing) = match db::subscribe_many(&data.pool, &prof, &ids, &now) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    if !ids.is_empty() {
+        audit(&data, &session, "subscribe", &ids.join(","));
+    }
+    render_list(&data, &prof, |ctx| {
+        ctx.insert("bulk_added", &added);
+        ctx.insert("bulk_existing", &existing);
+        ctx.insert("denied", &denied);
+    })
 }
\ No newline at end of file
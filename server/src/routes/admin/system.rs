@@ -0,0 +1,39 @@
+use actix_session::Session;
+use actix_web::{get, web, HttpResponse, Responder};
+use tera::Context;
+
+use crate::{db, routes::{admin::util::access::is_admin, auth::Authorized}, AppState};
+
+/// Worker health across all assignments (names the last processed student), so admins only
+#[get("/admin/system")]
+pub async fn system_page(
+    _: Authorized,
+    session: Session,
+    data: web::Data<AppState>
+) -> impl Responder {
+    if !is_admin(&session, &data) {
+        return HttpResponse::Forbidden().body("the system page is only visible to PAL_AUDIT_ADMINS");
+    }
+    let counts = match db::queue_counts(&data.pool) {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let count_of = |status: &str| counts.iter().find(|(s, _)| s == status).map(|(_, n)| *n).unwrap_or(0);
+
+    let last = db::last_processed(&data.pool).unwrap_or_default();
+    let avg_ms = db::avg_analysis_ms(&data.pool).unwrap_or_default();
+
+    let mut ctx = Context::new();
+    ctx.insert("received", &count_of("received"));
+    ctx.insert("processing", &count_of("processing"));
+    ctx.insert("failed", &count_of("failed"));
+    ctx.insert("counts", &counts);
+    ctx.insert("last", &last);
+    ctx.insert("avg_ms", &avg_ms.map(|v| v.round() as i64));
+    ctx.insert("errors", &data.worker.recent_errors());
+    ctx.insert("hide_procs", &data.hide_procs);
+    match data.tera.render("system/page.html", &ctx) {
+        Ok(html) => HttpResponse::Ok().body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
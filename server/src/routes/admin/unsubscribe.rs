@@ -2,7 +2,7 @@ use actix_session::Session;
 use actix_web::{post, web, HttpResponse, Responder};
 
 
-use crate::{db, routes::{admin::subscribe::SubForm, auth::Authorized}, AppState};
+use crate::{db, routes::{admin::{audit::audit, subscribe::SubForm, util::consts::PROFILES}, auth::Authorized}, AppState};
 
 #[post("/admin/unsubscribe")]
 pub async fn unsubscribe(
@@ -16,10 +16,12 @@ pub async fn unsubscribe(
     let aid = form.assignment_id.trim().to_string();
 
     let _ = db::unsubscribe(&data.pool, &prof, &aid);
+    audit(&data, &session, "unsubscribe", &aid);
     let subs = db::list_subscription_summaries(&data.pool, &prof).unwrap_or_default();
 
     let mut ctx = tera::Context::new();
     ctx.insert("subs", &subs);
+    ctx.insert("profiles", &PROFILES);
     match data.tera.render("dashboard/assignment_list.html", &ctx) {
         Ok(frag) => HttpResponse::Ok().body(frag),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
@@ -0,0 +1,27 @@
+use actix_session::Session;
+use actix_web::HttpResponse;
+
+use crate::{db, AppState};
+
+fn session_prof(session: &Session) -> Option<String> {
+    session.get::<String>("prof").ok().flatten()
+}
+
+/// Whether the logged in professor is one of PAL_AUDIT_ADMINS (audit log, access grants)
+pub fn is_admin(session: &Session, data: &AppState) -> bool {
+    session_prof(session).is_some_and(|prof| data.audit_admins.iter().any(|a| a == &prof))
+}
+
+/// `Some(403)` unless an admin granted the logged in professor access to assignment `aid`
+pub fn deny_assignment(session: &Session, data: &AppState, aid: &str) -> Option<HttpResponse> {
+    let allowed = session_prof(session)
+        .is_some_and(|prof| db::prof_can_access(&data.pool, &prof, aid).unwrap_or(false));
+    (!allowed).then(|| HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it"))
+}
+
+/// `Some(403)` unless the submission `id` belongs to an assignment the professor has access to
+pub fn deny_submission(session: &Session, data: &AppState, id: &str) -> Option<HttpResponse> {
+    let allowed = session_prof(session)
+        .is_some_and(|prof| db::prof_can_access_submission(&data.pool, &prof, id).unwrap_or(false));
+    (!allowed).then(|| HttpResponse::Forbidden().body("no access to this assignment, ask an administrator to grant it"))
+}
@@ -1,3 +1,8 @@
+// domain tables and the AI classifier live in palantir_core, shared with the desktop and collector
+pub use palantir_core::domains::{
+    ai_provider, base_domain_guess, is_ai_domain, CLOUD_BASES, CODE_HOST_BASES, DOH_HOSTS, PKG_BASES, QNA_BASES, SEARCH_BASES,
+};
+
 /// Process name buckets
 pub const BROWSERS: &[&str] = &[
     "firefox", 
@@ -39,6 +44,20 @@ pub const REMOTE_TOOLS: &[&str] = &[
     "signal",
 ];
 
+/// Screen mirroring, input sharing and phone/clipboard sync; ways to read answers off another device
+pub const SCREEN_SHARE_TOOLS: &[&str] = &[
+    "kdeconnect",
+    "gsconnect",
+    "scrcpy",
+    "barrier",
+    "synergy",
+    "input-leap",
+    "parsec",
+    "sunshine",
+    "moonlight",
+    "deskreen",
+];
+
 pub const SSH_LIKE: &[&str] = &[
     "ssh", 
     "scp", 
@@ -60,113 +79,59 @@ pub const DOWNLOAD_TOOLS: &[&str] = &[
     "pacman",
 ];
 
-/// Domain categories used in analysis
-pub const SEARCH_BASES: &[&str] = &[
-    "google.com", 
-    "bing.com", 
-    "duckduckgo.com"
-];
-
-pub const QNA_BASES: &[&str] = &[
-    "stackoverflow.com", 
-    "stackexchange.com"
-];
-pub const CODE_HOST_BASES: &[&str] = &[
-    "github.com", 
-    "gitlab.com", 
-    "bitbucket.org"
-];
-
-pub const PKG_BASES: &[&str] = &[
-    "pypi.org",
-    "pythonhosted.org",
-    "npmjs.com",
-    "registry.npmjs.org",
-    "crates.io",
-];
-
-pub const CLOUD_BASES: &[&str] = &[
-    "drive.google.com",
-    "dropbox.com",
-    "mega.nz",
-    "wetransfer.com",
-    "pastebin.com",
-    "hastebin.com",
-    "ghostbin.com",
+// capture quality thresholds
+pub const MONITORING_GAP_SECONDS: i64 = 300;          // no events at all for this long -> collector probably stopped
+pub const HEARTBEAT_MISSED_FACTOR: i64 = 3;           // heartbeats this many times their usual spacing apart -> outage
+pub const DOH_MIN_BROWSER_SECONDS: i64 = 600;         // browser ran at least this long ...
+pub const DOH_MAX_NET_EVENTS: usize = 10;             // ... but produced at most this many DNS events
+
+/// Packages that wrap an AI service or model runtime; installing one mid-exam is a strong signal.
+/// Names are compared after lowercasing and `_` -> `-`; entries ending in '-' match as prefix.
+pub const AI_SDK_PACKAGES: &[&str] = &[
+    "openai",
+    "anthropic",
+    "@anthropic-ai/sdk",
+    "google-generativeai",
+    "google-genai",
+    "@google/generative-ai",
+    "@google/genai",
+    "mistralai",
+    "@mistralai/mistralai",
+    "cohere",
+    "groq",
+    "groq-sdk",
+    "ollama",
+    "litellm",
+    "g4f",
+    "gpt4all",
+    "huggingface-hub",
+    "transformers",
+    "llama-index",
+    "llama-cpp-python",
+    "langchain",
+    "langchain-",
+    "@langchain/",
+    "replicate",
+    "together",
 ];
 
-/// AI provider domains. Prefer matching by base domain.
-/// Keep tight to reduce false positives.
-pub const AI_PROVIDER_BASES: &[&str] = &[
-    // OpenAI
-    "openai.com",
-    "chatgpt.com",
-
-    // Anthropic
-    "anthropic.com",
-    "claude.ai",
-
-    // Google
-    "ai.google",
-    "deepmind.com",
-    "gemini.google.com",
-
-    // Microsoft
-    "cognitive.microsoft.com",
-    "githubcopilot.com",
-    "openai.azure.com",
-    "azureopenai.com",
-
-    // Meta
-    "ai.meta.com",
-    "llama.meta.com",
-
-    // Others
-    "mistral.ai",
-    "cohere.ai",
-    "stability.ai",
-    "ai21.com",
-    "perplexity.ai",
-    "huggingface.co",
-    "replicate.com",
-    "runpod.io",
-    "openrouter.ai",
-    "poe.com",
-    "x.ai",
-    "you.com",
-    "character.ai",
-    "elevenlabs.io",
-    "jasper.ai",
-    "writesonic.com",
-    "copy.ai",
-    "rytr.me",
-    "forefront.ai",
-    "midjourney.com",
-    "replit.com",
+/// Install locations a well-known tool's executable is expected to live under.
+/// A known name (e.g. "firefox") running from anywhere else is treated as masquerading.
+pub const EXPECTED_EXE_PREFIXES: &[&str] = &[
+    "/usr/",
+    "/bin/",
+    "/sbin/",
+    "/lib/",
+    "/lib64/",
+    "/opt/",
+    "/snap/",
+    "/var/lib/flatpak/",
+    "/var/lib/snapd/",
+    "/app/",
 ];
 
-
-/// Private IPv4 prefixes checked by simple starts_with
-pub const PRIVATE_IPV4_PREFIXES: &[&str] = &[
-    "10.", 
-    "192.168.",
-    "172.16.", 
-    "172.17.", 
-    "172.18.", 
-    "172.19.", 
-    "172.20.", 
-    "172.21.", 
-    "172.22.",
-    "172.23.", 
-    "172.24.", 
-    "172.25.", 
-    "172.26.", 
-    "172.27.", 
-    "172.28.", 
-    "172.29.",
-    "172.30.", 
-    "172.31.",
-];
+/// Loopback sources counted for loopback_dominated (the collector logs the local resolver as one of these)
+pub const LOOPBACK_ADDRS: &[&str] = &["127.0.0.1", "::1"];
 
 /// Severity strings
 pub const KIND_META: &str    = "meta";
@@ -174,6 +139,10 @@ pub const KIND_PROC: &str    = "proc";
 pub const KIND_NET: &str     = "net";
 pub const KIND_ANOMALY: &str = "anomaly";
 
+/// Card severities, lowest to highest. Any anomaly finding raises a card to "anomaly",
+/// an AI provider visit to "critical".
+pub const SEVERITY_ORDER: &[&str] = &["info", "anomaly", "critical"];
+
 // ---- Finding keys ----
 // --- metadata about submission / session ---
 pub const FK_ZIP_NAME: &str         = "zip_name";          // original uploaded archive filename
@@ -182,16 +151,36 @@ pub const FK_LAST_TS: &str          = "last_ts";           // timestamp of last
 pub const FK_DURATION_MINUTES: &str = "duration_minutes";  // total observed session duration in minutes
 pub const FK_MAX_IDLE_SECONDS: &str = "max_idle_seconds";  // longest inactivity gap (seconds)
 pub const FK_SEAT_IP: &str          = "seat_ip";           // most common private LAN IP used
-pub const FK_DEVICE_KEY: &str       = "device_key";        // device identity key (currently equals seat_ip)
+pub const FK_DEVICE_KEY: &str       = "device_key";        // device identity key (machine id hash, see compute_device_key)
+pub const FK_ANALYSIS_MS: &str      = "analysis_ms";       // wall time the background worker spent analyzing the archive
+pub const FK_LOG_FORMAT_VERSION: &str = "log_format_version"; // palantir.log format the collector declared (absent for older collectors)
+pub const FK_MANIFEST_PARSE_ERROR: &str = "manifest_parse_error"; // why manifest.json was missing / unreadable / malformed
+pub const FK_STUDENT_NOTE: &str     = "student_note";      // free-text explanation the student attached to the upload
+pub const FK_RISK_SCORE: &str       = "risk_score";        // weighted 0-100 risk over several findings (see risk::compute_risk_score)
+pub const FK_RISK_FACTOR: &str      = "risk_factor";       // points one finding contributed to risk_score (key:points)
+pub const FK_FILE_COUNT: &str       = "file_count";        // number of files listed in the manifest
+pub const FK_FILE_HASH: &str        = "file_hash";         // one submitted file (sha256:name)
+pub const FK_FILES_SHA256: &str     = "files_sha256";      // sha256 over the sorted file hashes; equal for identical file sets
+
+/// sha256 of zero bytes; empty files match across every submission and are not evidence of copying
+pub const EMPTY_FILE_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+pub const STUDENT_NOTE_MAX_CHARS: usize = 2000;
 
 // --- process activity metrics ---
 pub const FK_TOTAL_PROC_STARTS: &str        = "total_proc_starts";        // total number of process start events
 pub const FK_TOTAL_PROC_STOPS: &str         = "total_proc_stops";         // total number of process stop events
+pub const FK_STILL_RUNNING_AT_END: &str     = "still_running_at_end";     // processes started but never stopped before the log ended
 pub const FK_TOP_PROC: &str                 = "top_proc";                 // most frequently started processes (name:count)
 pub const FK_BROWSER_RUNTIME_SECONDS: &str  = "browser_runtime_seconds";  // cumulative runtime of browser processes
 pub const FK_HAD_BROWSER: &str              = "had_browser";              // whether a browser was ever launched
 pub const FK_SHELL_INVOCATIONS: &str        = "shell_invocations";        // number of shell/terminal launches
 pub const FK_EXTERNAL_DOWNLOAD_TOOL_COUNT: &str = "external_download_tool_count"; // usage count of tools like curl/wget/npm/etc
+pub const FK_RESTART_MERGE_SECONDS: &str    = "restart_merge_seconds";    // window used to merge rapid restarts (RESTART_MERGE_SECONDS)
+pub const FK_SHELL_STARTS_RAW: &str         = "shell_starts_raw";         // shell starts before merging rapid restarts
+pub const FK_DOWNLOAD_TOOL_STARTS_RAW: &str = "download_tool_starts_raw"; // download tool starts before merging rapid restarts
+pub const FK_INSTALLED_PACKAGE: &str        = "installed_package";        // package named in an install command (manager:package)
+pub const FK_AI_SDK_INSTALLED: &str         = "ai_sdk_installed";         // number of distinct AI SDK packages installed (see AI_SDK_PACKAGES)
 
 // --- network activity metrics ---
 pub const FK_TOTAL_NET_EVENTS: &str         = "total_net_events";         // total number of network events
@@ -199,16 +188,48 @@ pub const FK_REQUESTS_PER_MIN: &str         = "requests_per_min";         // ave
 pub const FK_UNIQUE_DOMAINS: &str           = "unique_domains";           // number of distinct domains contacted
 pub const FK_TOP_DOMAIN: &str               = "top_domain";               // most contacted domains (base:count)
 pub const FK_TOP_SRC_IP: &str               = "top_src_ip";               // most active local source IPs
-pub const FK_AI_DOMAIN: &str                = "ai_domain";                // contacted AI service domains (base:count)
+pub const FK_PRIVATE_IP: &str               = "private_ip";               // every distinct private source IP seen (ip:count)
+pub const FK_DISTINCT_PRIVATE_IPS: &str     = "distinct_private_ips";     // number of distinct private source IPs (multi-homing / roaming)
+pub const FK_AI_DOMAIN: &str                = "ai_domain";                // contacted AI service domains (provider:count)
+pub const FK_AI_DOMAIN_PROCESS: &str        = "ai_domain_process";        // process that looked up an AI domain (comm:base:count), when the collector attributes DNS
 pub const FK_BURST_MAX_EVENTS_PER_MIN: &str = "burst_max_events_per_min"; // peak number of events in a single minute
 pub const FK_FINAL5_NET_EVENTS: &str        = "final5_net_events";        // number of network events in final 5 minutes
+pub const FK_BURST_WINDOW_SECONDS: &str     = "burst_window_seconds";     // window size used for burst_peak_events (BURST_WINDOW_SECONDS)
+pub const FK_BURST_PEAK_EVENTS: &str        = "burst_peak_events";        // peak number of events in any sliding window of that size
+pub const FK_FINAL_WINDOW_MINUTES: &str     = "final_window_minutes";     // window size used for final_window_net_events (FINAL_WINDOW_MINUTES)
+pub const FK_FINAL_WINDOW_NET_EVENTS: &str  = "final_window_net_events";  // number of network events in that final window
 
 // --- anomaly flags ---
 pub const FK_REMOTE_COLLAB_TOOL_SEEN: &str = "remote_collab_tool_seen"; // detected remote desktop / collab software
 pub const FK_SSH_ACTIVITY: &str            = "ssh_activity";            // detected SSH/SCP/SFTP/Mosh usage
+pub const FK_SCREEN_SHARE_SEEN: &str       = "screen_share_seen";       // detected screen mirroring / clipboard sync tool (see SCREEN_SHARE_TOOLS)
 pub const FK_AI_HITS_TOTAL: &str           = "ai_hits_total";           // total number of AI-related network events
 pub const FK_AI_RATIO_PERCENT: &str        = "ai_ratio_percent";        // % of AI events relative to all DNS queries
-pub const FK_LOOPBACK_DOMINATED: &str      = "loopback_dominated";      // >80% of traffic stayed on localhost (127.0.0.1)
+pub const FK_AI_DURING_BROWSER: &str       = "ai_during_browser";       // AI events that fell inside a browser-running interval
+
+/// AI usage findings; they don't raise severity when every AI domain hit is exam-provided
+pub const AI_USAGE_KEYS: &[&str] = &[FK_AI_HITS_TOTAL, FK_AI_RATIO_PERCENT, FK_AI_DURING_BROWSER];
+pub const FK_LOOPBACK_DOMINATED: &str      = "loopback_dominated";      // >80% of traffic stayed on localhost (127.0.0.1 / ::1)
+pub const FK_SUSPICIOUS_EXE_PATH: &str     = "suspicious_exe_path";     // known tool name running from an unexpected path (comm=path)
+pub const FK_AI_SDK_WITH_AI_TRAFFIC: &str  = "ai_sdk_with_ai_traffic";  // AI SDK installed and AI domains contacted in the same session (packages)
+
+// --- capture quality (degraded monitoring, see CAPTURE_QUALITY_KEYS) ---
+pub const FK_NO_PROCESS_DATA: &str         = "no_process_data";         // log contains no process events
+pub const FK_NO_NET_DATA: &str             = "no_net_data";             // log contains no network events
+pub const FK_MONITORING_GAP: &str          = "monitoring_gap";          // longest silence in seconds, when above MONITORING_GAP_SECONDS
+pub const FK_MONITORING_GAPS: &str         = "monitoring_gaps";         // spans with missing collector heartbeats (logs with heartbeats only)
+pub const FK_ENCRYPTED_DNS_SUSPECTED: &str = "encrypted_dns_suspected"; // DoH/DoT resolver seen, or long browser use with almost no DNS
+pub const FK_LOG_MISSING: &str              = "log_missing";             // client found no collector log and shipped snapshot/MISSING_LOG.txt (its text)
+
+/// Findings meaning the capture itself can't be trusted; a submission with any of them is "degraded"
+pub const CAPTURE_QUALITY_KEYS: &[&str] = &[
+    FK_LOOPBACK_DOMINATED,
+    FK_NO_PROCESS_DATA,
+    FK_NO_NET_DATA,
+    FK_MONITORING_GAP,
+    FK_ENCRYPTED_DNS_SUSPECTED,
+    FK_LOG_MISSING,
+];
 
 // --- categorized domain hits ---
 pub const FK_QNA_HITS: &str        = "qna_hits";        // visits to Q&A sites (StackOverflow, StackExchange, etc.)
@@ -216,6 +237,9 @@ pub const FK_CODE_HOST_HITS: &str  = "code_host_hits";  // visits to code hostin
 pub const FK_SEARCH_HITS: &str     = "search_hits";     // visits to search engines (Google, Bing, DuckDuckGo)
 pub const FK_PKG_HITS: &str        = "pkg_hits";        // requests to package registries (PyPI, npm, crates.io, etc.)
 pub const FK_CLOUD_HITS: &str      = "cloud_hits";      // uploads/downloads to cloud storage/file sharing services
+pub const FK_ALLOWLISTED_HITS: &str = "allowlisted_hits"; // requests to the assignment's allowlisted domains, never counted as AI
+pub const FK_UNEXPECTED_DOMAIN_COUNT: &str = "unexpected_domain_count"; // lookups outside the assignment's expected domains (only when it has some)
+pub const FK_UNEXPECTED_DOMAIN: &str = "unexpected_domain"; // most looked-up unexpected base domains (base:count)
 
 
 // Which keys can be filtered as numbers (CAST(value AS INTEGER))
@@ -227,22 +251,39 @@ pub const ALLOWED_KEYS_NUM: &[&str] = &[
     FK_UNIQUE_DOMAINS,
     FK_BURST_MAX_EVENTS_PER_MIN,
     FK_FINAL5_NET_EVENTS,
+    FK_BURST_PEAK_EVENTS,
+    FK_FINAL_WINDOW_NET_EVENTS,
+    FK_DISTINCT_PRIVATE_IPS,
+    FK_MONITORING_GAPS,
 
     // proc counts
     FK_TOTAL_PROC_STARTS,
     FK_TOTAL_PROC_STOPS,
+    FK_STILL_RUNNING_AT_END,
     FK_BROWSER_RUNTIME_SECONDS,
     FK_SHELL_INVOCATIONS,
     FK_EXTERNAL_DOWNLOAD_TOOL_COUNT,
+    FK_SHELL_STARTS_RAW,
+    FK_DOWNLOAD_TOOL_STARTS_RAW,
+    FK_AI_SDK_INSTALLED,
 
     // AI / categories
     FK_AI_HITS_TOTAL,
     FK_AI_RATIO_PERCENT,   // percent stored as number
+    FK_AI_DURING_BROWSER,
     FK_QNA_HITS,
     FK_CODE_HOST_HITS,
     FK_SEARCH_HITS,
     FK_PKG_HITS,
     FK_CLOUD_HITS,
+    FK_ALLOWLISTED_HITS,
+    FK_UNEXPECTED_DOMAIN_COUNT,
+
+    // submitted files
+    FK_FILE_COUNT,
+
+    // overall
+    FK_RISK_SCORE,
 ];
 
 // Which keys can be filtered as booleans (value ~ true/false/1/0/yes/no)
@@ -250,6 +291,10 @@ pub const ALLOWED_KEYS_BOOL: &[&str] = &[
     FK_HAD_BROWSER,
     FK_REMOTE_COLLAB_TOOL_SEEN,
     FK_SSH_ACTIVITY,
+    FK_SCREEN_SHARE_SEEN,
+    FK_NO_PROCESS_DATA,
+    FK_NO_NET_DATA,
+    FK_ENCRYPTED_DNS_SUSPECTED,
     // (loopback_dominated is "X/Y" string -> not boolean)
 ];
 
@@ -257,6 +302,81 @@ pub const ALLOWED_KEYS_BOOL: &[&str] = &[
 pub const ALLOWED_OPS: &[&str] = &["gt","ge","eq","le","lt","ne","exists"];
 
 
+/// Integrity policy of an assignment: which findings count as violations.
+/// A finding is a violation when its key is listed and its value is truthy (non-zero / "true").
+#[derive(serde::Serialize, Debug)]
+pub struct SensitivityProfile {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub violations: &'static [&'static str],
+}
+
+impl SensitivityProfile {
+    pub fn counts(&self, key: &str) -> bool {
+        self.violations.contains(&key)
+    }
+}
+
+/// The default, and how every assignment was judged before profiles existed: only AI use is critical
+pub const PROFILE_AI_ONLY: SensitivityProfile = SensitivityProfile {
+    name: "ai-only",
+    label: "AI only (everything but AI allowed)",
+    violations: &[FK_AI_HITS_TOTAL],
+};
+
+pub const PROFILE_CLOSED: SensitivityProfile = SensitivityProfile {
+    name: "closed",
+    label: "Closed (nothing allowed)",
+    violations: &[
+        FK_AI_HITS_TOTAL,
+        FK_SEARCH_HITS,
+        FK_QNA_HITS,
+        FK_CODE_HOST_HITS,
+        FK_PKG_HITS,
+        FK_CLOUD_HITS,
+        FK_REMOTE_COLLAB_TOOL_SEEN,
+        FK_SCREEN_SHARE_SEEN,
+        FK_SSH_ACTIVITY,
+        FK_EXTERNAL_DOWNLOAD_TOOL_COUNT,
+    ],
+};
+
+pub const PROFILE_OPEN_BOOK: SensitivityProfile = SensitivityProfile {
+    name: "open-book",
+    label: "Open book (search and docs allowed, no AI)",
+    violations: &[
+        FK_AI_HITS_TOTAL,
+        FK_CLOUD_HITS,
+        FK_REMOTE_COLLAB_TOOL_SEEN,
+        FK_SCREEN_SHARE_SEEN,
+        FK_SSH_ACTIVITY,
+    ],
+};
+
+pub const PROFILE_CODING: SensitivityProfile = SensitivityProfile {
+    name: "coding",
+    label: "Coding (search, Q&A, code hosts, packages allowed, no AI)",
+    violations: &[
+        FK_AI_HITS_TOTAL,
+        FK_REMOTE_COLLAB_TOOL_SEEN,
+        FK_SCREEN_SHARE_SEEN,
+        FK_SSH_ACTIVITY,
+    ],
+};
+
+pub const PROFILES: &[&SensitivityProfile] = &[&PROFILE_AI_ONLY, &PROFILE_CLOSED, &PROFILE_OPEN_BOOK, &PROFILE_CODING];
+pub const DEFAULT_PROFILE: &str = "ai-only";
+
+/// Look up a profile by name, falling back to the default (AI only) one
+pub fn profile_by_name(name: &str) -> &'static SensitivityProfile {
+    PROFILES
+        .iter()
+        .copied()
+        .find(|p| p.name == name)
+        .unwrap_or(&PROFILE_AI_ONLY)
+}
+
+
 /// System-ish noise we hide by default in the process timeline.
 pub const SYSTEM_HIDE_PROCS: &[&str] = &[
     "(sd-pam)",
@@ -313,9 +433,7 @@ pub const SYSTEM_HIDE_PROCS: &[&str] = &[
     "evolution-source-reg",
     "evolution-alarm-notify",
     "evolution",
-    "localsearch-3",
     "localsearch-ext",
-    "localsearch",
     "evolution-",
     "desktop-launch",
     "apport",
@@ -325,22 +443,14 @@ pub const SYSTEM_HIDE_PROCS: &[&str] = &[
     "build-script-builder",
     "rdd",
     "cpuusage.sh",
-    "rdd",
     "socket",
     "forkserver",
-    "xwayland",
-    "mutter",
     "mutter-x11-fram",
-    "gjs",
     "gdm-wayland-ses",
-    "gdm-session-worker",
-    "dconf-service",
-    "goa-daemon",
     "pressure-vessel",
     "srt-bwrap",
     "srt-logger",
     "goa-identity-se",
-    "mpris-proxy",
     "ld",
     "collect2",
     "vsce-sign",
@@ -350,6 +460,19 @@ pub const SYSTEM_HIDE_PROCS: &[&str] = &[
 
 ];
 
+/// SYSTEM_HIDE_PROCS plus site-specific additions (comma separated, e.g. HIDE_PROCS_EXTRA),
+/// without duplicates and in the original order.
+pub fn effective_hide_procs(extra: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let extra = extra.split(',').map(str::trim).filter(|s| !s.is_empty());
+    for name in SYSTEM_HIDE_PROCS.iter().copied().chain(extra) {
+        if !out.iter().any(|n| n == name) {
+            out.push(name.to_string());
+        }
+    }
+    out
+}
+
 /// Procs that carry cheating risk; highlight them in UI and raise attention in analysis.
 pub const CHEAT_HIGHLIGHT_PROCS: &[&str] = &[
     "tunnel",
@@ -431,31 +554,101 @@ pub const CHEAT_HIGHLIGHT_PROCS: &[&str] = &[
     "cloud-drive-",
     "steam-runtime-l",
     "steamwebhelper",
+    // SCREEN_SHARE_TOOLS
+    "kdeconnect",
+    "kdeconnectd",
+    "gsconnect",
+    "scrcpy",
+    "barrier",
+    "barriers",
+    "barrierc",
+    "synergy",
+    "input-leap",
+    "parsec",
+    "sunshine",
+    "moonlight",
+    "deskreen",
 ];
 
 /// Minimum percentile a submission must exceed to be considered an outlier in net events.
 /// Example: 95.0 means "flag anything >= 95th percentile".
 pub const OUTLIER_MIN_FLAG_PERCENTILE: i32 = 75;
 
-/// Small helper: guess base domain by stripping left-most label
-pub fn base_domain_guess(host: &str) -> String {
-    let mut parts: Vec<&str> = host.split('.').filter(|s| !s.is_empty()).collect();
-    if parts.len() >= 2 {
-        let last = parts.pop().unwrap();
-        let prev = parts.pop().unwrap();
-        format!("{prev}.{last}")
-    } else {
-        host.to_ascii_lowercase()
-    }
-}
-
 /// Helpers for proc name matching
 pub fn name_is_in(name: &str, set: &[&str]) -> bool {
     let c = name.to_ascii_lowercase();
     set.iter().any(|b| c == *b || c.contains(b) || c.ends_with(&format!("/{b}")) || c.starts_with(&format!("{b} ")))
 }
 
-/// Simple private IPv4 check
-pub fn is_private_ipv4(ip: &str) -> bool {
-    PRIVATE_IPV4_PREFIXES.iter().any(|p| ip.starts_with(p))
+/// Exact (not substring) match against the well-known tool buckets above
+pub fn is_known_tool_name(name: &str) -> bool {
+    let n = name.to_ascii_lowercase();
+    [BROWSERS, SHELLS, REMOTE_TOOLS, SCREEN_SHARE_TOOLS, SSH_LIKE, DOWNLOAD_TOOLS]
+        .iter()
+        .any(|set| set.contains(&n.as_str()))
+}
+
+/// True when an executable path lies outside the expected install locations
+pub fn is_unexpected_exe_path(path: &str) -> bool {
+    if path.contains("/.local/share/flatpak/") {
+        return false;
+    }
+    !EXPECTED_EXE_PREFIXES.iter().any(|p| path.starts_with(p))
+}
+
+/// Package name (version / extras stripped) is one of AI_SDK_PACKAGES
+pub fn is_ai_sdk_package(name: &str) -> bool {
+    let n = name.to_ascii_lowercase().replace('_', "-");
+    AI_SDK_PACKAGES.iter().any(|p| {
+        if p.ends_with('-') || p.ends_with('/') { n.starts_with(p) } else { n == *p }
+    })
+}
+
+/// Source address as logged: IPv4 or IPv6, an optional `%zone` suffix dropped and
+/// IPv4-mapped IPv6 (`::ffff:10.0.0.5`) read as the IPv4 address
+pub fn parse_ip(ip: &str) -> Option<std::net::IpAddr> {
+    use std::net::IpAddr;
+    let ip = ip.trim();
+    let ip = ip.split('%').next().unwrap_or(ip);
+    match ip.parse::<IpAddr>().ok()? {
+        IpAddr::V6(v6) => Some(v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(v6))),
+        v4 => Some(v4),
+    }
+}
+
+/// LAN address: RFC 1918 IPv4 (10/8, 172.16/12, 192.168/16), IPv6 unique local (fc00::/7)
+/// or link-local (fe80::/10)
+pub fn is_private_ip(ip: &str) -> bool {
+    use std::net::IpAddr;
+    match parse_ip(ip) {
+        Some(IpAddr::V4(v4)) => v4.is_private(),
+        Some(IpAddr::V6(v6)) => {
+            let first = v6.segments()[0];
+            (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn system_hide_procs_has_no_duplicates() {
+        let mut seen = HashSet::new();
+        for name in SYSTEM_HIDE_PROCS {
+            assert!(seen.insert(*name), "{name} is listed twice");
+        }
+        assert_eq!(effective_hide_procs("").len(), SYSTEM_HIDE_PROCS.len());
+    }
+
+    #[test]
+    fn extra_hide_procs_are_merged() {
+        let merged = effective_hide_procs(" slack, , mutter,slack,zoom ");
+        // built-ins keep their place, new names follow in the order given, each once
+        assert_eq!(&merged[..SYSTEM_HIDE_PROCS.len()], SYSTEM_HIDE_PROCS);
+        assert_eq!(&merged[SYSTEM_HIDE_PROCS.len()..], ["slack", "zoom"]);
+    }
 }
@@ -0,0 +1,128 @@
+use actix_web::HttpRequest;
+use url::form_urlencoded;
+
+use crate::{routes::admin::util::consts::{FK_DURATION_MINUTES, FK_TOTAL_NET_EVENTS}, AppState};
+
+// Trivial/test submissions are those whose duration or net event count is below
+// HIDE_MIN_DURATION_MINUTES / HIDE_MIN_NET_EVENTS. The submission table and every
+// assignment statistic leave them out unless `show_hidden` is ticked.
+
+/// SQL predicate (over `s`) matching hidden submissions, None when hiding is off.
+/// Unprocessed submissions have no findings yet and never match. The thresholds are
+/// numbers from the config and the keys constants, so they are written inline and the
+/// predicate can be appended to queries with positional parameters.
+pub fn hidden_predicate(data: &AppState) -> Option<String> {
+    let below = |key: &str, min: i64| format!(
+        "EXISTS (SELECT 1 FROM findings hf
+                  WHERE hf.submission_ref = s.id AND hf.key = '{key}'
+                    AND hf.value GLOB '[0-9]*' AND CAST(hf.value AS INTEGER) < {min})"
+    );
+    let mut parts: Vec<String> = Vec::new();
+    if data.hide_min_duration_minutes > 0 {
+        parts.push(below(FK_DURATION_MINUTES, data.hide_min_duration_minutes));
+    }
+    if data.hide_min_net_events > 0 {
+        parts.push(below(FK_TOTAL_NET_EVENTS, data.hide_min_net_events));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(format!("({})", parts.join(" OR ")))
+}
+
+/// `" AND NOT <hidden>"` to append to a WHERE over `s`; empty when showing hidden ones
+pub fn visible_clause(data: &AppState, show_hidden: bool) -> String {
+    match hidden_predicate(data) {
+        Some(pred) if !show_hidden => format!(" AND NOT {pred}"),
+        _ => String::new(),
+    }
+}
+
+/// The `show_hidden` toggle of the assignment page
+pub fn show_hidden(req: &HttpRequest) -> bool {
+    form_urlencoded::parse(req.query_string().as_bytes())
+        .any(|(k, v)| k == "show_hidden" && is_on(&v))
+}
+
+pub fn is_on(v: &str) -> bool {
+    matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "on" | "yes")
+}
+
+/// Submissions of the assignment that are hidden; the statistics never count test uploads
+pub fn count_hidden(conn: &rusqlite::Connection, data: &AppState, aid: &str, with_tests: bool) -> i64 {
+    let Some(pred) = hidden_predicate(data) else { return 0; };
+    let tests = if with_tests { "" } else { " AND s.is_test = 0" };
+    let sql = format!("SELECT COUNT(*) FROM submissions s WHERE s.submission_id = ?1{tests} AND {pred}");
+    conn.query_row(&sql, [aid], |r| r.get(0)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn state(min_duration: i64, min_net: i64) -> AppState {
+        let mut data = crate::test_state("");
+        data.hide_min_duration_minutes = min_duration;
+        data.hide_min_net_events = min_net;
+        data
+    }
+
+    fn seed(conn: &rusqlite::Connection) {
+        // (id, duration, net events); "pending" has no findings yet
+        for (id, duration, net) in [("short", Some(2), Some(500)), ("quiet", Some(90), Some(3)), ("full", Some(90), Some(500)), ("pending", None, None)] {
+            conn.execute(
+                "INSERT INTO submissions (id, submission_id, student_name, created_at, status, is_test)
+                 VALUES (?1, 'a1', ?1, '2024-01-01T00:00:00Z', 'processed', 0)",
+                [id],
+            ).unwrap();
+            for (key, v) in [(FK_DURATION_MINUTES, duration), (FK_TOTAL_NET_EVENTS, net)] {
+                if let Some(v) = v {
+                    conn.execute(
+                        "INSERT INTO findings (id, submission_ref, key, value, kind, created_at)
+                         VALUES (?1 || ?2, ?1, ?2, ?3, 'metric', '2024-01-01T00:00:00Z')",
+                        rusqlite::params![id, key, v.to_string()],
+                    ).unwrap();
+                }
+            }
+        }
+    }
+
+    fn visible(conn: &rusqlite::Connection, data: &AppState, show_hidden: bool) -> Vec<String> {
+        let sql = format!(
+            "SELECT s.id FROM submissions s WHERE s.submission_id = ?1{} ORDER BY s.id",
+            visible_clause(data, show_hidden)
+        );
+        let mut stmt = conn.prepare(&sql).unwrap();
+        stmt.query_map(["a1"], |r| r.get(0)).unwrap().flatten().collect()
+    }
+
+    #[test]
+    fn below_either_threshold_is_hidden() {
+        let data = state(10, 20);
+        let conn = data.pool.get().unwrap();
+        seed(&conn);
+
+        assert_eq!(visible(&conn, &data, false), ["full", "pending"]);
+        assert_eq!(visible(&conn, &data, true), ["full", "pending", "quiet", "short"]);
+        assert_eq!(count_hidden(&conn, &data, "a1", true), 2);
+    }
+
+    #[test]
+    fn nothing_hidden_without_thresholds() {
+        let data = state(0, 0);
+        let conn = data.pool.get().unwrap();
+        seed(&conn);
+
+        assert!(hidden_predicate(&data).is_none());
+        assert_eq!(visible(&conn, &data, false).len(), 4);
+        assert_eq!(count_hidden(&conn, &data, "a1", true), 0);
+    }
+
+    #[test]
+    fn toggle_is_read_from_the_query() {
+        assert!(show_hidden(&TestRequest::with_uri("/x?q=a&show_hidden=on").to_http_request()));
+        assert!(!show_hidden(&TestRequest::with_uri("/x?show_hidden=0").to_http_request()));
+        assert!(!show_hidden(&TestRequest::with_uri("/x").to_http_request()));
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::OnceLock;
+
+use time::{OffsetDateTime, UtcOffset};
+use tz::TimeZone;
+
+// All arithmetic (durations, bucketing, ordering) is done on the UTC instant.
+// Local time is only used to render labels, and the offset is resolved for
+// each instant separately so sessions crossing a DST change get correct labels.
+//
+// The zone comes from the tz database rather than the libc local offset, which the
+// time crate refuses to read once the server runs more than one thread.
+
+// loaded on first use; None falls back to UTC
+static ZONE: OnceLock<Option<TimeZone>> = OnceLock::new();
+
+/// PAL_TIMEZONE (e.g. "Europe/Ljubljana"), else TZ / /etc/localtime
+fn zone() -> Option<&'static TimeZone> {
+    ZONE.get_or_init(|| {
+        let configured = std::env::var("PAL_TIMEZONE").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let zone = match &configured {
+            Some(name) => TimeZone::from_posix_tz(name).map_err(|e| format!("PAL_TIMEZONE '{name}': {e}")),
+            None => TimeZone::local().map_err(|e| format!("local time zone: {e}")),
+        };
+        zone.map_err(|e| log::warn!("{e}, showing times in UTC")).ok()
+    })
+    .as_ref()
+}
+
+/// Offset of `zone` in effect at `dt`
+fn offset_at(zone: &TimeZone, dt: OffsetDateTime) -> UtcOffset {
+    zone.find_local_time_type(dt.unix_timestamp())
+        .ok()
+        .and_then(|t| UtcOffset::from_whole_seconds(t.ut_offset()).ok())
+        .unwrap_or(UtcOffset::UTC)
+}
+
+/// The same instant, expressed in the server's local offset valid *at that instant*
+pub fn to_local(dt: OffsetDateTime) -> OffsetDateTime {
+    let offset = zone().map_or(UtcOffset::UTC, |z| offset_at(z, dt));
+    dt.to_offset(offset)
+}
+
+/// Minute bucket of an instant; stable across DST (repeated local hours stay distinct)
+pub fn minute_bucket(dt: OffsetDateTime) -> i64 {
+    dt.unix_timestamp().div_euclid(60)
+}
+
+/// "YYYY-MM-DD HH:MM" label of an instant in local time
+pub fn minute_label(dt: OffsetDateTime) -> String {
+    label(to_local(dt))
+}
+
+fn label(l: OffsetDateTime) -> String {
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", l.year(), u8::from(l.month()), l.day(), l.hour(), l.minute())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::format_description::well_known::Rfc3339;
+
+    // central Europe as a POSIX rule, so the test doesn't depend on the installed tz database:
+    // on 2024-10-27 clocks go back from 03:00 CEST to 02:00 CET (01:00 UTC)
+    const CET: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+
+    fn at(s: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(s, &Rfc3339).unwrap()
+    }
+
+    #[test]
+    fn labels_follow_the_offset_across_dst() {
+        let zone = TimeZone::from_posix_tz(CET).unwrap();
+        let local = |s: &str| label(at(s).to_offset(offset_at(&zone, at(s))));
+
+        assert_eq!(local("2024-10-27T00:30:00Z"), "2024-10-27 02:30");
+        assert_eq!(local("2024-10-27T01:30:00Z"), "2024-10-27 02:30");
+        assert_eq!(local("2024-10-27T02:30:00Z"), "2024-10-27 03:30");
+        assert_eq!(offset_at(&zone, at("2024-10-27T00:59:59Z")).whole_hours(), 2);
+        assert_eq!(offset_at(&zone, at("2024-10-27T01:00:00Z")).whole_hours(), 1);
+        // and forward again on 2024-03-31 at 01:00 UTC
+        assert_eq!(offset_at(&zone, at("2024-03-31T00:59:59Z")).whole_hours(), 1);
+        assert_eq!(offset_at(&zone, at("2024-03-31T01:00:00Z")).whole_hours(), 2);
+    }
+
+    #[test]
+    fn arithmetic_stays_on_the_instant() {
+        // 02:30 CEST to 02:30 CET reads as no time at all on the wall clock, but is an hour
+        let start = at("2024-10-27T02:30:00+02:00");
+        let end = at("2024-10-27T02:30:00+01:00");
+        assert_eq!((end - start).whole_minutes(), 60);
+        assert_eq!(minute_bucket(end) - minute_bucket(start), 60);
+    }
+
+    #[test]
+    fn named_zones_come_from_the_tz_database() {
+        // skipped where no tz database is installed
+        if let Ok(zone) = TimeZone::from_posix_tz("Europe/Ljubljana") {
+            assert_eq!(offset_at(&zone, at("2024-07-01T12:00:00Z")).whole_hours(), 2);
+            assert_eq!(offset_at(&zone, at("2024-12-01T12:00:00Z")).whole_hours(), 1);
+        }
+    }
+}
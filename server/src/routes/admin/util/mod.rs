@@ -1,3 +1,7 @@
 pub mod point;
 pub mod zip;
-pub mod consts;
\ No newline at end of file
+pub mod consts;
+pub mod localtime;
+pub mod stats;
+pub mod negotiate;
+pub mod access;pub mod hidden;
@@ -0,0 +1,26 @@
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use tera::{Context, Tera};
+
+/// JSON was asked for, via `?format=json` or an `Accept: application/json` header.
+/// htmx and browsers send neither, so they keep getting the HTML fragment.
+pub fn wants_json(req: &HttpRequest) -> bool {
+    let by_query = req.query_string().split('&').any(|kv| kv == "format=json");
+    let by_accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|a| a.contains("application/json") && !a.contains("text/html"));
+    by_query || by_accept
+}
+
+/// Renders `template` with `ctx`, or returns the same context as JSON when the client
+/// asked for it, so a stats handler computes once and serves both.
+pub fn render_or_json(req: &HttpRequest, tera: &Tera, template: &str, ctx: Context) -> HttpResponse {
+    if wants_json(req) {
+        return HttpResponse::Ok().json(ctx.into_json());
+    }
+    match tera.render(template, &ctx) {
+        Ok(html) => HttpResponse::Ok().body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
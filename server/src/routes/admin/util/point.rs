@@ -3,7 +3,10 @@ use serde::Serialize;
 #[derive(Serialize)]
 pub struct Point { 
     pub t: String, 
+    // UTC minute bucket (unix minutes), for server side plotting
+    #[serde(skip)]
+    pub minute: i64,
     pub total: i32, 
     pub ai: i32, 
     pub ma100: f32 
-}
\ No newline at end of file
+}
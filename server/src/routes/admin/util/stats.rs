@@ -0,0 +1,102 @@
+/// Percentile `p` (0..=100) of `values` by linear interpolation between closest ranks.
+/// Works on unsorted input; `None` only when `values` is empty. Index is clamped, so
+/// n=1 returns the single value and p outside 0..=100 behaves like the bounds.
+pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut v = values.to_vec();
+    v.sort_by(|a, b| a.total_cmp(b));
+
+    let rank = (p.clamp(0.0, 100.0) / 100.0) * (v.len() - 1) as f64;
+    let lo = (rank.floor() as usize).min(v.len() - 1);
+    let hi = (rank.ceil() as usize).min(v.len() - 1);
+    let frac = rank - lo as f64;
+    Some(v[lo] + (v[hi] - v[lo]) * frac)
+}
+
+/// Integer flavour of [`percentile`], rounded; 0 for empty input
+pub fn percentile_i64(values: &[i64], p: f64) -> i64 {
+    let v: Vec<f64> = values.iter().map(|x| *x as f64).collect();
+    percentile(&v, p).map(|x| x.round() as i64).unwrap_or(0)
+}
+
+pub fn median_i64(values: &[i64]) -> i64 {
+    percentile_i64(values, 50.0)
+}
+
+/// Mid-rank percentile of `x` within `sorted` (ascending): share of values below `x`
+/// plus half of those equal to it, as 0..=100. Ties share one percentile, so all-equal
+/// input and a single submission both give 50; 0 for empty input.
+pub fn percentile_rank(sorted: &[i64], x: i64) -> u8 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let below = sorted.partition_point(|v| *v < x);
+    let equal = sorted.partition_point(|v| *v <= x) - below;
+    let p = (below as f64 + equal as f64 / 2.0) / sorted.len() as f64 * 100.0;
+    p.round().clamp(0.0, 100.0) as u8
+}
+
+/// Distance of `x` above `med` in MADs; `None` when `mad` is 0 (over half the values
+/// identical) since the spread gives no scale to measure against.
+pub fn robust_score(x: i64, med: i64, mad: i64) -> Option<f64> {
+    (mad > 0).then(|| (x - med) as f64 / mad as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_one_value() {
+        for p in [0.0, 50.0, 90.0, 95.0, 100.0] {
+            assert_eq!(percentile(&[42.0], p), Some(42.0));
+            assert_eq!(percentile_i64(&[42], p), 42);
+        }
+    }
+
+    #[test]
+    fn percentile_of_two_values() {
+        assert_eq!(percentile(&[30.0, 10.0], 0.0), Some(10.0));
+        assert_eq!(percentile(&[30.0, 10.0], 50.0), Some(20.0));
+        assert_eq!(percentile(&[30.0, 10.0], 95.0), Some(29.0));
+        assert_eq!(percentile(&[30.0, 10.0], 100.0), Some(30.0));
+        assert_eq!(percentile_i64(&[10, 30], 90.0), 28);
+    }
+
+    #[test]
+    fn percentile_typical() {
+        let v: Vec<f64> = (1..=11).map(|x| x as f64).collect();
+        assert_eq!(percentile(&v, 50.0), Some(6.0));
+        assert_eq!(percentile(&v, 90.0), Some(10.0));
+        assert_eq!(percentile(&v, 95.0), Some(10.5));
+        assert_eq!(median_i64(&[5, 1, 3, 2, 4]), 3);
+        assert_eq!(median_i64(&[4, 1, 3, 2]), 3); // 2.5 rounds away from zero
+    }
+
+    #[test]
+    fn percentile_bounds() {
+        assert_eq!(percentile(&[], 50.0), None);
+        assert_eq!(percentile_i64(&[], 95.0), 0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0], -10.0), Some(1.0));
+        assert_eq!(percentile(&[1.0, 2.0, 3.0], 250.0), Some(3.0));
+    }
+
+    #[test]
+    fn percentile_rank_degenerate() {
+        assert_eq!(percentile_rank(&[], 5), 0);
+        assert_eq!(percentile_rank(&[7], 7), 50);
+        // all equal: everyone sits in the middle, nobody at the top
+        assert_eq!(percentile_rank(&[3, 3, 3, 3], 3), 50);
+        assert_eq!(percentile_rank(&[3, 3, 3, 3], 4), 100);
+        assert_eq!(percentile_rank(&[3, 3, 3, 3], 2), 0);
+    }
+
+    #[test]
+    fn robust_score_needs_a_spread() {
+        assert_eq!(robust_score(10, 10, 0), None);
+        assert_eq!(robust_score(40, 10, 10), Some(3.0));
+        assert_eq!(robust_score(5, 10, 10), Some(-0.5));
+    }
+}
@@ -10,7 +10,7 @@ pub fn open_processed_zip_by_submission(
 ) -> Result<ZipArchive<File>, String> {
     let conn = data.pool.get().map_err(|e| e.to_string())?;
     let full: String = conn.query_row(
-        "SELECT fs_path FROM logs WHERE submission_ref = ?1 ORDER BY rowid ASC LIMIT 1",
+        "SELECT fs_path FROM logs WHERE submission_ref = ?1 AND archived = 0 ORDER BY rowid DESC LIMIT 1",
         params![&submission_id],
         |r| r.get(0),
     ).map_err(|e| e.to_string())?;
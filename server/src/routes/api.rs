@@ -1,93 +1,616 @@
 use actix_multipart::Multipart;
-use actix_web::{post, web, Error, HttpResponse};
+use actix_web::{get, http::{header, StatusCode}, post, put, web, Error, HttpRequest, HttpResponse};
 use futures_util::StreamExt as _;
 use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
 use std::fs;
 use crate::AppState;
+use crate::rate_limit::RateLimiter;
 use crate::db; 
+use crate::routes::admin::util::consts::{FK_STUDENT_NOTE, KIND_META, STUDENT_NOTE_MAX_CHARS};
+use crate::live;
+use crate::moodle;
 
 
 #[derive(serde::Serialize)]
 pub struct ApiReceipt { pub receipt_id: String }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 pub struct LogMeta {
     pub submission_id: String,
     pub student_name: String,
+    // login name when student_name is the moodle full name (older clients only send student_name)
+    pub student_username: Option<String>,
     pub moodle_assignment_id: Option<String>,
     pub client_version: Option<String>,
+    // "Test my setup" dry run from the client; kept out of assignment stats
+    pub is_test: Option<bool>,
+    // receipt of an earlier upload this one replaces (same student and assignment only)
+    pub replaces: Option<String>,
+}
+
+impl LogMeta {
+    /// receipt being replaced, if any
+    fn replacing(&self) -> Option<&str> {
+        self.replaces.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    }
+}
+
+/// Collector side of live tailing: each text frame is one palantir.log event line,
+/// relayed as-is to professors watching this assignment/seat. The collector presents its
+/// seat token (`Authorization: Bearer <token>`, issued on the live page); assignment and
+/// seat come from the token, not from the client.
+#[get("/api/v1/live")]
+pub async fn live_ingest(
+    req: HttpRequest,
+    body: web::Payload,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let Some((assignment_id, seat)) = token.and_then(|t| live::check_token(&data.pool, t)) else {
+        return Ok(HttpResponse::Unauthorized().body("missing or unknown live seat token"));
+    };
+
+    let (resp, mut session, mut msgs) = actix_ws::handle(&req, body)?;
+    let key = live::stream_key(&assignment_id, &seat);
+    log::info!("live: collector connected for {key}");
+
+    actix_web::rt::spawn(async move {
+        let conn_id = data.live.source_connected(&key);
+        while let Some(Ok(msg)) = msgs.next().await {
+            match msg {
+                actix_ws::Message::Text(line) => data.live.publish(&key, &line).await,
+                actix_ws::Message::Ping(b) if session.pong(&b).await.is_err() => break,
+                actix_ws::Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        data.live.source_disconnected(&key, conn_id);
+        log::info!("live: collector disconnected for {key}");
+        let _ = session.close(None).await;
+    });
+
+    Ok(resp)
+}
+
+/// 507 when the upload volume has less than the configured reserve plus `incoming` bytes free
+fn refuse_if_disk_full(data: &AppState, incoming: u64) -> Option<HttpResponse> {
+    match fs2::available_space(&data.upload_dir) {
+        Ok(free) if free < data.min_free_disk_bytes.saturating_add(incoming) => {
+            log::error!("upload refused: {free} bytes free on upload dir, need {} + {incoming}", data.min_free_disk_bytes);
+            Some(HttpResponse::build(StatusCode::INSUFFICIENT_STORAGE)
+                .body("The server is out of storage space. Please try again in a few minutes or contact your teacher."))
+        }
+        Ok(_) => None,
+        Err(e) => {
+            log::warn!("could not read free space of upload dir: {e}");
+            None
+        }
+    }
+}
+
+/// 429 once this client has used up its allowance on `limiter` (PAL_UPLOAD_RATE_* for starting
+/// uploads, PAL_UPLOAD_SESSION_RATE_* for the requests of a chunked upload)
+fn refuse_if_rate_limited(req: &HttpRequest, limiter: &RateLimiter) -> Option<HttpResponse> {
+    let ip = limiter.client_ip(req)?;
+    let retry_after = limiter.check(ip).err()?;
+    log::warn!("upload refused: rate limit for {ip}, retry in {retry_after}s");
+    Some(HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .body("Too many uploads from this address. Please wait a moment and try again."))
+}
+
+/// 413 when an upload would exceed PAL_MAX_UPLOAD_BYTES (0 = no limit)
+fn refuse_if_too_large(data: &AppState, size: u64) -> Option<HttpResponse> {
+    if data.max_upload_bytes == 0 || size <= data.max_upload_bytes {
+        return None;
+    }
+    log::warn!("upload refused: {size} bytes, limit is {}", data.max_upload_bytes);
+    Some(HttpResponse::PayloadTooLarge()
+        .body(format!("The upload is larger than the server accepts ({} bytes).", data.max_upload_bytes)))
+}
+
+/// The claimed student must be the owner of the Moodle token sent along (when MOODLE_BASE_URL is set)
+async fn refuse_if_not_student(req: &HttpRequest, data: &AppState, meta: &LogMeta) -> Option<HttpResponse> {
+    let moodle_base = data.verify_identity_moodle.as_deref()?;
+    let token = req
+        .headers()
+        .get("X-Moodle-Token")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let Some(token) = token else {
+        return Some(HttpResponse::Unauthorized()
+            .body("This server needs your Moodle login to accept logs. Please update the Palantir client."));
+    };
+    let username = meta.student_username.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    match moodle::site_info(moodle_base, token).await {
+        Ok(who) if who.matches(&meta.student_name, username) => None,
+        Ok(who) => {
+            log::warn!(
+                "upload refused: claims '{}' ({:?}) but token belongs to '{}'",
+                meta.student_name, username, who.username
+            );
+            Some(HttpResponse::Forbidden().body("The student name does not match your Moodle account."))
+        }
+        Err(e) => {
+            log::warn!("upload refused: could not verify moodle token: {e}");
+            Some(HttpResponse::BadGateway()
+                .body("Could not verify your identity with Moodle. Please log in again and retry."))
+        }
+    }
+}
+
+/// Create the submission row, or check and reuse the one named in `replaces`. The bool is
+/// false when this zip was already uploaded as a recent submission (nothing to store then).
+/// `Ok(Err(resp))` is a refusal to send back as-is.
+fn open_submission(data: &AppState, meta: &LogMeta, now: &str, sha256: &str) -> Result<Result<(String, bool), HttpResponse>, Error> {
+    let username = meta.student_username.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if let Some(prev) = meta.replacing() {
+        let detail = db::get_submission_detail(&data.pool, prev)
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        let Some(detail) = detail else {
+            return Ok(Err(HttpResponse::NotFound().body("unknown receipt")));
+        };
+        let same_student = detail.student_name == meta.student_name.trim()
+            && detail.student_username.as_deref() == username;
+        if detail.submission_id != meta.submission_id || !same_student {
+            log::warn!("re-upload of {prev} refused: student/assignment mismatch");
+            return Ok(Err(HttpResponse::Forbidden().body("receipt belongs to another student or assignment")));
+        }
+        return Ok(Ok((prev.to_string(), true)));
+    }
+    let (sub_id, created) = db::new_submission(&data.pool, &db::NewSubmission {
+        submission_id: &meta.submission_id,
+        student_name: meta.student_name.trim(),
+        student_username: username,
+        created_at_rfc3339: now,
+        moodle_assignment_id: meta.moodle_assignment_id.as_deref().unwrap_or(""),
+        client_version: meta.client_version.as_deref().unwrap_or("client"),
+        is_test: meta.is_test.unwrap_or(false),
+        content_sha256: Some(sha256),
+    }).map_err(|e| {
+        log::error!("new_submission failed: {e}");
+        actix_web::error::ErrorInternalServerError(e)
+    })?;
+    if !created {
+        log::info!("identical upload from '{}' folded into submission {sub_id}", meta.student_name);
+    }
+    Ok(Ok((sub_id, created)))
+}
+
+/// Where an upload's zip is stored
+fn artifact_path(data: &AppState, meta: &LogMeta, now: &str) -> std::path::PathBuf {
+    let filename = format!(
+        "{}-{}-{}.zip",
+        now.replace(':', "_"),
+        meta.submission_id,
+        meta.student_name.replace([' ', '/', '\\'], "_")
+    );
+    data.upload_dir.join(filename)
+}
+
+/// Record a stored zip (and the student's note) for `sub_id`; a re-upload archives the old artifact first
+fn finish_upload(
+    data: &AppState,
+    sub_id: &str,
+    replacing: bool,
+    artifact: Option<(&std::path::Path, &str, i64, bool)>, // (path, sha256 hex, size, matched the client's sha256)
+    note: &[u8],
+    now: &str,
+) -> Result<(), Error> {
+    if let Some((path, sum_hex, total, verified)) = artifact {
+        if replacing {
+            db::reset_for_reupload(&data.pool, sub_id)
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+        }
+        db::add_log_artifact(
+            &data.pool,
+            sub_id,
+            &path.to_string_lossy(),
+            sum_hex,
+            total,
+            verified,
+        ).map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+
+    let note: String = String::from_utf8_lossy(note)
+        .trim()
+        .chars()
+        .take(STUDENT_NOTE_MAX_CHARS)
+        .collect();
+    if !note.is_empty() {
+        db::add_finding(&data.pool, sub_id, KIND_META, FK_STUDENT_NOTE, &note, now)
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    Ok(())
+}
+
+fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap()
 }
 
 #[post("/api/v1/logs")]
 pub async fn upload_logs(
+    req: HttpRequest,
     data: web::Data<AppState>,
     mut payload: Multipart,
     query: web::Query<LogMeta>,
 ) -> Result<HttpResponse, Error> {
     let meta = query.into_inner();
-    let now = OffsetDateTime::now_utc()
-        .format(&time::format_description::well_known::Rfc3339)
-        .unwrap();
 
-    // 1 create submission row via db layer
-    let sub_id = db::new_submission(
-        &data.pool,
-        &meta.submission_id,
-        &meta.student_name,
-        &now,
-        meta.moodle_assignment_id.as_deref().unwrap_or(""),
-        meta.client_version.as_deref().unwrap_or("client"),
-    ).map_err(|e| {
-        log::error!("new_submission failed: {e}");
-        actix_web::error::ErrorInternalServerError(e)
-    })?;
+    // 0 refuse early when the client is flooding us, the body is too big or the upload volume
+    // is (nearly) full, before any row or file exists
+    if let Some(resp) = refuse_if_rate_limited(&req, &data.upload_rate) {
+        return Ok(resp);
+    }
+    let declared: u64 = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    if let Some(resp) = refuse_if_too_large(&data, declared) {
+        return Ok(resp);
+    }
+    if let Some(resp) = refuse_if_disk_full(&data, declared) {
+        return Ok(resp);
+    }
+    if let Some(resp) = refuse_if_not_student(&req, &data, &meta).await {
+        return Ok(resp);
+    }
+
+    // sha256 the client computed over the zip; older clients don't send it
+    let declared_sha256: Option<String> = req
+        .headers()
+        .get("X-Content-SHA256")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.trim().to_ascii_lowercase())
+        .filter(|s| !s.is_empty());
 
+    let now = now_rfc3339();
+    let replacing = meta.replacing().is_some();
 
-    // 2 stream-upload file to disk, compute sha256 and size
+    // 1 stream-upload file to disk, compute sha256 and size
     let mut saved_path = None;
     let mut sha256 = Sha256::new();
     let mut total: i64 = 0;
+    let mut note_bytes: Vec<u8> = Vec::new();
+    // bytes of all fields; Content-Length may be missing (chunked encoding) or lie
+    let mut received: u64 = 0;
 
     while let Some(item) = payload.next().await {
         let mut field = item?;
         let cd = field.content_disposition().clone();
         let name = cd.get_name().unwrap_or("");
+        if name == "student_note" {
+            // utf-8 is at most 4 bytes per char; anything beyond is dropped
+            while let Some(chunk) = field.next().await {
+                let bytes = chunk?;
+                received += bytes.len() as u64;
+                if let Some(resp) = refuse_if_too_large(&data, received) {
+                    if let Some(p) = &saved_path { let _ = fs::remove_file(p); }
+                    return Ok(resp);
+                }
+                let room = (STUDENT_NOTE_MAX_CHARS * 4).saturating_sub(note_bytes.len());
+                note_bytes.extend_from_slice(&bytes[..bytes.len().min(room)]);
+            }
+            continue;
+        }
         if name != "log_zip" {
-            while let Some(chunk) = field.next().await { let _ = chunk?; }
+            while let Some(chunk) = field.next().await {
+                received += chunk?.len() as u64;
+                if let Some(resp) = refuse_if_too_large(&data, received) {
+                    if let Some(p) = &saved_path { let _ = fs::remove_file(p); }
+                    return Ok(resp);
+                }
+            }
             continue;
         }
 
-        let filename = format!(
-            "{}-{}-{}.zip",
-            now.replace(':', "_"),
-            meta.submission_id,
-            meta.student_name.replace(' ', "_")
-        );
-        let dest = data.upload_dir.join(filename);
-        let mut f = fs::File::create(&dest)?;
+        let dest = artifact_path(&data, &meta, &now);
+        let mut too_large = None;
+        let written: Result<(), Error> = async {
+            let mut f = fs::File::create(&dest)?;
+            while let Some(chunk) = field.next().await {
+                let bytes = chunk?;
+                received += bytes.len() as u64;
+                too_large = refuse_if_too_large(&data, received);
+                if too_large.is_some() {
+                    return Ok(());
+                }
+                sha256.update(&bytes);
+                total += bytes.len() as i64;
+                use std::io::Write;
+                f.write_all(&bytes)?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            // don't leave a truncated zip behind; no submission row exists yet
+            log::error!("upload write failed for {}: {e}", dest.display());
+            let _ = fs::remove_file(&dest);
+            return Err(e);
+        }
+        if let Some(resp) = too_large {
+            let _ = fs::remove_file(&dest);
+            if let Some(p) = &saved_path { let _ = fs::remove_file(p); }
+            return Ok(resp);
+        }
+        saved_path = Some(dest);
+    }
+    let sum_hex = hex::encode(sha256.finalize());
+    if let Some(declared) = &declared_sha256
+        && saved_path.is_some()
+        && *declared != sum_hex
+    {
+        log::warn!("upload for {}: sha256 mismatch after {total} bytes", meta.submission_id);
+        if let Some(p) = &saved_path { let _ = fs::remove_file(p); }
+        // 422 like a checksum mismatch on a chunked upload, which the client shows as is
+        return Ok(HttpResponse::UnprocessableEntity().body("upload corrupted (X-Content-SHA256 does not match the received zip), please upload again"));
+    }
+    let verified = declared_sha256.is_some() && saved_path.is_some();
 
-        while let Some(chunk) = field.next().await {
+    // 2 create submission row via db layer (reusing an identical recent upload), or reuse the one being replaced
+    let (sub_id, created) = match open_submission(&data, &meta, &now, &sum_hex)? {
+        Ok(v) => v,
+        Err(resp) => {
+            if let Some(p) = &saved_path { let _ = fs::remove_file(p); }
+            return Ok(resp);
+        }
+    };
+    if !created {
+        if let Some(p) = &saved_path { let _ = fs::remove_file(p); }
+        return Ok(HttpResponse::Ok().json(ApiReceipt { receipt_id: sub_id }));
+    }
+
+    // 3 persist artifact row
+    finish_upload(
+        &data,
+        &sub_id,
+        replacing,
+        saved_path.as_deref().map(|p| (p, sum_hex.as_str(), total, verified)),
+        &note_bytes,
+        &now,
+    )?;
+
+    Ok(HttpResponse::Ok().json(ApiReceipt { receipt_id: sub_id }))
+}
+
+// ---- chunked uploads ----
+// init -> chunk* -> complete. The bytes collect in upload_dir/sessions/<id>/data.part next to the
+// upload's query (meta.json); the submission row is only created on complete, so a dropped
+// connection leaves no half-made submission and the client resumes from the stored offset.
+
+/// In-progress sessions older than this are removed when a new one starts
+const UPLOAD_SESSION_MAX_AGE_HOURS: u64 = 24;
+
+#[derive(serde::Serialize)]
+pub struct UploadSession {
+    pub session_id: String,
+    pub offset: u64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ChunkQuery {
+    pub offset: u64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CompleteReq {
+    pub sha256: String,
+    pub student_note: Option<String>,
+}
+
+/// Directory of a session; `None` for ids that aren't ours (keeps paths inside upload_dir)
+fn session_dir(data: &AppState, id: &str) -> Option<std::path::PathBuf> {
+    let id = uuid::Uuid::parse_str(id).ok()?;
+    let dir = data.upload_dir.join("sessions").join(id.to_string());
+    dir.is_dir().then_some(dir)
+}
+
+fn session_offset(dir: &std::path::Path) -> u64 {
+    fs::metadata(dir.join("data.part")).map(|m| m.len()).unwrap_or(0)
+}
+
+fn remove_stale_sessions(data: &AppState) {
+    let Ok(entries) = fs::read_dir(data.upload_dir.join("sessions")) else { return; };
+    let max_age = std::time::Duration::from_secs(UPLOAD_SESSION_MAX_AGE_HOURS * 3600);
+    for e in entries.flatten() {
+        let stale = e.metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if stale {
+            let _ = fs::remove_dir_all(e.path());
+        }
+    }
+}
+
+/// Start a chunked upload; takes the same query (and identity check) as `upload_logs`
+#[post("/api/v1/logs/init")]
+pub async fn upload_init(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<LogMeta>,
+) -> Result<HttpResponse, Error> {
+    let meta = query.into_inner();
+    if let Some(resp) = refuse_if_rate_limited(&req, &data.upload_rate) {
+        return Ok(resp);
+    }
+    if let Some(resp) = refuse_if_disk_full(&data, 0) {
+        return Ok(resp);
+    }
+    if let Some(resp) = refuse_if_not_student(&req, &data, &meta).await {
+        return Ok(resp);
+    }
+    remove_stale_sessions(&data);
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let dir = data.upload_dir.join("sessions").join(&id);
+    fs::create_dir_all(&dir)?;
+    let meta_json = serde_json::to_vec(&meta).map_err(actix_web::error::ErrorInternalServerError)?;
+    fs::write(dir.join("meta.json"), meta_json)?;
+    fs::File::create(dir.join("data.part"))?;
+
+    Ok(HttpResponse::Ok().json(UploadSession { session_id: id, offset: 0 }))
+}
+
+/// How much of a session has arrived, for resuming after a dropped connection
+#[get("/api/v1/logs/{session}")]
+pub async fn upload_status(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = refuse_if_rate_limited(&req, &data.upload_session_rate) {
+        return Ok(resp);
+    }
+    let id = path.into_inner();
+    let Some(dir) = session_dir(&data, &id) else {
+        return Ok(HttpResponse::NotFound().body("unknown upload session"));
+    };
+    Ok(HttpResponse::Ok().json(UploadSession { offset: session_offset(&dir), session_id: id }))
+}
+
+/// Append the body at `offset`, which must be the current end; 409 with the real offset otherwise
+#[put("/api/v1/logs/{session}/chunk")]
+pub async fn upload_chunk(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<ChunkQuery>,
+    mut body: web::Payload,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = refuse_if_rate_limited(&req, &data.upload_session_rate) {
+        return Ok(resp);
+    }
+    let id = path.into_inner();
+    let Some(dir) = session_dir(&data, &id) else {
+        return Ok(HttpResponse::NotFound().body("unknown upload session"));
+    };
+    let offset = session_offset(&dir);
+    if query.offset != offset {
+        return Ok(HttpResponse::Conflict().json(UploadSession { session_id: id, offset }));
+    }
+    let declared: u64 = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    if let Some(resp) = refuse_if_too_large(&data, offset.saturating_add(declared)) {
+        return Ok(resp);
+    }
+    if let Some(resp) = refuse_if_disk_full(&data, declared) {
+        return Ok(resp);
+    }
+
+    let part = dir.join("data.part");
+    let mut too_large = None;
+    let written: Result<(), Error> = async {
+        use std::io::Write;
+        let mut f = fs::OpenOptions::new().append(true).open(&part)?;
+        let mut end = offset;
+        while let Some(chunk) = body.next().await {
             let bytes = chunk?;
-            sha256.update(&bytes);
-            total += bytes.len() as i64;
-            use std::io::Write;
+            end += bytes.len() as u64;
+            too_large = refuse_if_too_large(&data, end);
+            if too_large.is_some() {
+                return Ok(());
+            }
             f.write_all(&bytes)?;
         }
-        saved_path = Some(dest);
+        Ok(())
+    }
+    .await;
+    if let Some(resp) = too_large {
+        if let Ok(f) = fs::OpenOptions::new().write(true).open(&part) {
+            let _ = f.set_len(offset);
+        }
+        return Ok(resp);
+    }
+    if let Err(e) = written {
+        // cut back to the last good end so the client can resend this chunk
+        if let Ok(f) = fs::OpenOptions::new().write(true).open(&part) {
+            let _ = f.set_len(offset);
+        }
+        return Err(e);
     }
+    Ok(HttpResponse::Ok().json(UploadSession { offset: session_offset(&dir), session_id: id }))
+}
 
-    // 3 persist artifact row
-    let sum_hex = hex::encode(sha256.finalize());
-    if let Some(path) = saved_path {
-        db::add_log_artifact(
-            &data.pool,
-            &sub_id,
-            &path.to_string_lossy(),
-            &sum_hex,
-            total,
-        ).map_err(actix_web::error::ErrorInternalServerError)?;
+/// Check the assembled zip against the client's sha256, then store it like a single-shot upload
+#[post("/api/v1/logs/{session}/complete")]
+pub async fn upload_complete(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<CompleteReq>,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = refuse_if_rate_limited(&req, &data.upload_session_rate) {
+        return Ok(resp);
+    }
+    let id = path.into_inner();
+    let Some(dir) = session_dir(&data, &id) else {
+        return Ok(HttpResponse::NotFound().body("unknown upload session"));
+    };
+    let meta: LogMeta = fs::read(dir.join("meta.json"))
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("upload session has no metadata"))?;
+
+    let part = dir.join("data.part");
+    let (sum_hex, total) = {
+        use std::io::Read;
+        let mut f = fs::File::open(&part)?;
+        let mut sha256 = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut total: i64 = 0;
+        loop {
+            let n = f.read(&mut buf)?;
+            if n == 0 { break; }
+            sha256.update(&buf[..n]);
+            total += n as i64;
+        }
+        (hex::encode(sha256.finalize()), total)
+    };
+    if !sum_hex.eq_ignore_ascii_case(body.sha256.trim()) {
+        log::warn!("upload session {id}: sha256 mismatch after {total} bytes");
+        let _ = fs::remove_dir_all(&dir);
+        return Ok(HttpResponse::UnprocessableEntity().body("upload corrupted (checksum mismatch), please upload again"));
+    }
+
+    let now = now_rfc3339();
+    let replacing = meta.replacing().is_some();
+    let (sub_id, created) = match open_submission(&data, &meta, &now, &sum_hex)? {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    if !created {
+        let _ = fs::remove_dir_all(&dir);
+        return Ok(HttpResponse::Ok().json(ApiReceipt { receipt_id: sub_id }));
+    }
+    let dest = artifact_path(&data, &meta, &now);
+    if let Err(e) = fs::rename(&part, &dest) {
+        log::error!("upload session {id}: moving {} failed: {e}", part.display());
+        if !replacing {
+            let _ = db::set_submission_status(&data.pool, &sub_id, "failed");
+        }
+        return Err(e.into());
     }
+    let note = body.student_note.as_deref().unwrap_or("");
+    let note = &note.as_bytes()[..note.len().min(STUDENT_NOTE_MAX_CHARS * 4)];
+    finish_upload(&data, &sub_id, replacing, Some((&dest, &sum_hex, total, true)), note, &now)?;
+    let _ = fs::remove_dir_all(&dir);
 
     Ok(HttpResponse::Ok().json(ApiReceipt { receipt_id: sub_id }))
 }
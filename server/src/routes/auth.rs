@@ -2,9 +2,43 @@ use actix_web::{dev::Payload, get, post, web, Error, FromRequest, HttpRequest, H
 use actix_session::Session;
 use ldap3::{LdapConn, Scope, SearchEntry};
 use serde::Deserialize;
+use time::OffsetDateTime;
 
 use crate::{template, AppState};
 
+/// How /admin/login checks credentials (PAL_AUTH_MODE)
+#[derive(Clone, Debug)]
+pub enum AuthMode {
+    /// bind against the directory configured by LDAP_URL / LDAP_BASE_DN / LDAP_USER_ATTR
+    Ldap,
+    /// a single local account for development: PAL_STATIC_USER and the hex sha256 of its
+    /// password in PAL_STATIC_PASSWORD_SHA256
+    Static { user: String, password_sha256: String },
+    /// nobody can log in
+    Disabled,
+}
+
+impl AuthMode {
+    pub fn from_env() -> Self {
+        match std::env::var("PAL_AUTH_MODE").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "" | "ldap" => AuthMode::Ldap,
+            "static" => {
+                let user = std::env::var("PAL_STATIC_USER").expect("PAL_AUTH_MODE=static needs PAL_STATIC_USER");
+                let hash = std::env::var("PAL_STATIC_PASSWORD_SHA256")
+                    .expect("PAL_AUTH_MODE=static needs PAL_STATIC_PASSWORD_SHA256");
+                let hash = hash.trim().to_ascii_lowercase();
+                assert!(
+                    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()),
+                    "PAL_STATIC_PASSWORD_SHA256 must be a hex sha256 digest"
+                );
+                AuthMode::Static { user: user.trim().to_string(), password_sha256: hash }
+            }
+            "disabled" | "off" => AuthMode::Disabled,
+            other => panic!("unknown PAL_AUTH_MODE '{other}' (ldap, static or disabled)"),
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct LoginForm {
     pub username: String,
@@ -15,7 +49,7 @@ pub struct LoginForm {
 pub async fn login_page(session: Session, data: web::Data<AppState>) -> impl Responder {
     if let Ok(Some(_)) = session.get::<String>("prof") {
         return HttpResponse::Found()
-            .append_header(("Location", "/admin"))
+            .append_header(("Location", format!("{}/admin", data.base_path)))
             .finish();
     }
     match template::login_page(&data.tera) {
@@ -26,14 +60,29 @@ pub async fn login_page(session: Session, data: web::Data<AppState>) -> impl Res
 
 
 #[post("/admin/login")]
-pub async fn do_login(form: web::Form<LoginForm>, session: Session) -> impl Responder {
+pub async fn do_login(form: web::Form<LoginForm>, session: Session, data: web::Data<AppState>) -> impl Responder {
     let username = form.username.clone();
     let password = form.password.clone();
+    // an empty password is an anonymous bind on most directories, which "succeeds"
+    if username.trim().is_empty() || password.is_empty() {
+        return HttpResponse::Unauthorized().body("invalid credentials");
+    }
 
-    match web::block(move || ldap_login_blocking(username, password)).await {
+    let mode = data.auth_mode.clone();
+    let check = move || match mode {
+        AuthMode::Ldap => ldap_login_blocking(username, password),
+        AuthMode::Static { user, password_sha256 } => Ok(static_login(&user, &password_sha256, &username, &password)),
+        AuthMode::Disabled => Err("admin login is disabled on this server".to_string()),
+    };
+    match web::block(check).await {
         Ok(Ok(Some(_dn))) => {
+            // new session id on privilege change, so a planted cookie can't be reused (fixation)
+            session.renew();
             let _ = session.insert("prof", &form.username);
-            HttpResponse::Found().append_header(("Location", "/admin")).finish()
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let _ = session.insert("login_ts", now);
+            let _ = session.insert("last_seen", now);
+            HttpResponse::Found().append_header(("Location", format!("{}/admin", data.base_path))).finish()
         }
         Ok(Ok(None)) => HttpResponse::Unauthorized().body("invalid credentials"),
         Ok(Err(e)) => HttpResponse::Unauthorized().body(format!("login failed: {e}")),
@@ -42,20 +91,30 @@ pub async fn do_login(form: web::Form<LoginForm>, session: Session) -> impl Resp
 }
 
 #[get("/admin/logout")]
-pub async fn logout(session: Session) -> impl Responder {
+pub async fn logout(session: Session, data: web::Data<AppState>) -> impl Responder {
     session.purge();
-    HttpResponse::Found().append_header(("Location", "/admin/login")).finish()
+    HttpResponse::Found().append_header(("Location", format!("{}/admin/login", data.base_path))).finish()
 }
 
-// identical to your previous function, just kept private in this module
-fn ldap_login_blocking(username: String, password: String) -> Result<Option<String>, String> {
-    return Ok(Some("vake".into()));
+/// The configured account when both name and password match
+fn static_login(user: &str, password_sha256: &str, username: &str, password: &str) -> Option<String> {
+    use sha2::{Digest, Sha256};
+    let given = hex::encode(Sha256::digest(password.as_bytes()));
+    // compare every byte so the time taken doesn't reveal how much of the hash matched
+    let same_hash = given.len() == password_sha256.len()
+        && given.bytes().zip(password_sha256.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0;
+    (same_hash && username == user).then(|| user.to_string())
+}
 
-    let server   = std::env::var("LDAP_SERVER").map_err(|_| "LDAP_SERVER not set".to_string())?;
+/// DN of `username` when the directory accepts `password` for it
+fn ldap_login_blocking(username: String, password: String) -> Result<Option<String>, String> {
+    let server   = std::env::var("LDAP_URL")
+        .or_else(|_| std::env::var("LDAP_SERVER"))
+        .map_err(|_| "LDAP_URL not set".to_string())?;
     let base_dn  = std::env::var("LDAP_BASE_DN").unwrap_or_else(|_| "dc=example,dc=org".to_string());
     let user_attr= std::env::var("LDAP_USER_ATTR").unwrap_or_else(|_| "uid".to_string());
-    let bind_dn  = std::env::var("LDAP_BIND_DN").ok();
-    let bind_pw  = std::env::var("LDAP_BIND_PASSWORD").ok();
+    let bind_dn  = std::env::var("LDAP_BIND_DN").ok().filter(|s| !s.is_empty());
+    let bind_pw  = std::env::var("LDAP_BIND_PASSWORD").ok().filter(|s| !s.is_empty());
 
     let mut ldap = LdapConn::new(&server).map_err(|e| e.to_string())?;
 
@@ -101,9 +160,26 @@ fn ldap_escape(s: &str) -> String {
 
 fn is_authorized(req: &HttpRequest) -> bool {
     let session = actix_session::SessionExt::get_session(req);
-    let session = session.get::<String>("prof").ok().flatten();
-    println!("auth check: {session:?}");
-    session.is_some()
+    let prof = session.get::<String>("prof").ok().flatten();
+    if prof.is_none() {
+        return false;
+    }
+
+    // sessions older than the ttl, idle for longer than the idle timeout, or from before
+    // login_ts / last_seen existed must log in again
+    let (ttl, idle) = req
+        .app_data::<web::Data<AppState>>()
+        .map(|d| (d.session_ttl_secs, d.session_idle_secs))
+        .unwrap_or((0, 0));
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let login_ts = session.get::<i64>("login_ts").ok().flatten().unwrap_or(0);
+    let last_seen = session.get::<i64>("last_seen").ok().flatten().unwrap_or(0);
+    if (ttl > 0 && now - login_ts > ttl) || (idle > 0 && now - last_seen > idle) {
+        session.purge();
+        return false;
+    }
+    let _ = session.insert("last_seen", now);
+    true
 }
 
 pub struct Authorized;
@@ -119,11 +195,107 @@ impl FromRequest for Authorized {
             ready(Ok(Authorized))
         } else {
             // Redirect to /admin/login instead of returning Unauthorized
+            let base = req
+                .app_data::<web::Data<AppState>>()
+                .map(|d| d.base_path.clone())
+                .unwrap_or_default();
             let resp = HttpResponse::Found()
-                .append_header(("Location", "/admin/login"))
+                .append_header(("Location", format!("{base}/admin/login")))
                 .finish();
             let err = actix_web::error::InternalError::from_response("Unauthorized", resp).into();
             ready(Err(err))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+    use actix_web::{cookie::Key, http::{header::LOCATION, StatusCode}, test, App};
+
+    use crate::routes::admin::{
+        assignment::get_stats_status::stats_status,
+        submission::{
+            get_artifacts::submission_artifacts_frag, get_timeline_network::net_timeline_json,
+            get_timeline_process::proc_timeline_json,
+        },
+    };
+
+    #[get("/plant")]
+    async fn plant(session: Session) -> HttpResponse {
+        let _ = session.insert("visited", true);
+        HttpResponse::Ok().finish()
+    }
+
+    #[get("/whoami")]
+    async fn whoami(_: Authorized, session: Session) -> HttpResponse {
+        HttpResponse::Ok().body(session.get::<String>("prof").ok().flatten().unwrap_or_default())
+    }
+
+    #[actix_web::test]
+    async fn login_rotates_the_session() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::test_state("")))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .service(plant)
+                .service(whoami)
+                .service(do_login),
+        )
+        .await;
+
+        // an attacker obtains a session cookie and gets it into the victim's browser
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/plant").to_request()).await;
+        let planted = resp.response().cookies().next().unwrap().into_owned();
+
+        let login = test::TestRequest::post()
+            .uri("/admin/login")
+            .cookie(planted.clone())
+            .set_form([("username", "prof"), ("password", "secret")])
+            .to_request();
+        let resp = test::call_service(&app, login).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let issued = resp.response().cookies().next().unwrap().into_owned();
+        assert_eq!(issued.name(), planted.name());
+        assert_ne!(issued.value(), planted.value());
+
+        // the victim's new session is logged in, the planted one still is not
+        let req = test::TestRequest::get().uri("/whoami").cookie(issued).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(test::read_body(resp).await, "prof");
+
+        let req = test::TestRequest::get().uri("/whoami").cookie(planted).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers().get(LOCATION).unwrap(), "/admin/login");
+    }
+
+    #[actix_web::test]
+    async fn data_endpoints_redirect_without_a_session() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::test_state("/palantir")))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .service(web::scope("/palantir")
+                    .service(net_timeline_json)
+                    .service(proc_timeline_json)
+                    .service(stats_status)
+                    .service(submission_artifacts_frag)),
+        )
+        .await;
+
+        let sub = "0b7c8a52-6f3e-4c1a-9d7e-2f4b5a6c7d8e";
+        for uri in [
+            format!("/palantir/admin/submissions/{sub}/net_timeline.json"),
+            format!("/palantir/admin/submissions/{sub}/proc_timeline.json"),
+            format!("/palantir/admin/submissions/{sub}/artifacts"),
+            "/palantir/admin/assignment/42/stats_status".to_string(),
+        ] {
+            let resp = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::FOUND, "{uri}");
+            assert_eq!(resp.headers().get(LOCATION).unwrap(), "/palantir/admin/login", "{uri}");
+        }
+    }
+}
@@ -1,13 +1,40 @@
 use actix_files::NamedFile;
-use actix_web::{get, web};
-use crate::{routes::auth::Authorized, AppState};
+use actix_session::Session;
+use actix_web::{error::{ErrorForbidden, ErrorNotFound}, get, http::header::ContentEncoding, web};
+use crate::{db, routes::{admin::audit::audit, auth::Authorized}, AppState};
 
 #[get("/uploads/{filename}")]
 pub async fn get_upload(
     _: Authorized,
-    data: web::Data<AppState>, 
+    session: Session,
+    data: web::Data<AppState>,
     path: web::Path<String>
 ) -> actix_web::Result<NamedFile> {
-    let f = data.processed_dir.join(path.into_inner());
-    Ok(NamedFile::open(f)?)
+    let name = path.into_inner();
+    // only the uploaded .zip artifacts are served
+    let is_zip = std::path::Path::new(&name)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        return Err(ErrorNotFound("not found"));
+    }
+
+    // same access rule as the submission page the link is on
+    let sub_id = db::submission_for_artifact(&data.pool, &name)
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("not found"))?;
+    let prof = session.get::<String>("prof").ok().flatten().unwrap_or_default();
+    if !db::prof_can_access_submission(&data.pool, &prof, &sub_id).unwrap_or(false) {
+        return Err(ErrorForbidden("no access to this assignment"));
+    }
+
+    // resolve symlinks / ".." and make sure we are still inside processed_dir
+    let base = data.processed_dir.canonicalize().map_err(|_| ErrorNotFound("not found"))?;
+    let f = base.join(&name).canonicalize().map_err(|_| ErrorNotFound("not found"))?;
+    if !f.starts_with(&base) || !f.is_file() {
+        return Err(ErrorNotFound("not found"));
+    }
+    audit(&data, &session, "download_artifact", &sub_id);
+    // already compressed, don't spend cpu re-encoding it in the Compress middleware
+    Ok(NamedFile::open(f)?.set_content_encoding(ContentEncoding::Identity))
 }
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use serde_json::json;
+
+use crate::AppState;
+
+/// Liveness: the process is up and serving requests.
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+/// Readiness: a pooled connection can be taken and answers a query. An exhausted pool
+/// fails after a short wait instead of the pool's usual 30s.
+#[get("/readyz")]
+pub async fn readyz(data: web::Data<AppState>) -> impl Responder {
+    let check = data
+        .pool
+        .get_timeout(Duration::from_secs(2))
+        .map_err(|e| e.to_string())
+        .and_then(|conn| {
+            conn.query_row("SELECT 1", [], |r| r.get::<_, i64>(0))
+                .map_err(|e| e.to_string())
+        });
+    match check {
+        Ok(_) => HttpResponse::Ok().json(json!({ "status": "ok" })),
+        Err(e) => HttpResponse::ServiceUnavailable().json(json!({ "status": "unavailable", "error": e })),
+    }
+}
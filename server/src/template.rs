@@ -1,8 +1,8 @@
 use tera::{Context, Tera};
-use time::{format_description::{self, well_known::Rfc3339}, OffsetDateTime, UtcOffset};
+use time::{format_description::{self, well_known::Rfc3339}, OffsetDateTime};
 
 
-use crate::{db::{FindingRow, LogRow, SubmissionDetail, SubmissionRow}, routes::admin::util::consts::{AI_PROVIDER_BASES, ALLOWED_KEYS_BOOL, ALLOWED_KEYS_NUM, ALLOWED_OPS}};
+use crate::{db::{FindingRow, LogRow, SubmissionDetail, SubmissionRow}, routes::admin::util::localtime::to_local, routes::admin::util::consts::{is_ai_domain, SensitivityProfile, ALLOWED_KEYS_BOOL, ALLOWED_KEYS_NUM, ALLOWED_OPS, AI_USAGE_KEYS, FK_AI_DOMAIN, FK_AI_HITS_TOTAL, FK_RISK_SCORE, FK_TOP_DOMAIN, KIND_ANOMALY, PROFILES, SEVERITY_ORDER}};
 
 #[derive(Debug)]
 pub struct RenderError(pub String);
@@ -35,10 +35,15 @@ pub fn assignment_cards_page(
     tera: &Tera,
     assignment_id: &str,
     cards: &[SubmissionCard],
+    profile: &SensitivityProfile,
+    provided: &[String],
 ) -> Result<String, RenderError> {
     let mut ctx = Context::new();
     ctx.insert("assignment_id", &assignment_id);
     ctx.insert("cards", &cards);
+    ctx.insert("profile", profile);
+    ctx.insert("provided", &provided);
+    ctx.insert("profiles", &PROFILES);
     ctx.insert("allowed_keys_num", &ALLOWED_KEYS_NUM);
     ctx.insert("allowed_keys_bool", &ALLOWED_KEYS_BOOL);
     ctx.insert("allowed_ops", &ALLOWED_OPS);
@@ -52,6 +57,9 @@ pub struct SubmissionCard {
     pub created_at: String,
     pub created_at_pretty: String,
     pub status: String,
+    pub is_test: bool,
+    // arrived after the professor's previous visit of the assignment page
+    pub is_new: bool,
     pub f: std::collections::HashMap<String, String>,
     pub first_ts_pretty: Option<String>,
     pub last_ts_pretty: Option<String>,
@@ -61,7 +69,13 @@ pub struct SubmissionCard {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub num_web_requests: Option<i64>,
     pub max_severity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub risk_score: Option<i64>,
     pub ai_domains: Vec<Visit>,
+    pub anomalies: Vec<String>,
+    pub violations: Vec<String>,
+    // hits to exam-provided domains (domain:count), shown but never suspicious
+    pub provided: Vec<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -70,8 +84,21 @@ pub struct Visit {
     pub severity: String,
 }
 
-pub fn build_cards(rows: &[SubmissionRow], findings: &[FindingRow]) -> Vec<SubmissionCard> {
+/// `dom` is one of the exam-provided domains or a subdomain of one
+pub fn is_provided_domain(dom: &str, provided: &[String]) -> bool {
+    let dom = dom.trim_end_matches('.').to_ascii_lowercase();
+    provided.iter().any(|p| dom == *p || dom.ends_with(&format!(".{p}")))
+}
+
+pub fn build_cards(
+    rows: &[SubmissionRow],
+    findings: &[FindingRow],
+    profile: &SensitivityProfile,
+    provided: &[String],
+    last_seen: Option<&str>,
+) -> Vec<SubmissionCard> {
     use std::collections::{HashMap, HashSet};
+    let last_seen = last_seen.and_then(parse_rfc3339);
     let mut by_sub: HashMap<&str, Vec<&FindingRow>> = HashMap::new();
     for f in findings { 
         by_sub.entry(&f.submission_ref)
@@ -85,18 +112,49 @@ pub fn build_cards(rows: &[SubmissionRow], findings: &[FindingRow]) -> Vec<Submi
         let mut max_sev = "info".to_string();
         let mut ai_domains = vec![];
         let mut seen_dom = HashSet::new();
+        let mut anomalies: Vec<String> = vec![];
+        let mut violations: Vec<String> = vec![];
+        let mut provided_hits: Vec<String> = vec![];
+        let ai_counts = profile.counts(FK_AI_HITS_TOTAL);
 
         if let Some(fs) = by_sub.get(r.id.as_str()) {
+            // AI usage that only went to exam-provided domains stays visible but isn't suspicious
+            let ai_doms: Vec<&str> = fs
+                .iter()
+                .filter(|f| f.key == FK_AI_DOMAIN)
+                .filter_map(|f| f.value.split(':').next())
+                .collect();
+            let ai_all_provided = !ai_doms.is_empty() && ai_doms.iter().all(|d| is_provided_domain(d, provided));
+
             for f in fs {
                 // keep first value per key
                 fkv.entry(f.key.clone()).or_insert_with(|| f.value.clone());
-                if f.key == "ai_domain" {
+                if (f.key == FK_AI_DOMAIN || f.key == FK_TOP_DOMAIN)
+                    && f.value.split(':').next().is_some_and(|d| is_provided_domain(d, provided))
+                    && !provided_hits.contains(&f.value)
+                {
+                    provided_hits.push(f.value.clone());
+                }
+                if ai_all_provided && AI_USAGE_KEYS.contains(&f.key.as_str()) {
+                    continue;
+                }
+                if profile.counts(&f.key) && is_truthy(&f.value) && !violations.contains(&f.key) {
+                    violations.push(f.key.clone());
+                    bump_severity(&mut max_sev, "critical");
+                }
+                if f.kind == KIND_ANOMALY {
+                    bump_severity(&mut max_sev, "anomaly");
+                    if !anomalies.contains(&f.key) {
+                        anomalies.push(f.key.clone());
+                    }
+                }
+                if f.key == FK_AI_DOMAIN {
                     if let Some(dom) = f.value.split(':').next() {
-                        if !seen_dom.insert(dom) { continue; }
+                        if is_provided_domain(dom, provided) || !seen_dom.insert(dom) { continue; }
                         let mut severity = "info".to_string();
-                        if AI_PROVIDER_BASES.iter().any(|ai| dom.ends_with(ai) || dom.contains(ai)) {
+                        if ai_counts && is_ai_domain(dom) {
                             severity = "critical".into();
-                            max_sev = "critical".into();
+                            bump_severity(&mut max_sev, "critical");
                         }
                         ai_domains.push(Visit{ domain: dom.to_string(), severity });
                     }
@@ -111,11 +169,14 @@ pub fn build_cards(rows: &[SubmissionRow], findings: &[FindingRow]) -> Vec<Submi
 
         let duration_minutes = match (fkv.get("first_ts").and_then(|s| parse_rfc3339(s)),
                                       fkv.get("last_ts").and_then(|s| parse_rfc3339(s))) {
+            // instant difference, independent of any local offset / DST
             (Some(a), Some(b)) => Some(((b - a).whole_minutes()).max(0)),
             _ => None
         };
 
         let num_web_requests = fkv.get("total_net_events").and_then(|s| s.parse::<i64>().ok());
+        // submissions analyzed before risk scoring have none
+        let risk_score = fkv.get(FK_RISK_SCORE).and_then(|s| s.parse::<i64>().ok());
 
         SubmissionCard {
             id: r.id.clone(),
@@ -123,6 +184,11 @@ pub fn build_cards(rows: &[SubmissionRow], findings: &[FindingRow]) -> Vec<Submi
             created_at: r.created_at.clone(),
             created_at_pretty: pretty_rfc3339(&r.created_at),
             status: r.status.clone(),
+            is_test: r.is_test,
+            is_new: match (last_seen, parse_rfc3339(&r.created_at)) {
+                (Some(seen), Some(created)) => created > seen,
+                _ => false,
+            },
             f: fkv,
             first_ts_pretty: first_pretty,
             last_ts_pretty: last_pretty,
@@ -130,19 +196,39 @@ pub fn build_cards(rows: &[SubmissionRow], findings: &[FindingRow]) -> Vec<Submi
             had_browser,
             num_web_requests,
             max_severity: max_sev,
+            risk_score,
             ai_domains,
+            anomalies,
+            violations,
+            provided: provided_hits,
         }
     }).collect()
 }
 
 
+/// Finding values that indicate "it happened": positive numbers, true/yes, or any X/Y ratio string
+fn is_truthy(v: &str) -> bool {
+    let t = v.trim().to_ascii_lowercase();
+    if let Ok(n) = t.parse::<f64>() {
+        return n > 0.0;
+    }
+    !matches!(t.as_str(), "" | "false" | "no" | "0")
+}
+
+/// Raise `cur` to `to` if `to` ranks higher in SEVERITY_ORDER
+fn bump_severity(cur: &mut String, to: &str) {
+    let rank = |s: &str| SEVERITY_ORDER.iter().position(|x| *x == s).unwrap_or(0);
+    if rank(to) > rank(cur) {
+        *cur = to.to_string();
+    }
+}
+
 fn pretty_rfc3339(s: &str) -> String {
     // fall back to raw string on any error
     let Ok(dt) = OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339) else {
         return s.to_string();
     };
-    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
-    let local = dt.to_offset(offset);
+    let local = to_local(dt);
     // Example: "Aug 27, 2025 18:59"
     let fmt = format_description::parse("[month repr:short] [day], [year] [hour]:[minute]").unwrap();
     local.format(&fmt).unwrap_or_else(|_| s.to_string())
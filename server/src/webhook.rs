@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>` keyed with PAL_WEBHOOK_SECRET
+pub const SIGNATURE_HEADER: &str = "X-Palantir-Signature";
+
+/// Outbound notification for high-risk submissions (PAL_WEBHOOK_*), e.g. into a Slack or
+/// Teams workflow. Nothing is sent unless `url` is set.
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    // signs the body when set, so the receiver can check it came from us
+    pub secret: Option<String>,
+    // fire at or above this risk_score / ai_hits_total; 0 disables that threshold
+    pub min_risk: i64,
+    pub min_ai_hits: i64,
+    pub timeout: Duration,
+}
+
+impl WebhookConfig {
+    /// `None` when PAL_WEBHOOK_URL is empty
+    pub fn from_env() -> Option<Self> {
+        let var = |k: &str| std::env::var(k).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        Some(WebhookConfig {
+            url: var("PAL_WEBHOOK_URL")?,
+            secret: var("PAL_WEBHOOK_SECRET"),
+            min_risk: var("PAL_WEBHOOK_MIN_RISK").and_then(|s| s.parse().ok()).unwrap_or(70),
+            min_ai_hits: var("PAL_WEBHOOK_MIN_AI_HITS").and_then(|s| s.parse().ok()).unwrap_or(0),
+            timeout: Duration::from_secs(var("PAL_WEBHOOK_TIMEOUT_SECS").and_then(|s| s.parse().ok()).unwrap_or(5)),
+        })
+    }
+
+    pub fn triggers(&self, risk_score: i64, ai_hits_total: i64) -> bool {
+        (self.min_risk > 0 && risk_score >= self.min_risk) || (self.min_ai_hits > 0 && ai_hits_total >= self.min_ai_hits)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct HighRiskPayload {
+    pub event: &'static str,
+    pub assignment_id: String,
+    // submissions.id, as in /admin/submissions/{id}
+    pub submission_id: String,
+    pub student_name: String,
+    pub student_username: Option<String>,
+    pub risk_score: i64,
+    pub ai_hits_total: i64,
+    // risk factors and anomalies, as key/value findings
+    pub top_findings: Vec<TopFinding>,
+    pub analyzed_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopFinding {
+    pub key: String,
+    pub value: String,
+}
+
+/// POST `payload` from a background thread; the worker never waits for the receiver and
+/// failures are only logged
+pub fn send(cfg: &WebhookConfig, payload: &HighRiskPayload) {
+    let body = match serde_json::to_vec(payload) {
+        Ok(b) => b,
+        Err(e) => {
+            log::warn!("webhook: serialize payload: {e}");
+            return;
+        }
+    };
+    let cfg = cfg.clone();
+    let id = payload.submission_id.clone();
+    std::thread::spawn(move || {
+        let client = match reqwest::blocking::Client::builder().timeout(cfg.timeout).build() {
+            Ok(c) => c,
+            Err(e) => {
+                log::warn!("webhook: client: {e}");
+                return;
+            }
+        };
+        let mut req = client
+            .post(&cfg.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &cfg.secret {
+            req = req.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+        }
+        match req.body(body).send() {
+            Ok(resp) if resp.status().is_success() => log::info!("webhook: notified about {id}"),
+            Ok(resp) => log::warn!("webhook: {} answered {} for {id}", cfg.url, resp.status()),
+            Err(e) => log::warn!("webhook: POST {} for {id}: {e}", cfg.url),
+        }
+    });
+}
+
+/// Hex HMAC-SHA256 of `body`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}